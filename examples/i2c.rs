@@ -75,8 +75,8 @@ fn main() -> ! {
     // Associate DMA channels with I2C1: One for transmit; one for receive.
     // Note that mux is not used on F3, F4, and most L4s: DMA channels are hard-coded
     // to peripherals on those platforms.
-    dma::mux(DmaPeriph::Dma1, DmaChannel::C6, DmaInput::I2c1Tx);
-    dma::mux(DmaPeriph::Dma1, DmaChannel::C7, DmaInput::I2c1Rx);
+    dma::mux(DmaPeriph::Dma1, DmaChannel::C6, DmaInput::I2c1Tx).unwrap();
+    dma::mux(DmaPeriph::Dma1, DmaChannel::C7, DmaInput::I2c1Rx).unwrap();
 
     // Write to DMA, requesting readings
     unsafe {
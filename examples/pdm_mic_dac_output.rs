@@ -168,9 +168,9 @@ mod app {
 
         let mut dma = Dma::new(dp.DMA1);
 
-        dma::mux(DmaPeriph::Dma1, DmaChannel::C0, dma::DmaInput::Dfsdm1F0);
-        dma::mux(DmaPeriph::Dma1, DmaChannel::C1, dma::DmaInput::Dfsdm1F11);
-        dma::mux(DmaPeriph::Dma1, DmaChannel::C2, dma::DmaInput::DacCh1);
+        dma::mux(DmaPeriph::Dma1, DmaChannel::C0, dma::DmaInput::Dfsdm1F0).unwrap();
+        dma::mux(DmaPeriph::Dma1, DmaChannel::C1, dma::DmaInput::Dfsdm1F11).unwrap();
+        dma::mux(DmaPeriph::Dma1, DmaChannel::C2, dma::DmaInput::DacCh1).unwrap();
 
         dma.enable_interrupt(DmaChannel::C0, DmaInterrupt::HalfTransfer);
         dma.enable_interrupt(DmaChannel::C0, DmaInterrupt::TransferComplete);
@@ -5,6 +5,9 @@
 //!
 //! Note: For many cases when reading or writing multiple words, DMA should be the
 //! first choice, to minimize CPU use.
+//!
+//! `usart::BufferedUsart` packages this approach: It queues received and transmitted data in ring
+//! buffers from its `on_interrupt()` method, and records receive errors.
 
 #![no_main]
 #![no_std]
@@ -79,7 +82,7 @@ fn main() -> ! {
     // attention to how you know when a message starts and ends, if not of a fixed size.
     let mut dma = Dma::new(dp.DMA1);
     // This DMA MUX step isn't required on F3, F4, and most L4 variants.
-    dma::mux(DMA_PERIPH, DMA_CH, DmaInput::Usart1Tx);
+    dma::mux(DMA_PERIPH, DMA_CH, DmaInput::Usart1Tx).unwrap();
     dma.enable_interrupt(DMA_CH, DmaInterrupt::TransferComplete);
 
     // Example of how to start a DMA transfer:
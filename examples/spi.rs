@@ -74,8 +74,8 @@ fn main() -> ! {
     // Associate a pair of DMA channels with SPI1: One for transmit; one for receive.
     // Note that mux is not used on F3, F4, and most L4s: DMA channels are hard-coded
     // to peripherals on those platforms.
-    dma::mux(DmaPeriph::Dma2, DmaChannel::C1, DmaInput::Spi1Tx);
-    dma::mux(DmaPeriph::Dma2, DmaChannel::C2, DmaInput::Spi1Rx);
+    dma::mux(DmaPeriph::Dma2, DmaChannel::C1, DmaInput::Spi1Tx).unwrap();
+    dma::mux(DmaPeriph::Dma2, DmaChannel::C2, DmaInput::Spi1Rx).unwrap();
 
     cs.set_low();
 
@@ -401,8 +401,8 @@ mod app {
 
         // Assign appropriate DMA channels to SPI transmit and receive. (Required on DMAMUX-supporting
         // MCUs only; channels are hard-coded on older ones).
-        dma::mux(DmaPeriph::Dma1, DmaChannel::C1, DmaInput::Spi1Tx);
-        dma::mux(DmaPeriph::Dma1, DmaChannel::C2, DmaInput::Spi1Rx);
+        dma::mux(DmaPeriph::Dma1, DmaChannel::C1, DmaInput::Spi1Tx).unwrap();
+        dma::mux(DmaPeriph::Dma1, DmaChannel::C2, DmaInput::Spi1Rx).unwrap();
 
         // We use Spi transfer complete to know when our readings are ready.
         dma.enable_interrupt(DmaChannel::C2, DmaInterrupt::TransferComplete);
@@ -111,7 +111,7 @@ fn main() -> ! {
     // Configure DMA, to be used by peripherals.
     let mut dma = Dma::new(&mut dp.DMA1);
 
-    dma::mux(DmaPeriph::Dma1, DmaChannel::C2, DmaInput::Adc2);
+    dma::mux(DmaPeriph::Dma1, DmaChannel::C2, DmaInput::Adc2).unwrap();
 
     let spi_cfg = SpiConfig {
         mode: SpiMode::mode3(), // SpiConfig::default() uses mode 0.
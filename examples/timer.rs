@@ -108,7 +108,7 @@ fn main() -> ! {
     // arbitrary waveforms.
 
     let mut dma = Dma::new(dp.DMA1);
-    dma::mux(DmaPeriph::Dma1, DmaChannel::C1, DmaInput::Tim3Up);
+    dma::mux(DmaPeriph::Dma1, DmaChannel::C1, DmaInput::Tim3Up).unwrap();
     timer.enable_interrupt(TimerInterrupt::UpdateDma);
 
     timer.rotors.write_dma_burst(
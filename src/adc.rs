@@ -14,9 +14,9 @@ use cfg_if::cfg_if;
 use paste::paste;
 
 #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
-use crate::dma::{self, ChannelCfg, DmaChannel};
+use crate::dma::{self, ChannelCfg, Circular, DmaChannel, Transfer, WriteBuffer};
 
-//#[cfg(any(feature = "f3", feature = "l4"))]
+#[cfg(any(feature = "f3", feature = "l4", feature = "g4"))]
 use crate::dma::DmaInput;
 
 // Address of the ADCinterval voltage reference. This address is found in the User manual. It appears
@@ -977,6 +977,8 @@ macro_rules! hal {
             /// Take a reading, using DMA. Sets conversion sequence; no need to set it directly.
             /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
             /// and can't be configured using the DMAUX peripheral. (`dma::mux()` fn).
+            /// It's only used there if this peripheral isn't connected to `dma_periph`. On G4, panics
+            /// if `dma_channel` doesn't exist on `dma_periph`.
             pub unsafe fn read_dma(
                 &mut self, buf: &mut [u16],
                 adc_channels: &[u8],
@@ -991,43 +993,17 @@ macro_rules! hal {
 
                 // Configure the DMA mux on G4
                 #[cfg(feature = "g4")]
-                match self.device {
-                    AdcDevice::One => {
-                        dma::mux(
-                            dma_periph,
-                            dma_channel,
-                            DmaInput::Adc1
-                        );
-                    }
-                    AdcDevice::Two => {
-                        dma::mux(
-                            dma_periph,
-                            dma_channel,
-                            DmaInput::Adc2
-                        );
-                    }
-                    AdcDevice::Three => {
-                        dma::mux(
-                            dma_periph,
-                            dma_channel,
-                            DmaInput::Adc3
-                        );
-                    }
-                    AdcDevice::Four => {
-                        dma::mux(
-                            dma_periph,
-                            dma_channel,
-                            DmaInput::Adc4
-                        );
-                    }
-                    AdcDevice::Five => {
-                        dma::mux(
-                            dma_periph,
-                            dma_channel,
-                            DmaInput::Adc5
-                        );
-                    }
-                    _ => unimplemented!(),
+                {
+                    let dma_input = match self.device {
+                        AdcDevice::One => DmaInput::Adc1,
+                        AdcDevice::Two => DmaInput::Adc2,
+                        AdcDevice::Three => DmaInput::Adc3,
+                        AdcDevice::Four => DmaInput::Adc4,
+                        AdcDevice::Five => DmaInput::Adc5,
+                        _ => unimplemented!(),
+                    };
+                    dma::mux(dma_periph, dma_channel, dma_input)
+                        .expect("This DMA channel doesn't exist on this DMA peripheral");
                 }
 
                 let (ptr, len) = (buf.as_mut_ptr(), buf.len());
@@ -1048,35 +1024,14 @@ macro_rules! hal {
                     w.dmngt().bits(if channel_cfg.circular == dma::Circular::Enabled { 0b11 } else { 0b01 })
                 });
 
-                // L44 RM, Table 41. "DMA1 requests for each channel
-                // todo: DMA2 support.
                 #[cfg(any(feature = "f3", feature = "l4"))]
-                let dma_channel = match self.device {
-                    AdcDevice::One => DmaInput::Adc1.dma1_channel(),
-                    AdcDevice::Two => DmaInput::Adc2.dma1_channel(),
-                    _ => panic!("DMA on ADC beyond 2 is not supported. If it is for your MCU, please submit an issue \
-                or PR on Github.")
-                };
+                let dma_input = self.dma_input();
+
+                #[cfg(any(feature = "f3", feature = "l4"))]
+                let dma_channel = dma_input.channel(dma_periph).unwrap_or(dma_channel);
 
                 #[cfg(feature = "l4")]
-                match dma_periph {
-                    dma::DmaPeriph::Dma1 => {
-                        let mut regs = unsafe { &(*pac::DMA1::ptr()) };
-                        match self.device {
-                            AdcDevice::One => dma::channel_select(&mut regs, DmaInput::Adc1),
-                            AdcDevice::Two => dma::channel_select(&mut regs, DmaInput::Adc2),
-                            _ => unimplemented!(),
-                        }
-                    }
-                    dma::DmaPeriph::Dma2 => {
-                        let mut regs = unsafe { &(*pac::DMA2::ptr()) };
-                        match self.device {
-                            AdcDevice::One => dma::channel_select(&mut regs, DmaInput::Adc1),
-                            AdcDevice::Two => dma::channel_select(&mut regs, DmaInput::Adc2),
-                            _ => unimplemented!(),
-                        }
-                    }
-                }
+                dma::channel_select(dma_periph, dma_input);
 
 
                 let mut seq_len = 0;
@@ -1162,6 +1117,55 @@ macro_rules! hal {
                 }
             }
 
+            #[cfg(any(feature = "f3", feature = "l4"))]
+            /// The DMA request for this ADC. L44 RM, Table 41. "DMA1 requests for each channel", and
+            /// Table 42: "DMA2 requests for each channel"
+            fn dma_input(&self) -> DmaInput {
+                match self.device {
+                    AdcDevice::One => DmaInput::Adc1,
+                    AdcDevice::Two => DmaInput::Adc2,
+                    _ => panic!("DMA on ADC beyond 2 is not supported. If it is for your MCU, please submit an issue \
+                or PR on Github.")
+                }
+            }
+
+            #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+            /// Take a reading using DMA, without `unsafe`: The returned `Transfer` owns this ADC and
+            /// the buffer until the transfer completes or is aborted. Otherwise, the same as `read_dma()`.
+            pub fn read_dma_transfer<B: WriteBuffer<Word = u16>>(
+                mut self,
+                mut buf: B,
+                adc_channels: &[u8],
+                dma_channel: DmaChannel,
+                channel_cfg: ChannelCfg,
+                dma_periph: dma::DmaPeriph,
+            ) -> Transfer<Self, B> {
+                let (ptr, len) = buf.write_buffer();
+
+                // A circular transfer never completes, so `Transfer::wait()` would block forever.
+                let channel_cfg = ChannelCfg {
+                    circular: Circular::Disabled,
+                    ..channel_cfg
+                };
+
+                #[cfg(any(feature = "f3", feature = "l4"))]
+                let dma_channel = self.dma_input().channel(dma_periph).unwrap_or(dma_channel);
+
+                // The buffer is owned by the transfer, and `'static` or pinned, so stays valid while DMA
+                // uses it.
+                unsafe {
+                    self.read_dma(
+                        core::slice::from_raw_parts_mut(ptr, len),
+                        adc_channels,
+                        dma_channel,
+                        channel_cfg,
+                        dma_periph,
+                    );
+                }
+
+                Transfer::new(self, buf, dma_channel, dma_periph)
+            }
+
             /// Enable a specific type of ADC interrupt.
             pub fn enable_interrupt(&mut self, interrupt: AdcInterrupt) {
                 self.regs.ier.modify(|_, w| match interrupt {
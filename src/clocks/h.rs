@@ -1166,6 +1166,12 @@ impl Clocks {
         }
     }
 
+    #[cfg(feature = "h7")]
+    /// Get the APB4 peripheral clock frequency (rcc_pclk4), in hz. Eg LPUART1 is on this bus.
+    pub fn apb4(&self) -> u32 {
+        self.hclk() / self.d3_prescaler.value() as u32
+    }
+
     /// Get the SAI1 audio clock frequency, in hz
     pub fn sai1_speed(&self) -> u32 {
         let pll_src = match self.input_src {
@@ -439,6 +439,7 @@ where
     /// output.
     /// Note that the `dma_channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
     pub unsafe fn write_dma(
         &mut self,
@@ -455,28 +456,16 @@ where
         let (ptr, len) = (buf.as_ptr(), buf.len());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let dma_channel = match dac_channel {
-            DacChannel::C1 => DmaInput::Dac1Ch1.dma1_channel(),
-            DacChannel::C2 => DmaInput::Dac1Ch2.dma1_channel(),
+        let dma_input = match dac_channel {
+            DacChannel::C1 => DmaInput::Dac1Ch1,
+            DacChannel::C2 => DmaInput::Dac1Ch2,
         };
 
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let dma_channel = dma_input.channel(dma_periph).unwrap_or(dma_channel);
+
         #[cfg(feature = "l4")]
-        match dma_periph {
-            dma::DmaPeriph::Dma1 => {
-                let mut regs = unsafe { &(*DMA1::ptr()) };
-                match dac_channel {
-                    DacChannel::C1 => dma::channel_select(&mut regs, DmaInput::Dac1Ch1),
-                    DacChannel::C2 => dma::channel_select(&mut regs, DmaInput::Dac1Ch2),
-                }
-            }
-            dma::DmaPeriph::Dma2 => {
-                let mut regs = unsafe { &(*pac::DMA2::ptr()) };
-                match dac_channel {
-                    DacChannel::C1 => dma::channel_select(&mut regs, DmaInput::Dac1Ch1),
-                    DacChannel::C2 => dma::channel_select(&mut regs, DmaInput::Dac1Ch2),
-                };
-            }
-        }
+        dma::channel_select(dma_periph, dma_input);
 
         // H743 RM, section 26.4.8: DMA requests
         // Each DAC channel has a DMA capability. Two DMA channels are used to service DAC
@@ -1184,31 +1184,18 @@ where
 
         #[cfg(feature = "f3")]
         let dma_channel = match filter {
-            Filter::F0 => DmaInput::Dfsdm1F0.dma1_channel(),
-            Filter::F1 => DmaInput::Dfsdm1F1.dma1_channel(),
-        };
+            Filter::F0 => DmaInput::Dfsdm1F0.channel(dma_periph),
+            Filter::F1 => DmaInput::Dfsdm1F1.channel(dma_periph),
+        }
+        .unwrap_or(dma_channel);
 
         #[cfg(feature = "l4")]
-        match dma_periph {
-            dma::DmaPeriph::Dma1 => {
-                let mut regs = unsafe { &(*DMA1::ptr()) };
-                match filter {
-                    Filter::F0 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F0),
-                    Filter::F1 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F1),
-                    Filter::F2 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F2),
-                    Filter::F3 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F3),
-                };
-            }
-            dma::DmaPeriph::Dma2 => {
-                let mut regs = unsafe { &(*pac::DMA2::ptr()) };
-                match filter {
-                    Filter::F0 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F0),
-                    Filter::F1 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F1),
-                    Filter::F2 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F2),
-                    Filter::F3 => dma::channel_select(&mut regs, DmaInput::Dfsdm1F3),
-                };
-            }
-        }
+        match filter {
+            Filter::F0 => dma::channel_select(dma_periph, DmaInput::Dfsdm1F0),
+            Filter::F1 => dma::channel_select(dma_periph, DmaInput::Dfsdm1F1),
+            Filter::F2 => dma::channel_select(dma_periph, DmaInput::Dfsdm1F2),
+            Filter::F3 => dma::channel_select(dma_periph, DmaInput::Dfsdm1F3),
+        };
 
         match filter {
             Filter::F0 => {
@@ -14,7 +14,8 @@
 // }
 
 use core::{
-    ops::Deref,
+    ops::{Deref, DerefMut},
+    pin::Pin,
     sync::atomic::{self, Ordering},
 };
 
@@ -263,6 +264,17 @@ pub enum DmaInput2 {
 }
 
 impl DmaInput {
+    #[cfg(any(feature = "f3", feature = "l4"))]
+    /// Select the hard set channel associated with a given input source, on a given DMA peripheral.
+    /// Returns `None` if the input isn't connected to DMA2.
+    pub fn channel(&self, periph: DmaPeriph) -> Option<DmaChannel> {
+        match periph {
+            DmaPeriph::Dma1 => Some(self.dma1_channel()),
+            #[cfg(not(feature = "f3x4"))]
+            DmaPeriph::Dma2 => self.dma2_channel(),
+        }
+    }
+
     #[cfg(any(feature = "f3", feature = "l4"))]
     /// Select the hard set channel associated with a given input source. See L44 RM, Table 41.
     pub fn dma1_channel(&self) -> DmaChannel {
@@ -313,6 +325,92 @@ impl DmaInput {
         }
     }
 
+    #[cfg(feature = "f3")]
+    /// Select the hard set DMA2 channel associated with a given input source. See F303 RM, Table 79.
+    /// Returns `None` if the input isn't connected to DMA2.
+    pub fn dma2_channel(&self) -> Option<DmaChannel> {
+        match self {
+            Self::Adc2 => Some(DmaChannel::C1),
+            Self::Adc4 => Some(DmaChannel::C2),
+            Self::Adc3 => Some(DmaChannel::C5),
+            Self::Spi3Rx => Some(DmaChannel::C1),
+            Self::Spi3Tx => Some(DmaChannel::C2),
+            Self::Uart4Rx => Some(DmaChannel::C3),
+            Self::Uart4Tx => Some(DmaChannel::C5),
+            Self::Dac1Ch1 => Some(DmaChannel::C3),
+            Self::Dac1Ch2 => Some(DmaChannel::C4),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "l4")]
+    /// Select the hard set DMA2 channel associated with a given input source. See L44 RM, Table 42.
+    /// Returns `None` if the input isn't connected to DMA2.
+    pub fn dma2_channel(&self) -> Option<DmaChannel> {
+        match self {
+            Self::Adc1 => Some(DmaChannel::C3),
+            Self::Adc2 => Some(DmaChannel::C4),
+            Self::Adc3 => Some(DmaChannel::C5),
+            Self::Dac1Ch1 => Some(DmaChannel::C4),
+            Self::Dac1Ch2 => Some(DmaChannel::C5),
+            Self::Spi1Rx => Some(DmaChannel::C3),
+            Self::Spi1Tx => Some(DmaChannel::C4),
+            Self::Spi3Rx => Some(DmaChannel::C1),
+            Self::Spi3Tx => Some(DmaChannel::C2),
+            Self::I2c1Rx => Some(DmaChannel::C6),
+            Self::I2c1Tx => Some(DmaChannel::C7),
+            Self::I2c4Rx => Some(DmaChannel::C1),
+            Self::I2c4Tx => Some(DmaChannel::C2),
+            Self::Usart1Rx => Some(DmaChannel::C7),
+            Self::Usart1Tx => Some(DmaChannel::C6),
+            Self::Uart4Rx => Some(DmaChannel::C5),
+            Self::Uart4Tx => Some(DmaChannel::C3),
+            Self::Uart5Rx => Some(DmaChannel::C2),
+            Self::Uart5Tx => Some(DmaChannel::C1),
+            Self::Lpuart1Rx => Some(DmaChannel::C7),
+            Self::Lpuart1Tx => Some(DmaChannel::C6),
+            Self::Sai1A => Some(DmaChannel::C1),
+            Self::Sai1B => Some(DmaChannel::C2),
+            Self::Sai2A => Some(DmaChannel::C6),
+            Self::Sai2B => Some(DmaChannel::C7),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "l4")]
+    /// Find the value to set in the DMA_CSELR register, for DMA2 on L4. See L44 RM, Table 42.
+    /// Returns `None` if the input isn't connected to DMA2.
+    pub fn dma2_channel_select(&self) -> Option<u8> {
+        match self {
+            Self::Adc1 => Some(0b000),
+            Self::Adc2 => Some(0b000),
+            Self::Adc3 => Some(0b000),
+            Self::I2c4Rx => Some(0b000),
+            Self::I2c4Tx => Some(0b000),
+            Self::Sai1A => Some(0b001),
+            Self::Sai1B => Some(0b001),
+            Self::Sai2A => Some(0b001),
+            Self::Sai2B => Some(0b001),
+            Self::Usart1Rx => Some(0b010),
+            Self::Usart1Tx => Some(0b010),
+            Self::Uart4Rx => Some(0b010),
+            Self::Uart4Tx => Some(0b010),
+            Self::Uart5Rx => Some(0b010),
+            Self::Uart5Tx => Some(0b010),
+            Self::Spi3Rx => Some(0b011),
+            Self::Spi3Tx => Some(0b011),
+            Self::Dac1Ch1 => Some(0b011),
+            Self::Dac1Ch2 => Some(0b011),
+            Self::Spi1Rx => Some(0b100),
+            Self::Spi1Tx => Some(0b100),
+            Self::Lpuart1Rx => Some(0b100),
+            Self::Lpuart1Tx => Some(0b100),
+            Self::I2c1Rx => Some(0b101),
+            Self::I2c1Tx => Some(0b101),
+            _ => None,
+        }
+    }
+
     #[cfg(feature = "l4")]
     /// Find the value to set in the DMA_CSELR register, for L4. Ie, channel select value for a given DMA input.
     /// See L44 RM, Table 41.
@@ -671,11 +769,6 @@ where
         )
     }
 
-    #[cfg(feature = "l4")]
-    pub(crate) fn channel_select(&mut self, input: DmaInput) {
-        channel_select(&mut self.regs, input);
-    }
-
     /// Stop a DMA transfer, if in progress.
     pub fn stop(&mut self, channel: DmaChannel) {
         stop_internal(&mut self.regs, channel);
@@ -1210,6 +1303,77 @@ pub fn cfg_channel<D>(
     cfg: ChannelCfg,
 ) where
     D: Deref<Target = dma1::RegisterBlock>,
+{
+    cfg_stream(
+        regs,
+        channel,
+        periph_addr,
+        mem_addr,
+        None,
+        num_data,
+        direction,
+        periph_size,
+        mem_size,
+        cfg,
+    );
+}
+
+/// Configure a DMA stream in double-buffer mode. H743 RM, section 15.3.10: "Double-buffer mode":
+/// "This mode is available for all the DMA1 and DMA2 streams. The double-buffer mode is enabled by
+/// setting the DBM bit in the DMA_SxCR register. A double-buffer stream works as a regular (single
+/// buffer) stream with the difference that it has two memory pointers. When the double-buffer mode
+/// is enabled, the circular mode is automatically enabled (CIRC bit in DMA_SxCR is not relevant)
+/// and at each end of transaction, the memory pointers are swapped."
+///
+/// `mem0_addr` and `mem1_addr` are the addresses of two buffers of `num_data` words each. The
+/// stream starts with buffer 0. While the DMA uses one buffer, software can process the other, and
+/// optionally replace it using `set_inactive_buffer()`. Use `current_target()` to find which
+/// buffer is in use. Sets the Transfer Complete interrupt, which fires at each swap.
+#[cfg(feature = "h7")]
+pub fn cfg_channel_double_buffer<D>(
+    regs: &mut D,
+    channel: DmaChannel,
+    periph_addr: u32,
+    mem0_addr: u32,
+    mem1_addr: u32,
+    num_data: u32,
+    direction: Direction,
+    periph_size: DataSize,
+    mem_size: DataSize,
+    cfg: ChannelCfg,
+) where
+    D: Deref<Target = dma1::RegisterBlock>,
+{
+    cfg_stream(
+        regs,
+        channel,
+        periph_addr,
+        mem0_addr,
+        Some(mem1_addr),
+        num_data,
+        direction,
+        periph_size,
+        mem_size,
+        cfg,
+    );
+}
+
+/// Helper function for `cfg_channel` and `cfg_channel_double_buffer`. Double-buffer mode is
+/// enabled if `mem1_addr` is `Some`.
+#[cfg(feature = "h7")]
+fn cfg_stream<D>(
+    regs: &mut D,
+    channel: DmaChannel,
+    periph_addr: u32,
+    mem_addr: u32,
+    mem1_addr: Option<u32>,
+    num_data: u32,
+    direction: Direction,
+    periph_size: DataSize,
+    mem_size: DataSize,
+    cfg: ChannelCfg,
+) where
+    D: Deref<Target = dma1::RegisterBlock>,
 {
     // todo: The H7 sections are different, but we consolidated the comments. Figure out
     // todo what's different and fix it by following the steps
@@ -1236,7 +1400,12 @@ pub fn cfg_channel<D>(
         .m0ar
         .write(|w| unsafe { w.bits(mem_addr) });
 
-    // todo: m1ar too, if in double-buffer mode.
+    // "DMA_SxM1AR: This register is used only for the double-buffer mode."
+    if let Some(addr) = mem1_addr {
+        regs.st[channel as usize]
+            .m1ar
+            .write(|w| unsafe { w.bits(addr) });
+    }
 
     // 3. Configure the total number of data to transfer in the DMA_CNDTRx register.
     // After each data transfer, this value is decremented.
@@ -1291,6 +1460,10 @@ pub fn cfg_channel<D>(
         w.dir().bits(direction as u8);
         // – the circular mode
         w.circ().bit(cfg.circular as u8 != 0);
+        // – double-buffer mode, starting with memory 0. "CT: This bit can be written only if EN
+        // is 0 to indicate the target memory area of the first transfer."
+        w.dbm().bit(mem1_addr.is_some());
+        w.ct().clear_bit();
         // – the peripheral and memory incremented mode
         w.pinc().bit(cfg.periph_incr as u8 != 0);
         w.minc().bit(cfg.mem_incr as u8 != 0);
@@ -1470,6 +1643,386 @@ pub fn stop(periph: DmaPeriph, channel: DmaChannel) {
     }
 }
 
+/// Read the number of data items remaining in the current transfer. (CNDTR register)
+#[cfg(not(feature = "h7"))]
+fn transfers_remaining_internal<D>(regs: &D, channel: DmaChannel) -> u16
+where
+    D: Deref<Target = dma1::RegisterBlock>,
+{
+    cfg_if! {
+        if #[cfg(any(feature = "f3", feature = "g0"))] {
+            match channel {
+                DmaChannel::C1 => regs.ch1.ndtr.read().ndt().bits(),
+                DmaChannel::C2 => regs.ch2.ndtr.read().ndt().bits(),
+                DmaChannel::C3 => regs.ch3.ndtr.read().ndt().bits(),
+                DmaChannel::C4 => regs.ch4.ndtr.read().ndt().bits(),
+                DmaChannel::C5 => regs.ch5.ndtr.read().ndt().bits(),
+                #[cfg(not(feature = "g0"))]
+                DmaChannel::C6 => regs.ch6.ndtr.read().ndt().bits(),
+                #[cfg(not(feature = "g0"))]
+                DmaChannel::C7 => regs.ch7.ndtr.read().ndt().bits(),
+            }
+        } else if #[cfg(any(feature = "wl", feature = "l5"))] {
+            // The WL and L5 PACs use a 32-bit NDT field, although only the low 16 bits are used.
+            match channel {
+                DmaChannel::C1 => regs.cndtr1.read().ndt().bits() as u16,
+                DmaChannel::C2 => regs.cndtr2.read().ndt().bits() as u16,
+                DmaChannel::C3 => regs.cndtr3.read().ndt().bits() as u16,
+                DmaChannel::C4 => regs.cndtr4.read().ndt().bits() as u16,
+                DmaChannel::C5 => regs.cndtr5.read().ndt().bits() as u16,
+                DmaChannel::C6 => regs.cndtr6.read().ndt().bits() as u16,
+                DmaChannel::C7 => regs.cndtr7.read().ndt().bits() as u16,
+                #[cfg(feature = "l5")]
+                DmaChannel::C8 => regs.cndtr8.read().ndt().bits() as u16,
+            }
+        } else {
+            match channel {
+                DmaChannel::C1 => regs.cndtr1.read().ndt().bits(),
+                DmaChannel::C2 => regs.cndtr2.read().ndt().bits(),
+                DmaChannel::C3 => regs.cndtr3.read().ndt().bits(),
+                DmaChannel::C4 => regs.cndtr4.read().ndt().bits(),
+                DmaChannel::C5 => regs.cndtr5.read().ndt().bits(),
+                DmaChannel::C6 => regs.cndtr6.read().ndt().bits(),
+                DmaChannel::C7 => regs.cndtr7.read().ndt().bits(),
+                #[cfg(feature = "g4")]
+                DmaChannel::C8 => regs.cndtr8.read().ndt().bits(),
+            }
+        }
+    }
+}
+
+/// Read the number of data items remaining in the current transfer. (NDTR register)
+#[cfg(feature = "h7")]
+fn transfers_remaining_internal<D>(regs: &D, channel: DmaChannel) -> u16
+where
+    D: Deref<Target = dma1::RegisterBlock>,
+{
+    regs.st[channel as usize].ndtr.read().ndt().bits()
+}
+
+/// Read the number of data items remaining to be transferred on a channel. In circular mode, this
+/// is reloaded with the initial value each time the end of the buffer is reached; it can be used to
+/// find the position the DMA is currently writing to, or reading from.
+pub fn transfers_remaining(periph: DmaPeriph, channel: DmaChannel) -> u16 {
+    match periph {
+        DmaPeriph::Dma1 => {
+            let regs = unsafe { &(*DMA1::ptr()) };
+            transfers_remaining_internal(&regs, channel)
+        }
+        #[cfg(not(any(
+            feature = "f3x4",
+            all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+            feature = "wb",
+        )))]
+        DmaPeriph::Dma2 => {
+            let regs = unsafe { &(*pac::DMA2::ptr()) };
+            transfers_remaining_internal(&regs, channel)
+        }
+    }
+}
+
+/// Read the Transfer Error flag of a channel. (ISR register, TEIFx field; LISR or HISR on H7)
+/// "When a bus error occurs during a DMA read or write access, the hardware automatically
+/// clears the EN bit of the channel x [...]".
+fn transfer_error(periph: DmaPeriph, channel: DmaChannel) -> bool {
+    cfg_if! {
+        if #[cfg(feature = "h7")] {
+            let regs = match periph {
+                DmaPeriph::Dma1 => unsafe { &(*DMA1::ptr()) },
+                DmaPeriph::Dma2 => unsafe { &(*DMA2::ptr()) },
+            };
+
+            // Streams 0-3 are in LISR, and 4-7 in HISR, at bit offsets 0, 6, 16, and 22.
+            let (isr, i) = if (channel as u8) < 4 {
+                (regs.lisr.read().bits(), channel as u8)
+            } else {
+                (regs.hisr.read().bits(), channel as u8 - 4)
+            };
+            let offset = [0, 6, 16, 22][i as usize];
+
+            isr & (1 << (offset + 3)) != 0
+        } else {
+            let isr = match periph {
+                DmaPeriph::Dma1 => unsafe { (*DMA1::ptr()).isr.read().bits() },
+                #[cfg(not(any(
+                    feature = "f3x4",
+                    all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+                    feature = "wb",
+                )))]
+                DmaPeriph::Dma2 => unsafe { (*pac::DMA2::ptr()).isr.read().bits() },
+            };
+
+            // Each channel has 4 flags: GIF, TCIF, HTIF, and TEIF, starting with channel 1.
+            isr & (1 << (4 * (channel as u8 - 1) + 3)) != 0
+        }
+    }
+}
+
+/// A buffer DMA can read from, for transfers from memory to a peripheral. Implemented for `'static`
+/// buffers, and pinned ones behind a `StableDeref` pointer: a `Transfer` can be leaked (eg with
+/// `mem::forget`) without ending the DMA transfer, so a borrowed buffer could be freed while DMA is
+/// still accessing it.
+///
+/// # Safety
+/// The pointer and length returned must be valid for as long as the value exists, and must not
+/// change when the value is moved.
+pub unsafe trait ReadBuffer {
+    type Word;
+
+    /// The buffer's address, and length in words.
+    fn read_buffer(&self) -> (*const Self::Word, usize);
+}
+
+/// A buffer DMA can write to, for transfers from a peripheral to memory. See `ReadBuffer`.
+///
+/// # Safety
+/// The pointer and length returned must be valid for as long as the value exists, and must not
+/// change when the value is moved.
+pub unsafe trait WriteBuffer {
+    type Word;
+
+    /// The buffer's address, and length in words.
+    fn write_buffer(&mut self) -> (*mut Self::Word, usize);
+}
+
+unsafe impl<W> ReadBuffer for &'static [W] {
+    type Word = W;
+
+    fn read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<W> ReadBuffer for &'static mut [W] {
+    type Word = W;
+
+    fn read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<W, const N: usize> ReadBuffer for &'static [W; N] {
+    type Word = W;
+
+    fn read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl<W, const N: usize> ReadBuffer for &'static mut [W; N] {
+    type Word = W;
+
+    fn read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl<W> WriteBuffer for &'static mut [W] {
+    type Word = W;
+
+    fn write_buffer(&mut self) -> (*mut W, usize) {
+        (self.as_mut_ptr(), self.len())
+    }
+}
+
+unsafe impl<W, const N: usize> WriteBuffer for &'static mut [W; N] {
+    type Word = W;
+
+    fn write_buffer(&mut self) -> (*mut W, usize) {
+        (self.as_mut_ptr(), N)
+    }
+}
+
+/// A pointer whose target stays at the same address when the pointer is moved, and stays valid for
+/// as long as the pointer exists. Eg `&'static mut [u8]`, or a box from a static memory pool. Pinned
+/// buffers behind one of these can be used in a `Transfer`.
+///
+/// # Safety
+/// Moving the pointer must not move its target, or change the address `deref()` returns.
+pub unsafe trait StableDeref: Deref {}
+
+unsafe impl<T: ?Sized> StableDeref for &'static T {}
+
+unsafe impl<T: ?Sized> StableDeref for &'static mut T {}
+
+unsafe impl<P, W> ReadBuffer for Pin<P>
+where
+    P: StableDeref<Target = [W]> + 'static,
+{
+    type Word = W;
+
+    fn read_buffer(&self) -> (*const W, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<P, W> WriteBuffer for Pin<P>
+where
+    P: StableDeref<Target = [W]> + DerefMut + 'static,
+{
+    type Word = W;
+
+    fn write_buffer(&mut self) -> (*mut W, usize) {
+        // We only take the buffer's address here; nothing is moved out of it.
+        let buf = unsafe { self.as_mut().get_unchecked_mut() };
+        (buf.as_mut_ptr(), buf.len())
+    }
+}
+
+/// DMA errors.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The Transfer Error flag was set; eg from a bus error when accessing the buffer or
+    /// peripheral. The hardware disables the channel when this happens.
+    Transfer,
+    /// The channel doesn't exist on the DMA peripheral selected.
+    UnsupportedChannel,
+}
+
+/// A DMA transfer in progress. This owns the buffer, and the peripheral using it, so neither can
+/// be accessed until the transfer completes or is aborted, when they're returned. Created by
+/// peripheral functions such as `Spi::write_dma_transfer()`, and `Usart::read_dma_transfer()`.
+/// Any `dma::mux()` configuration is unchanged; set it up before starting the transfer as usual.
+///
+/// This is for one-shot transfers; the peripheral functions that create one disable circular mode.
+pub struct Transfer<P, B> {
+    periph: P,
+    buf: B,
+    channel: DmaChannel,
+    dma_periph: DmaPeriph,
+}
+
+impl<P, B> Transfer<P, B> {
+    /// Used by peripheral modules after starting a DMA transfer, to wrap the peripheral and buffer.
+    pub(crate) fn new(periph: P, buf: B, channel: DmaChannel, dma_periph: DmaPeriph) -> Self {
+        Self {
+            periph,
+            buf,
+            channel,
+            dma_periph,
+        }
+    }
+
+    /// Returns `true` if all data has been transferred. Note that the peripheral may still be
+    /// sending the last word; eg, check the peripheral's status flags before disabling it. Returns
+    /// an error if the Transfer Error flag is set, in which case the transfer won't complete.
+    pub fn is_complete(&self) -> Result<bool, Error> {
+        if transfer_error(self.dma_periph, self.channel) {
+            return Err(Error::Transfer);
+        }
+        Ok(transfers_remaining(self.dma_periph, self.channel) == 0)
+    }
+
+    /// Block until the transfer is complete, then stop the channel, and return the peripheral and
+    /// buffer. To avoid blocking, check `is_complete()` first; eg in the DMA Transfer Complete
+    /// interrupt. If a transfer error occurs, stops waiting, and returns them along with the error.
+    pub fn wait(self) -> Result<(P, B), (Error, P, B)> {
+        loop {
+            match self.is_complete() {
+                Ok(true) => return Ok(self.abort()),
+                Ok(false) => (),
+                Err(e) => {
+                    let (periph, buf) = self.abort();
+                    return Err((e, periph, buf));
+                }
+            }
+        }
+    }
+
+    /// Stop the transfer, whether or not it's complete, and return the peripheral and buffer.
+    /// Clears the Transfer Error flag, if set, so the channel can be used again.
+    pub fn abort(self) -> (P, B) {
+        stop(self.dma_periph, self.channel);
+        clear_interrupt(self.dma_periph, self.channel, DmaInterrupt::TransferError);
+
+        // "We use Ordering::Acquire to prevent all the subsequent memory operations from being
+        // moved before [stopping the DMA], which performs a volatile read."
+        atomic::compiler_fence(Ordering::Acquire);
+
+        (self.periph, self.buf)
+    }
+
+    /// The DMA channel used by this transfer. Eg for clearing its interrupt flags.
+    pub fn channel(&self) -> DmaChannel {
+        self.channel
+    }
+}
+
+#[cfg(feature = "h7")]
+#[derive(Copy, Clone, PartialEq)]
+#[repr(u8)]
+/// The memory a double-buffer mode stream is currently accessing. (SxCR register, CT field)
+pub enum MemTarget {
+    Mem0 = 0,
+    Mem1 = 1,
+}
+
+#[cfg(feature = "h7")]
+impl MemTarget {
+    /// The other buffer.
+    pub fn other(self) -> Self {
+        match self {
+            Self::Mem0 => Self::Mem1,
+            Self::Mem1 => Self::Mem0,
+        }
+    }
+}
+
+/// Read which memory a double-buffer mode stream is currently accessing. "CT: This bit is toggled
+/// by hardware at the end of each buffer transfer."
+#[cfg(feature = "h7")]
+pub fn current_target(periph: DmaPeriph, channel: DmaChannel) -> MemTarget {
+    let regs = match periph {
+        DmaPeriph::Dma1 => unsafe { &(*DMA1::ptr()) },
+        DmaPeriph::Dma2 => unsafe { &(*DMA2::ptr()) },
+    };
+
+    if regs.st[channel as usize].cr.read().ct().bit_is_set() {
+        MemTarget::Mem1
+    } else {
+        MemTarget::Mem0
+    }
+}
+
+/// In double-buffer mode, point the memory the stream isn't currently accessing to a new buffer,
+/// of the same length as the original. Run this in the Transfer Complete interrupt, soon after a
+/// swap. H743 RM, section 15.3.10: "the software can update the memory address that is not
+/// currently used: if CT = 1, DMA_SxM0AR can be written; if CT = 0, DMA_SxM1AR can be written. An
+/// attempt to write to the register currently in use [...] causes the stream to be disabled."
+///
+/// Returns the memory that was updated, or `None` if the DMA swapped buffers before the update, in
+/// which case the stream has been disabled by hardware, with the Transfer Error flag set.
+#[cfg(feature = "h7")]
+pub fn set_inactive_buffer(
+    periph: DmaPeriph,
+    channel: DmaChannel,
+    mem_addr: u32,
+) -> Option<MemTarget> {
+    let regs = match periph {
+        DmaPeriph::Dma1 => unsafe { &(*DMA1::ptr()) },
+        DmaPeriph::Dma2 => unsafe { &(*DMA2::ptr()) },
+    };
+    let st = &regs.st[channel as usize];
+
+    let inactive = current_target(periph, channel).other();
+
+    atomic::compiler_fence(Ordering::SeqCst);
+
+    match inactive {
+        MemTarget::Mem0 => st.m0ar.write(|w| unsafe { w.bits(mem_addr) }),
+        MemTarget::Mem1 => st.m1ar.write(|w| unsafe { w.bits(mem_addr) }),
+    }
+
+    // If the DMA swapped buffers between reading CT and writing the address, the write was to the
+    // memory in use, and hardware disabled the stream.
+    if st.cr.read().en().bit_is_clear() {
+        None
+    } else {
+        Some(inactive)
+    }
+}
+
 fn clear_interrupt_internal<D>(regs: &mut D, channel: DmaChannel, interrupt: DmaInterrupt)
 where
     D: Deref<Target = dma1::RegisterBlock>,
@@ -1898,8 +2451,10 @@ pub fn clear_interrupt(periph: DmaPeriph, channel: DmaChannel, interrupt: DmaInt
     feature = "wb",
     feature = "wl",
 ))]
-/// Configure a specific DMA channel to work with a specific peripheral.
-pub fn mux(periph: DmaPeriph, channel: DmaChannel, input: DmaInput) {
+/// Configure a specific DMA channel to work with a specific peripheral. Returns
+/// `Error::UnsupportedChannel` if the channel doesn't exist on `periph`; eg DMA2 channels 7 and 8
+/// on G431 and G441.
+pub fn mux(periph: DmaPeriph, channel: DmaChannel, input: DmaInput) -> Result<(), Error> {
     // Note: This is similar in API and purpose to `channel_select` above,
     // for different families. We're keeping it as a separate function instead
     // of feature-gating within the same function so the name can be recognizable
@@ -1927,8 +2482,6 @@ pub fn mux(periph: DmaPeriph, channel: DmaChannel, input: DmaInput) {
     // DMAMUX1 channels 8 to 15 are connected to DMA2 channels 0 to 7
     // (Note: The H7 and G4 cat 3/4 mappings are the same, except for H7's use of 0-7, and G4's use of 1-8.)
 
-    // todo: With this in mind, some of the mappings below are not correct on some G4 variants.
-
     unsafe {
         let mux = unsafe { &(*DMAMUX::ptr()) };
 
@@ -1959,7 +2512,19 @@ pub fn mux(periph: DmaPeriph, channel: DmaChannel, input: DmaInput) {
                 feature = "wb"
             )))]
             DmaPeriph::Dma2 => {
-                #[cfg(not(feature = "h7"))]
+                // G4 category 2 devices: DMA2 channels 1 to 6 are connected to DMAMUX channels 6 to 11.
+                #[cfg(any(feature = "g431", feature = "g441"))]
+                match channel {
+                    DmaChannel::C1 => mux.c6cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    DmaChannel::C2 => mux.c7cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    DmaChannel::C3 => mux.c8cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    DmaChannel::C4 => mux.c9cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    DmaChannel::C5 => mux.c10cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    DmaChannel::C6 => mux.c11cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
+                    _ => return Err(Error::UnsupportedChannel),
+                }
+
+                #[cfg(not(any(feature = "h7", feature = "g431", feature = "g441")))]
                 match channel {
                     DmaChannel::C1 => mux.c8cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
                     DmaChannel::C2 => mux.c9cr.modify(|_, w| w.dmareq_id().bits(input as u8)),
@@ -1981,6 +2546,8 @@ pub fn mux(periph: DmaPeriph, channel: DmaChannel, input: DmaInput) {
             }
         }
     }
+
+    Ok(())
 }
 
 #[cfg(feature = "h7")]
@@ -2011,15 +2578,23 @@ pub fn enable_mux1() {
 }
 
 #[cfg(feature = "l4")] // Only required on L4
-/// Select which peripheral on a given channel we're using.
-/// See L44 RM, Table 41.
-pub(crate) fn channel_select<D>(regs: &mut D, input: DmaInput)
-where
-    D: Deref<Target = dma1::RegisterBlock>,
-{
+/// Select which peripheral on a given channel we're using, on a given DMA peripheral.
+/// See L44 RM, Tables 41 and 42. No effect if the input isn't connected to DMA2.
+pub(crate) fn channel_select(periph: DmaPeriph, input: DmaInput) {
+    let (regs, val) = match periph {
+        DmaPeriph::Dma1 => (unsafe { &(*DMA1::ptr()) }, input.dma1_channel_select()),
+        DmaPeriph::Dma2 => match input.dma2_channel_select() {
+            Some(val) => (unsafe { &(*DMA2::ptr()) }, val),
+            None => return,
+        },
+    };
+    let channel = match input.channel(periph) {
+        Some(c) => c,
+        None => return,
+    };
+
     // todo: Allow selecting channels in pairs to save a write.
-    let val = input.dma1_channel_select();
-    regs.cselr.modify(|_, w| match input.dma1_channel() {
+    regs.cselr.modify(|_, w| match channel {
         DmaChannel::C1 => w.c1s().bits(val),
         DmaChannel::C2 => w.c2s().bits(val),
         DmaChannel::C3 => w.c3s().bits(val),
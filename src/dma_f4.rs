@@ -0,0 +1,103 @@
+//! Minimal support for the F4's DMA1 streams, as used by the `i2c` and `i2s` modules. The `dma`
+//! module doesn't support F4's DMA peripheral. See RM0090, section 10: "DMA controller".
+
+use crate::pac::DMA1;
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+/// A DMA1 stream. See RM0090, Table 42: "DMA1 request mapping" for the streams and channels
+/// connected to each peripheral.
+pub enum DmaStream {
+    S0 = 0,
+    S1 = 1,
+    S2 = 2,
+    S3 = 3,
+    S4 = 4,
+    S5 = 5,
+    S6 = 6,
+    S7 = 7,
+}
+
+#[derive(Clone, Copy)]
+/// The size of each data item transferred; used for both the peripheral and memory sides.
+/// Sets DMA_SxCR register, PSIZE and MSIZE fields.
+pub enum DataSize {
+    S8,
+    S16,
+}
+
+/// Clear all interrupt flags for a DMA1 stream.
+pub fn clear_flags(stream: DmaStream) {
+    let regs = unsafe { &(*DMA1::ptr()) };
+    // Each stream's flags: FEIF (bit 0), DMEIF (2), TEIF (3), HTIF (4), TCIF (5). Streams 0-3 are
+    // in LIFCR, and 4-7 in HIFCR, at offsets of 0, 6, 16, and 22.
+    let shift = [0, 6, 16, 22][stream as usize % 4];
+    let flags = 0b11_1101 << shift;
+
+    if (stream as u8) < 4 {
+        regs.lifcr.write(|w| unsafe { w.bits(flags) });
+    } else {
+        regs.hifcr.write(|w| unsafe { w.bits(flags) });
+    }
+}
+
+/// Configure and enable a DMA1 stream. See RM0090, section 10.3.18: "Stream configuration
+/// procedure". The transfer complete interrupt is enabled; in circular mode, the half transfer
+/// interrupt is too, eg to refill each half of the buffer in turn.
+pub unsafe fn cfg_stream(
+    periph_addr: u32,
+    mem_addr: u32,
+    len: u16,
+    mem_to_periph: bool,
+    stream: DmaStream,
+    channel: u8,
+    data_size: DataSize,
+    circular: bool,
+) {
+    let regs = unsafe { &(*DMA1::ptr()) };
+    let st = &regs.st[stream as usize];
+
+    // "If the stream is enabled, disable it by resetting the EN bit in the DMA_SxCR register,
+    // then read this bit in order to confirm that there is no ongoing stream operation."
+    st.cr.modify(|_, w| w.en().clear_bit());
+    while st.cr.read().en().bit_is_set() {}
+
+    clear_flags(stream);
+
+    st.par.write(|w| w.pa().bits(periph_addr));
+    st.m0ar.write(|w| w.m0a().bits(mem_addr));
+    st.ndtr.write(|w| w.ndt().bits(len));
+
+    st.cr.write(|w| {
+        w.chsel().bits(channel);
+        if mem_to_periph {
+            w.dir().memory_to_peripheral();
+        } else {
+            w.dir().peripheral_to_memory();
+        }
+        w.minc().set_bit();
+        match data_size {
+            DataSize::S8 => {
+                w.psize().bits8();
+                w.msize().bits8();
+            }
+            DataSize::S16 => {
+                w.psize().bits16();
+                w.msize().bits16();
+            }
+        }
+        w.circ().bit(circular);
+        w.htie().bit(circular);
+        w.tcie().set_bit();
+        w.en().set_bit()
+    });
+}
+
+/// Disable a DMA1 stream, and clear its interrupt flags.
+pub fn stop(stream: DmaStream) {
+    let regs = unsafe { &(*DMA1::ptr()) };
+    regs.st[stream as usize]
+        .cr
+        .modify(|_, w| w.en().clear_bit());
+    clear_flags(stream);
+}
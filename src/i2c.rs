@@ -11,6 +11,7 @@ use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
 
 use crate::{
     clocks::Clocks,
+    gpio::{OutputType, Pin, PinMode},
     pac::{self, RCC},
     util::RccPeriph,
     MAX_ITERS,
@@ -47,6 +48,12 @@ macro_rules! busy_wait {
             } else if isr.arlo().bit_is_set() {
                 $regs.icr.write(|w| w.arlocf().set_bit());
                 return Err(Error::Arbitration);
+            } else if isr.pecerr().bit_is_set() {
+                $regs.icr.write(|w| w.peccf().set_bit());
+                return Err(Error::Pec);
+            } else if isr.timeout().bit_is_set() {
+                $regs.icr.write(|w| w.timoutcf().set_bit());
+                return Err(Error::Timeout);
             } else if isr.nackf().bit_is_set() {
                 $regs.icr.write(|w| w.stopcf().set_bit().nackcf().set_bit());
 
@@ -69,7 +76,7 @@ macro_rules! busy_wait {
 
 /// I2C error
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error {
     /// Bus error
     Bus,
@@ -77,10 +84,19 @@ pub enum Error {
     Arbitration,
     /// NACK
     Nack,
-    // Overrun, // slave mode only
-    // Pec, // SMBUS mode only
-    // Timeout, // SMBUS mode only
-    // Alert, // SMBUS mode only
+    /// Overrun or underrun. Slave mode, with clock stretching disabled only.
+    Overrun,
+    /// The bus stayed busy when starting a transfer; eg a device is holding SDA low. See
+    /// `I2c::recover_bus()`.
+    BusBusy,
+    /// The received PEC doesn't match the calculated one. SMBus mode only.
+    Pec,
+    /// SCL was held low for longer than the SMBus timeout, or the cumulative clock low extend time
+    /// was exceeded. SMBus mode only.
+    Timeout,
+    /// A value passed is out of range; eg an SMBus block longer than 255 bytes, an SMBus timeout too
+    /// long for the I2C clock, or more than `MAX_OPS` operations in a transaction.
+    OutOfRange,
     Hardware,
 }
 
@@ -132,24 +148,370 @@ pub enum NoiseFilter {
     Disabled,
 }
 
+#[derive(Clone, Copy)]
+#[repr(u8)]
+/// Own address 2 mask: The least significant bits of OA2 that are ignored when comparing
+/// received addresses. Sets the OAR2 register, OA2MSK field. "As soon as OA2MSK is not equal to 0,
+/// the reserved I2C addresses (0b0000xxx and 0b1111xxx) are not acknowledged even if the comparison
+/// matches."
+pub enum Oa2Mask {
+    /// No mask; all 7 bits are compared.
+    NoMask = 0,
+    /// Address bit 0 isn't compared.
+    Mask1 = 1,
+    /// Address bits 1:0 aren't compared.
+    Mask2 = 2,
+    /// Address bits 2:0 aren't compared.
+    Mask3 = 3,
+    /// Address bits 3:0 aren't compared.
+    Mask4 = 4,
+    /// Address bits 4:0 aren't compared.
+    Mask5 = 5,
+    /// Address bits 5:0 aren't compared.
+    Mask6 = 6,
+    /// No bits are compared; all non-reserved 7-bit addresses are acknowledged.
+    Mask7 = 7,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+/// The direction of a transfer, from the master's perspective. Read from the ISR register, DIR field.
+pub enum TransferDirection {
+    /// The master writes; the slave receives.
+    Write,
+    /// The master reads; the slave transmits.
+    Read,
+}
+
+#[derive(Clone, Copy)]
+/// The type of I2C interrupt to configure. Enable in CR1. Reference the ISR register.
+pub enum I2cInterrupt {
+    /// Slave address matched. (ADDRIE)
+    Address,
+    /// Stop condition detected. (STOPIE)
+    Stop,
+    /// NACK received. (NACKIE)
+    Nack,
+    /// Receive buffer not empty. (RXIE)
+    Rx,
+    /// Transmit interrupt status. (TXIE)
+    Tx,
+    /// Transfer complete, and transfer complete reload. (TCIE)
+    TransferComplete,
+    /// Bus error, arbitration loss, overrun/underrun, PEC error, timeout, and alert. (ERRIE)
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+/// Slave mode events, as returned by `I2c::slave_poll()`.
+pub enum SlaveEvent {
+    /// The master addressed this device. `address` is the matched address code (ADDCODE); the 7-bit
+    /// address, 0 for a general call, or the 10-bit header (0b11110xx) for 10-bit addresses. When
+    /// clock stretching is enabled, SCL is held low until this event is reported.
+    AddressMatch {
+        address: u8,
+        direction: TransferDirection,
+    },
+    /// A byte was received from the master.
+    Received(u8),
+    /// The master is reading, and the next byte must be written using `slave_write_byte()`.
+    TransmitRequest,
+    /// The master NACKed the last byte sent; it doesn't want more data.
+    Nack,
+    /// A stop condition was detected; the transfer is over.
+    Stop,
+}
+
+/// SMBus configuration. Used when `I2cConfig::smbus` is true. See L44 RM, section 37.4.10:
+/// "SMBus specific features".
+#[derive(Clone)]
+pub struct SmbusConfig {
+    /// Send and check a Packet Error Checking (PEC) byte at the end of each transfer. The PEC is
+    /// calculated and checked by hardware. Defaults to false.
+    pub pec: bool,
+    /// Clock low timeout (tTIMEOUT), in µs. The SMBus specification requires 25ms to 35ms. A timeout
+    /// error is reported if SCL is held low for longer than this. `None` disables it. Defaults to 25ms.
+    pub timeout: Option<u32>,
+    /// Cumulative clock low extend time, in µs; tLOW:MEXT in master mode (10ms max), and tLOW:SEXT in
+    /// slave mode (25ms max). `None` disables it. Defaults to `None`.
+    pub clock_extend_timeout: Option<u32>,
+    /// Host mode: Enable detection of the SMBA (SMBus alert) pin. Check alerts with
+    /// `smbus_alert_pending()`. Defaults to false.
+    pub alert: bool,
+    /// Host mode: Acknowledge the SMBus host address (0b000_1000), to receive Host Notify messages
+    /// from devices. These are received as slave events. (See `slave_poll()`) Defaults to false.
+    pub host_notify: bool,
+}
+
+impl Default for SmbusConfig {
+    fn default() -> Self {
+        Self {
+            pec: false,
+            timeout: Some(25_000),
+            clock_extend_timeout: None,
+            alert: false,
+            host_notify: false,
+        }
+    }
+}
+
+/// The SMBus Alert Response Address. The host reads from this to find which device asserted SMBA.
+const SMBUS_ALERT_RESPONSE_ADDR: u8 = 0b000_1100;
+
+/// Calculate the SMBus Packet Error Code (PEC) of a sequence of bytes: CRC-8, with polynomial
+/// x^8 + x^2 + x + 1 (0x07), and an initial value of 0. The PEC covers all bytes of a message,
+/// including address bytes with their R/W bit. Hardware calculates this when PEC is enabled;
+/// this is useful for checking data, or for constructing messages manually.
+pub fn smbus_pec(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+
+    for byte in data {
+        crc ^= *byte;
+        for _ in 0..8 {
+            if crc & 0x80 != 0 {
+                crc = (crc << 1) ^ 0x07;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+/// Values for the TIMINGR register fields, as calculated by `calc_timings()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct I2cTimings {
+    /// Timing prescaler. tPRESC = (PRESC+1) x tI2CCLK
+    pub presc: u8,
+    /// Data setup time. tSCLDEL = (SCLDEL+1) x tPRESC
+    pub scldel: u8,
+    /// Data hold time. tSDADEL= SDADEL x tPRESC
+    pub sdadel: u8,
+    /// SCL high period (master mode). tSCLH = (SCLH+1) x tPRESC
+    pub sclh: u8,
+    /// SCL low period (master mode). tSCLL = (SCLL+1) x tPRESC
+    pub scll: u8,
+}
+
+/// I2C bus timing requirements for a speed mode, in ns. From the I2C specification (UM10204),
+/// Table 10: "Characteristics of the SDA and SCL bus lines".
+struct BusSpec {
+    /// Bus frequency, in Hz.
+    freq: u32,
+    /// Minimum data hold time. (tHD;DAT)
+    hddat_min: u32,
+    /// Maximum data valid time. (tVD;DAT)
+    vddat_max: u32,
+    /// Minimum data setup time. (tSU;DAT)
+    sudat_min: u32,
+    /// Minimum SCL low period. (tLOW)
+    lscl_min: u32,
+    /// Minimum SCL high period. (tHIGH)
+    hscl_min: u32,
+}
+
+impl I2cSpeed {
+    fn bus_spec(&self) -> BusSpec {
+        match self {
+            Self::Standard10K | Self::Standard100K => BusSpec {
+                freq: if let Self::Standard10K = self {
+                    10_000
+                } else {
+                    100_000
+                },
+                hddat_min: 0,
+                vddat_max: 3_450,
+                sudat_min: 250,
+                lscl_min: 4_700,
+                hscl_min: 4_000,
+            },
+            Self::Fast400K => BusSpec {
+                freq: 400_000,
+                hddat_min: 0,
+                vddat_max: 900,
+                sudat_min: 100,
+                lscl_min: 1_300,
+                hscl_min: 600,
+            },
+            Self::FastPlus1M => BusSpec {
+                freq: 1_000_000,
+                hddat_min: 0,
+                vddat_max: 450,
+                sudat_min: 50,
+                lscl_min: 500,
+                hscl_min: 260,
+            },
+        }
+    }
+}
+
+/// Minimum and maximum delay added by the analog noise filter, in ns. (tAF(min) and tAF(max), from
+/// the datasheet's I2C analog filter characteristics)
+const AF_DELAY_MIN: u32 = 50;
+const AF_DELAY_MAX: u32 = 260;
+
+/// Calculate TIMINGR register values from the I2C kernel clock speed (`i2c_clock`, in Hz), the bus
+/// speed, rise and fall times (in ns), and noise filter settings. Returns `None` if no values meet the
+/// bus specification. See L44 RM, section 37.4.5: "I2C timings", and section 37.4.9: "I2C master mode".
+///
+/// The data hold time (SDADEL) and setup time (SCLDEL) are chosen to satisfy:
+/// tSDADEL >= {tf + tHD;DAT(min) - tAF(min) - [(DNF + 3) x tI2CCLK]}
+/// tSDADEL <= {tVD;DAT(max) - tr - tAF(max) - [(DNF + 4) x tI2CCLK]}
+/// tSCLDEL >= tr + tSU;DAT(min)
+///
+/// The SCL low and high periods are then chosen, for each prescaler value that allows this, to get the
+/// SCL frequency closest to the target, without exceeding it. For each SCL low period, the shortest high
+/// period that meets this and tHIGH(min) is calculated directly:
+/// tSCL = tSYNC1 + tSYNC2 + {[(SCLL+1) + (SCLH+1)] x (PRESC+1) x tI2CCLK}, where tSYNC includes the
+/// filter delays, 2 x tI2CCLK, and the rise or fall time.
+pub fn calc_timings(
+    i2c_clock: u32,
+    speed: I2cSpeed,
+    rise_time: u32,
+    fall_time: u32,
+    noise_filter: NoiseFilter,
+) -> Option<I2cTimings> {
+    let spec = speed.bus_spec();
+
+    if i2c_clock == 0 {
+        return None;
+    }
+
+    // Work in ps, so rounding the I2C clock period doesn't cause significant error at high clock
+    // speeds. Use signed values, since intermediate delays can be negative.
+    let ps = |ns: u32| ns as i64 * 1_000;
+
+    let t_i2cclk = ((1_000_000_000_000 + i2c_clock as u64 / 2) / i2c_clock as u64) as i64;
+    let t_bus = (1_000_000_000_000 / spec.freq as u64) as i64;
+    let rise_time = ps(rise_time);
+    let fall_time = ps(fall_time);
+
+    let (af_delay_min, af_delay_max) = match noise_filter {
+        NoiseFilter::Analog => (ps(AF_DELAY_MIN), ps(AF_DELAY_MAX)),
+        _ => (0, 0),
+    };
+    let dnf = match noise_filter {
+        NoiseFilter::Digital(dnf) => dnf as i64,
+        _ => 0,
+    };
+    let dnf_delay = dnf * t_i2cclk;
+
+    let sdadel_min = (ps(spec.hddat_min) + fall_time - af_delay_min - (dnf + 3) * t_i2cclk).max(0);
+    // If this is negative, the rise time and filter delays already use up the data valid time, so no
+    // SDADEL meets tVD;DAT(max); eg in Fast-mode Plus at low I2C clock speeds. Allow an SDADEL of 0,
+    // the shortest delay, as the RM's example timings do.
+    let sdadel_max = (ps(spec.vddat_max) - rise_time - af_delay_max - (dnf + 4) * t_i2cclk).max(0);
+    let scldel_min = rise_time + ps(spec.sudat_min);
+
+    // The SCL period must be within the target, and 80% of it.
+    let t_scl_min = t_bus;
+    let t_scl_max = t_bus * 10 / 8;
+
+    // The delay between SCL going low or high, and the I2C peripheral detecting it.
+    let t_sync = af_delay_min + dnf_delay + 2 * t_i2cclk;
+
+    let mut result = None;
+    // Error from the target period, and difference between the low and high periods. We prefer
+    // the closest period, then the most even duty cycle.
+    let mut best = (i64::MAX, i64::MAX);
+
+    for presc in 0..16_i64 {
+        let t_presc = (presc + 1) * t_i2cclk;
+
+        // Find the smallest data setup and hold times that meet the spec at this prescaler.
+        let scldel = match (0..16_i64).find(|l| (l + 1) * t_presc >= scldel_min) {
+            Some(l) => l,
+            None => continue,
+        };
+        let sdadel = match (0..16_i64).find(|a| {
+            let t_sdadel = a * t_presc;
+            t_sdadel >= sdadel_min && t_sdadel <= sdadel_max
+        }) {
+            Some(a) => a,
+            None => continue,
+        };
+
+        for scll in 0..256_i64 {
+            let t_scl_l = (scll + 1) * t_presc + t_sync;
+
+            // "tSCLL ... must be greater than 4 x tI2CCLK", after filter delays.
+            if t_scl_l < ps(spec.lscl_min) || t_i2cclk >= (t_scl_l - af_delay_min - dnf_delay) / 4 {
+                continue;
+            }
+
+            // The high period must make up the rest of the minimum SCL period, meet tHIGH(min),
+            // and be longer than tI2CCLK. Longer ones only move the period further from the target.
+            let t_scl_h_min = (t_scl_min - t_scl_l - rise_time - fall_time)
+                .max(ps(spec.hscl_min))
+                .max(t_i2cclk + 1);
+            // The smallest SCLH where (SCLH + 1) x tPRESC + tSYNC >= tSCLH(min).
+            let sclh = ((t_scl_h_min - t_sync + t_presc - 1).div_euclid(t_presc) - 1).max(0);
+            if sclh > 255 {
+                continue;
+            }
+
+            let t_scl_h = (sclh + 1) * t_presc + t_sync;
+            let t_scl = t_scl_l + t_scl_h + rise_time + fall_time;
+            if t_scl > t_scl_max {
+                continue;
+            }
+
+            let score = ((t_scl - t_bus).abs(), (t_scl_l - t_scl_h).abs());
+            if score < best {
+                best = score;
+                result = Some(I2cTimings {
+                    presc: presc as u8,
+                    scldel: scldel as u8,
+                    sdadel: sdadel as u8,
+                    sclh: sclh as u8,
+                    scll: scll as u8,
+                });
+            }
+        }
+    }
+
+    result
+}
+
 /// Configuration data for the I2C peripheral.
 #[derive(Clone)]
 pub struct I2cConfig {
     /// Select master or slave mode. Defaults to Master.
     pub mode: I2cMode,
-    /// Select between one of 4 preset speeds. If you'd like to use custom
+    /// Select between one of 4 preset speeds. Timings are calculated from this, the I2C clock speed,
+    /// the rise and fall times, and the noise filter settings. If you'd like to use custom
     /// speed settings, use the PAC directly, with I2C disabled, after the
     /// peripheral clocks are enabled by `new()`. Defaults to Standard mode, 100kHz.
     pub speed: I2cSpeed,
+    /// SCL and SDA rise time, in ns. This depends on the bus capacitance and pull-up resistor values;
+    /// measure it, or see the I2C specification for its calculation. Must not exceed the maximum for
+    /// the speed used: 1000ns in Standard mode, 300ns in Fast mode, and 120ns in Fast mode plus.
+    /// Defaults to 100ns.
+    pub rise_time: u32,
+    /// SCL and SDA fall time, in ns. Must not exceed 300ns in Standard and Fast modes, and 120ns in
+    /// Fast mode plus. Defaults to 10ns.
+    pub fall_time: u32,
     /// Allows setting 7 or 10-bit addresses. Defaults to 7.
     pub address_bits: AddressBits,
     /// Select the analog noise filter, a digital filter, or no filter. Deafults to analog.
     pub noise_filter: NoiseFilter,
     /// Support for SMBUS, including hardware PEC, and alert pin. Defaults to false.
     pub smbus: bool,
+    /// SMBus settings, used if `smbus` is true.
+    pub smbus_cfg: SmbusConfig,
     /// Optionally disable clock stretching. Defaults to false (stretching allowed).
     /// Only relevant in slave mode.
     pub nostretch: bool,
+    /// Own address 1, used in slave mode. Uses `address_bits` to select a 7 or 10-bit address.
+    /// `None` disables it. Defaults to `None`.
+    pub own_address_1: Option<u16>,
+    /// Own address 2, used in slave mode. Always a 7-bit address. `None` disables it. Defaults to `None`.
+    pub own_address_2: Option<u8>,
+    /// Bits of own address 2 that aren't compared; allows responding to a range of addresses.
+    /// Defaults to no mask.
+    pub own_address_2_mask: Oa2Mask,
+    /// Acknowledge the general call address (0x00) in slave mode. Defaults to false.
+    pub general_call: bool,
 }
 
 impl Default for I2cConfig {
@@ -157,10 +519,17 @@ impl Default for I2cConfig {
         Self {
             mode: I2cMode::Master,
             speed: I2cSpeed::Standard100K,
+            rise_time: 100,
+            fall_time: 10,
             address_bits: AddressBits::B7,
             noise_filter: NoiseFilter::Analog,
             smbus: false,
+            smbus_cfg: Default::default(),
             nostretch: false,
+            own_address_1: None,
+            own_address_2: None,
+            own_address_2_mask: Oa2Mask::NoMask,
+            general_call: false,
         }
     }
 }
@@ -169,6 +538,9 @@ impl Default for I2cConfig {
 pub struct I2c<R> {
     pub regs: R,
     pub cfg: I2cConfig,
+    /// Bytes remaining in the current transfer after the loaded NBYTES chunk, for transfers longer
+    /// than 255 bytes.
+    reload_remaining: usize,
 }
 
 impl<R> I2c<R>
@@ -177,6 +549,14 @@ where
 {
     /// Initialize a I2C peripheral, including configuration register writes, and enabling and resetting
     /// its RCC peripheral clock. `freq` is in Hz.
+    ///
+    /// Panics if no TIMINGR values meet the bus specification at the APB1 clock speed, with the configured
+    /// speed, rise and fall times, and noise filter; eg Fast-mode Plus with a slow APB1 clock. Use
+    /// `calc_timings()` to check a configuration in advance.
+    ///
+    /// If `cfg.smbus` is set, also panics if an SMBus timeout in `cfg.smbus_cfg` doesn't fit in
+    /// TIMEOUTR's 12-bit fields at the APB1 clock speed; eg a 35ms timeout with a fast APB1 clock.
+    /// Use `enable_smbus()` after `new()` to handle this as an error instead.
     pub fn new(regs: R, cfg: I2cConfig, clocks: &Clocks) -> Self {
         free(|_| {
             let rcc = unsafe { &(*RCC::ptr()) };
@@ -186,9 +566,6 @@ where
         // Make sure the I2C unit is disabled so we can configure it
         regs.cr1.modify(|_, w| w.pe().clear_bit());
 
-        // todo: Slave currently nonfunctional!
-        // todo: Check out the RM recipes for slave transmitter and receiver.
-
         // RM: I2C timings:
         // The timings must be configured in order to guarantee a correct data hold and setup time,
         // used in master and slave modes. This is done by programming the PRESC[3:0],
@@ -196,136 +573,21 @@ where
         // ... Additionally, in master mode, the SCL clock high and low levels must be configured by
         // programming the PRESC[3:0], SCLH[7:0] and SCLL[7:0] bits in the I2C_TIMINGR register
 
-        // For these speed and frequency variables, we use the RM's conventions.
-        let t_i2cclk = clocks.apb1();
-
-        // assert!(t_i2cclk < (t_low - f_f) / 4);
-        // assert!(t_i2cclk < t_high);
-
-        // Set the prescaler using RM tables as a guide;
-        // L552 RM, Tables 324 - 326: Examples of timings settings.
-        // Note that the table only includes I2C clock multiples of 4Mhz (well, multiples of 8Mhz).
-        // In this case, we'll use the integer floor rounding to handle in-between
-        // values.
-
-        // We use this constant in several calculations.
-        let presc_const = match cfg.speed {
-            I2cSpeed::Standard10K => 4_000_000,
-            I2cSpeed::Standard100K => 4_000_000,
-            I2cSpeed::Fast400K => 8_000_000,
-            // Note: The 16Mhz example uses F+ / 16. The other 2 examples
-            // use 8e6.
-            I2cSpeed::FastPlus1M => 8_000_000,
-        };
-
-        // This is (offset by 1) which we set as the prescaler.
-        let mut presc_val = t_i2cclk / presc_const;
-
-        // The tables don't show faster I2C input clocks than 48Mhz, but it often will be.
-        // For example, an 80Mhz APB clock will peg prescaler at its maximum value.
-        // Let's just set it to this max. (Maybe we should use fast mode etc if this is so?)
-        if presc_val > 16 {
-            presc_val = 16;
-        }
-
-        // Hit the target freq by setting up t_scll (Period of SCL low)
-        // to be half the whole period. These constants
-        // are from the tables.
-        let freq = match cfg.speed {
-            I2cSpeed::Standard10K => 10_000,
-            I2cSpeed::Standard100K => 100_000,
-            I2cSpeed::Fast400K => 400_000,
-            I2cSpeed::FastPlus1M => 1_000_000,
-        };
-
-        // Set SCLL (SCL low time) to be half the duty period
-        // associated with the target frequency.
-        // todo: QC this is right if you peg presc_val at 16.
-        let scll_val;
-        if presc_val == 16 {
-            // IF we peg presc, we need to modify out calculation of scll (??)
-            scll_val = (t_i2cclk / presc_val) / (2 * freq);
-        } else {
-            scll_val = presc_const / (2 * freq);
-        }
-
-        // SCLH is smaller than SCLH. For standard mode it's close, although
-        // in the example tables, 20% different for 100Khz, and 2% different for
-        // 10K. THis may be due to delays
-        // involved. The ratio is different for Fast-mode and Fast-mode+.
-        // todo: Come back to this. How should we set this?
-        let sclh_val = match cfg.speed {
-            I2cSpeed::Standard10K => scll_val - 4,
-            I2cSpeed::Standard100K => scll_val - 4,
-            I2cSpeed::Fast400K => scll_val * 4 / 10,
-            I2cSpeed::FastPlus1M => scll_val / 2,
-        };
-
-        // Timing prescaler. This field is used to prescale I2CCLK in order to generate the clock period tPRESC used for
-        // data setup and hold counters (refer to I2C timings on page 1495) and for SCL high and low
-        // level counters (refer to I2C master initialization on page 1510).
-        // Sets TIMINGR reg, PRESC field.
-
-        let presc = presc_val - 1;
-
-        // SCL low period (master mode)
-        // This field is used to generate the SCL low period in master mode.
-        // tSCLL = (SCLL+1) x tPRESC
-        // Note: SCLL is also used to generate tBUF and tSU:STA timings.
-        // Sets TIMINGR reg, SCLL field.
-        let scll = scll_val - 1;
-
-        // SCL high period (master mode)
-        // This field is used to generate the SCL high period in master mode.
-        // tSCLH = (SCLH+1) x tPRESC
-        // Note: SCLH is also used to generate tSU:STO and tHD:STA timing
-        // Set the clock prescaler value. Sets TIMINGR reg, SCLH field.
-        let sclh = sclh_val - 1;
-
-        // todo: Can't find the sdadel and scldel pattern
-        // Data hold time
-        // This field is used to generate the delay tSDADEL between SCL falling edge and SDA edge. In
-        // master mode and in slave mode with NOSTRETCH = 0, the SCL line is stretched low during
-        // tSDADEL.
-        // tSDADEL= SDADEL x tPRESC
-        // Note: SDADEL is used to generate tHD:DAT timing
-        // Sets TIMINGR reg, SDADEL field.
-        let sdadel = match cfg.speed {
-            I2cSpeed::Standard10K => 0x2,
-            I2cSpeed::Standard100K => 0x2,
-            I2cSpeed::Fast400K => 0x3,
-            I2cSpeed::FastPlus1M => 0x0,
-        };
-
-        // Data setup time
-        // This field is used to generate a delay tSCLDEL between SDA edge and SCL rising edge. In
-        // master mode and in slave mode with NOSTRETCH = 0, the SCL line is stretched low during
-        // tSCLDEL.
-        // tSCLDEL = (SCLDEL+1) x tPRESC
-        // Note: tSCLDEL is used to generate tSU:DAT timing
-        // Sets TIMINGR reg, SCLDEL field.
-        let scldel = match cfg.speed {
-            I2cSpeed::Standard10K => 0x4,
-            I2cSpeed::Standard100K => 0x4,
-            I2cSpeed::Fast400K => 0x3,
-            I2cSpeed::FastPlus1M => 0x1,
-        };
-
-        // The fields for PRESC, SCLDEL, and SDADEL are 4-bits; don't overflow.
-        // The other TIMINGR fields we set are 8-bits, so won't overflow with u8.
-        assert!(presc <= 15);
-        assert!(scldel <= 15);
-        assert!(sdadel <= 15);
-
-        assert!(scll <= 255);
-        assert!(sclh <= 255);
+        let timings = calc_timings(
+            clocks.apb1(),
+            cfg.speed,
+            cfg.rise_time,
+            cfg.fall_time,
+            cfg.noise_filter,
+        )
+        .expect("Unable to find I2C timings for this clock speed, bus speed, and rise/fall times");
 
         regs.timingr.write(|w| unsafe {
-            w.presc().bits(presc as u8);
-            w.scldel().bits(scldel as u8);
-            w.sdadel().bits(sdadel as u8);
-            w.sclh().bits(sclh as u8);
-            w.scll().bits(scll as u8)
+            w.presc().bits(timings.presc);
+            w.scldel().bits(timings.scldel);
+            w.sdadel().bits(timings.sdadel);
+            w.sclh().bits(timings.sclh);
+            w.scll().bits(timings.scll)
         });
 
         // Before enabling the I2C peripheral by setting the PE bit in I2C_CR1 register, the user must
@@ -352,24 +614,65 @@ where
         });
 
         if let I2cMode::Slave = cfg.mode {
-            regs.cr1.modify(|_, w| w.nostretch().bit(cfg.nostretch));
+            // L44 RM, section 37.4.8: "I2C slave mode": "To work in slave mode, the user must enable at
+            // least one slave address. Two registers I2C_OAR1 and I2C_OAR2 are available in order to
+            // program the slave own addresses OA1 and OA2."
+            // "OA1 can be configured either in 7-bit mode (by default) or in 10-bit addressing mode by
+            // setting the OA1MODE bit in the I2C_OAR1 register. OA1 is enabled by setting the OA1EN
+            // bit in the I2C_OAR1 register."
+            regs.oar1.write(|w| w.oa1en().clear_bit());
+            if let Some(addr) = cfg.own_address_1 {
+                regs.oar1.write(|w| {
+                    match cfg.address_bits {
+                        // "OA1[7:1]: Interface address: 7-bit addressing mode: 7-bit address"
+                        AddressBits::B7 => unsafe { w.oa1().bits(addr << 1) },
+                        AddressBits::B10 => unsafe { w.oa1().bits(addr) },
+                    };
+                    w.oa1mode().bit(cfg.address_bits as u8 != 0)
+                });
+                regs.oar1.modify(|_, w| w.oa1en().set_bit());
+            }
+
+            // "If additional slave addresses are required, the 2nd slave address OA2 can be
+            // configured. Up to 7 OA2 LSB can be masked by configuring the OA2MSK[2:0] bits in the
+            // I2C_OAR2 register."
+            regs.oar2.write(|w| w.oa2en().clear_bit());
+            if let Some(addr) = cfg.own_address_2 {
+                regs.oar2.write(|w| unsafe {
+                    w.oa2().bits(addr);
+                    w.oa2msk().bits(cfg.own_address_2_mask as u8)
+                });
+                regs.oar2.modify(|_, w| w.oa2en().set_bit());
+            }
+
+            // "The General Call address is enabled by setting the GCEN bit in the I2C_CR1 register."
+            regs.cr1.modify(|_, w| {
+                w.gcen().bit(cfg.general_call);
+                w.nostretch().bit(cfg.nostretch)
+            });
         }
 
-        let mut result = Self { regs, cfg };
+        let mut result = Self {
+            regs,
+            cfg,
+            reload_remaining: 0,
+        };
 
         if result.cfg.smbus {
-            result.enable_smbus();
+            result
+                .enable_smbus(clocks)
+                .expect("SMBus timeouts are too long for the I2C clock speed");
         }
 
         // Enable the peripheral
-        result.regs.cr1.write(|w| w.pe().set_bit());
+        result.regs.cr1.modify(|_, w| w.pe().set_bit());
 
         result
     }
 
-    /// Enable SMBus support. See L44 RM, section 37.4.11: SMBus initialization
-    pub fn enable_smbus(&mut self) -> Result<(), Error> {
-        // todo: Roll this into an init setting or I2cConfig struct etc.
+    /// Enable SMBus support, using the settings in `cfg.smbus_cfg`. This is run by `new()` if
+    /// `cfg.smbus` is true. See L44 RM, section 37.4.11: SMBus initialization
+    pub fn enable_smbus(&mut self, clocks: &Clocks) -> Result<(), Error> {
         // PEC calculation is enabled by setting the PECEN bit in the I2C_CR1 register. Then the PEC
         // transfer is managed with the help of a hardware byte counter: NBYTES[7:0] in the I2C_CR2
         // register. The PECEN bit must be configured before enabling the I2C.
@@ -380,6 +683,27 @@ where
         // RELOAD is set, PECBYTE has no effect.
         // Caution: Changing the PECEN configuration is not allowed when the I2C is enabled.
 
+        // "Timeout detection: The timeout detection is enabled by setting the TIMOUTEN and TEXTEN
+        // bits in the I2C_TIMEOUTR register. The timers must be programmed in such a way that they
+        // detect a timeout before the maximum time given in the SMBus specification."
+        // tTIMEOUT = (TIMEOUTA+1) x 2048 x tI2CCLK. tLOW:EXT = (TIMEOUTB+1) x 2048 x tI2CCLK
+        // Check these before changing any settings.
+        let i2c_clock = clocks.apb1() as u64;
+        let timeout_val = |us: u32| {
+            let val = (us as u64 * i2c_clock / 1_000_000 / 2_048).saturating_sub(1);
+            if val > 0xfff {
+                return Err(Error::OutOfRange);
+            }
+            Ok(val as u16)
+        };
+        let timeout_a = self.cfg.smbus_cfg.timeout.map(timeout_val).transpose()?;
+        let timeout_b = self
+            .cfg
+            .smbus_cfg
+            .clock_extend_timeout
+            .map(timeout_val)
+            .transpose()?;
+
         let originally_enabled = self.regs.cr1.read().pe().bit_is_set();
         if originally_enabled {
             self.regs.cr1.modify(|_, w| w.pe().clear_bit());
@@ -393,12 +717,34 @@ where
             }
         }
 
-        self.regs.cr1.modify(|_, w| w.pecen().set_bit());
+        self.cfg.smbus = true;
 
-        // todo: Timeout detection?
+        // "SMBus Host address enable: When the SMBHEN bit is set, the SMBus host address 0b0001000
+        // is acknowledged." "SMBus alert enable, host mode: SMBA pin detection is enabled."
+        self.regs.cr1.modify(|_, w| {
+            w.pecen().bit(self.cfg.smbus_cfg.pec);
+            w.smbhen().bit(self.cfg.smbus_cfg.host_notify);
+            w.alerten().bit(self.cfg.smbus_cfg.alert)
+        });
 
-        // todo: HWCFGR Missing from PAC
-        // self.regs.hwcfgr.modify(|_, w| w.smbus().set_bit());
+        // "These bits can be written only when TIMOUTEN = 0", and "when TEXTEN = 0".
+        self.regs.timeoutr.write(|w| unsafe { w.bits(0) });
+        self.regs.timeoutr.write(|w| unsafe {
+            if let Some(t) = timeout_a {
+                // TIDLE = 0: TIMEOUTA is used to detect SCL low timeout.
+                w.timeouta().bits(t);
+                w.tidle().clear_bit();
+            }
+            if let Some(t) = timeout_b {
+                w.timeoutb().bits(t);
+            }
+            w
+        });
+        self.regs.timeoutr.modify(|_, w| {
+            w.timouten().bit(self.cfg.smbus_cfg.timeout.is_some());
+            w.texten()
+                .bit(self.cfg.smbus_cfg.clock_extend_timeout.is_some())
+        });
 
         if originally_enabled {
             self.regs.cr1.modify(|_, w| w.pe().set_bit());
@@ -413,27 +759,24 @@ where
         // automatically. This could be up to 50% of a bus
         // cycle (ie. up to 0.5/freq)
 
-        let mut i = 0;
-        while self.regs.cr2.read().start().bit_is_set() {
-            i += 1;
-            if i >= MAX_ITERS {
-                return Err(Error::Hardware);
-            }
-        }
+        self.wait_bus_ready()?;
 
         // Set START and prepare to receive bytes into
         // `buffer`. The START bit can be set even if the bus
         // is BUSY or I2C is in slave mode.
-        self.set_cr2_read(addr, bytes.len() as u8);
+        self.set_cr2_read(addr, bytes.len(), true);
+
+        let len = bytes.len();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            self.wait_reload(i)?;
 
-        for byte in bytes {
             // Wait until we have received something
             busy_wait!(self.regs, rxne);
 
             *byte = self.regs.rxdr.read().rxdata().bits();
         }
 
-        Ok(())
+        self.read_pec(len)
     }
 
     /// Write an array of words. Can return an error due to Bus, Arbitration, or NACK.
@@ -441,17 +784,13 @@ where
         // Wait for any previous address sequence to end
         // automatically. This could be up to 50% of a bus
         // cycle (ie. up to 0.5/freq)
-        let mut i = 0;
-        while self.regs.cr2.read().start().bit_is_set() {
-            i += 1;
-            if i >= MAX_ITERS {
-                return Err(Error::Hardware);
-            }
-        }
+        self.wait_bus_ready()?;
 
-        self.set_cr2_write(addr, bytes.len() as u8, true);
+        self.set_cr2_write(addr, bytes.len(), true);
+
+        for (i, byte) in bytes.iter().enumerate() {
+            self.wait_reload(i)?;
 
-        for byte in bytes {
             // Wait until we are allowed to send data
             // (START has been ACKed or last byte when
             // through)
@@ -469,17 +808,13 @@ where
         // Wait for any previous address sequence to end
         // automatically. This could be up to 50% of a bus
         // cycle (ie. up to 0.5/freq)
-        let mut i = 0;
-        while self.regs.cr2.read().start().bit_is_set() {
-            i += 1;
-            if i >= MAX_ITERS {
-                return Err(Error::Hardware);
-            }
-        }
+        self.wait_bus_ready()?;
+
+        self.set_cr2_write(addr, bytes.len(), false);
 
-        self.set_cr2_write(addr, bytes.len() as u8, false);
+        for (i, byte) in bytes.iter().enumerate() {
+            self.wait_reload(i)?;
 
-        for byte in bytes {
             // Wait until we are allowed to send data
             // (START has been ACKed or last byte went through)
 
@@ -494,20 +829,180 @@ where
 
         // reSTART and prepare to receive bytes into `buffer`
 
-        self.set_cr2_read(addr, buffer.len() as u8);
+        self.set_cr2_read(addr, buffer.len(), true);
+
+        let len = buffer.len();
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            self.wait_reload(i)?;
 
-        for byte in buffer {
             // Wait until we have received something
             busy_wait!(self.regs, rxne);
 
             *byte = self.regs.rxdr.read().rxdata().bits();
         }
 
+        self.read_pec(len)
+    }
+
+    /// SMBus Write Byte: Write a command code, then a data byte.
+    pub fn smbus_write_byte(&mut self, addr: u8, command: u8, value: u8) -> Result<(), Error> {
+        self.write(addr, &[command, value])
+    }
+
+    /// SMBus Read Byte: Write a command code, then read a data byte.
+    pub fn smbus_read_byte(&mut self, addr: u8, command: u8) -> Result<u8, Error> {
+        let mut buf = [0];
+        self.write_read(addr, &[command], &mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// SMBus Write Word: Write a command code, then a 16-bit value, low byte first.
+    pub fn smbus_write_word(&mut self, addr: u8, command: u8, value: u16) -> Result<(), Error> {
+        let v = value.to_le_bytes();
+        self.write(addr, &[command, v[0], v[1]])
+    }
+
+    /// SMBus Read Word: Write a command code, then read a 16-bit value, low byte first.
+    pub fn smbus_read_word(&mut self, addr: u8, command: u8) -> Result<u16, Error> {
+        let mut buf = [0; 2];
+        self.write_read(addr, &[command], &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// SMBus Process Call: Write a command code and a 16-bit value, then read a 16-bit value.
+    pub fn smbus_process_call(&mut self, addr: u8, command: u8, value: u16) -> Result<u16, Error> {
+        let v = value.to_le_bytes();
+        let mut buf = [0; 2];
+        self.write_read(addr, &[command, v[0], v[1]], &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// SMBus Block Write: Write a command code, a byte count, then the data. `data` can be up to 32
+    /// bytes per SMBus 2.0, or 255 per SMBus 3; returns `Error::OutOfRange` if it's longer.
+    pub fn smbus_block_write(&mut self, addr: u8, command: u8, data: &[u8]) -> Result<(), Error> {
+        if data.len() > 255 {
+            return Err(Error::OutOfRange);
+        }
+
+        self.wait_bus_ready()?;
+
+        self.set_cr2_write(addr, data.len() + 2, true);
+
+        for (i, byte) in [command, data.len() as u8]
+            .iter()
+            .chain(data.iter())
+            .enumerate()
+        {
+            self.wait_reload(i)?;
+            busy_wait!(self.regs, txis);
+            self.regs.txdr.write(|w| unsafe { w.txdata().bits(*byte) });
+        }
+
         Ok(())
     }
 
+    /// SMBus Block Read: Write a command code, then read a byte count, and that number of data bytes.
+    /// Returns the byte count sent by the device. If it's larger than `buf`, the excess bytes are
+    /// discarded.
+    pub fn smbus_block_read(
+        &mut self,
+        addr: u8,
+        command: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.wait_bus_ready()?;
+
+        self.set_cr2_write(addr, 1, false);
+        busy_wait!(self.regs, txis);
+        self.regs
+            .txdr
+            .write(|w| unsafe { w.txdata().bits(command) });
+        busy_wait!(self.regs, tc);
+
+        // The length isn't known until the count byte is read; use reload mode to read it, then set
+        // the remaining number of bytes. "RELOAD: The transfer is not completed after the NBYTES data
+        // transfer (NBYTES is reloaded). TCR flag is set when NBYTES data are transferred." "If
+        // RELOAD is set, PECBYTE has no effect."
+        self.regs.cr2.write(|w| unsafe {
+            w.add10().bit(self.cfg.address_bits as u8 != 0);
+            w.sadd().bits((addr << 1) as u16);
+            w.rd_wrn().set_bit();
+            w.nbytes().bits(1);
+            w.reload().set_bit();
+            w.start().set_bit()
+        });
+
+        busy_wait!(self.regs, rxne);
+        let count = self.regs.rxdr.read().rxdata().bits();
+        busy_wait!(self.regs, tcr);
+
+        let pec = self.pec_enabled();
+
+        // There's nothing left to read, and NBYTES can't be reloaded with 0 (SCL stays stretched
+        // until it's non-zero), so end the transfer here.
+        if count == 0 && !pec {
+            self.regs.cr2.modify(|_, w| {
+                w.reload().clear_bit();
+                w.stop().set_bit()
+            });
+            busy_wait!(self.regs, stopf);
+            self.regs.icr.write(|w| w.stopcf().set_bit());
+            return Ok(0);
+        }
+
+        self.regs.cr2.modify(|_, w| {
+            w.autoend().set_bit();
+            w.pecbyte().bit(pec)
+        });
+        // "NBYTES ... must be written when TCR is set."
+        self.reload_remaining = count as usize + pec as usize;
+        self.load_next_chunk();
+
+        for i in 0..count as usize {
+            self.wait_reload(i)?;
+            busy_wait!(self.regs, rxne);
+            let byte = self.regs.rxdr.read().rxdata().bits();
+            if i < buf.len() {
+                buf[i] = byte;
+            }
+        }
+
+        self.read_pec(count as usize)?;
+
+        Ok(count as usize)
+    }
+
+    /// Host mode: Check if a device is asserting the SMBA (SMBus alert) pin. Requires
+    /// `smbus_cfg.alert` to be set. Clears the flag.
+    pub fn smbus_alert_pending(&mut self) -> bool {
+        let alert = self.regs.isr.read().alert().bit_is_set();
+        if alert {
+            self.regs.icr.write(|w| w.alertcf().set_bit());
+        }
+        alert
+    }
+
+    /// Host mode: Read from the Alert Response Address, after an alert is detected, to find
+    /// which device asserted SMBA. Returns the 7-bit address of the device; if several devices
+    /// assert it, the one with the lowest address responds, and the others keep it asserted.
+    pub fn smbus_alert_response(&mut self) -> Result<u8, Error> {
+        let mut buf = [0];
+        self.read(SMBUS_ALERT_RESPONSE_ADDR, &mut buf)?;
+        Ok(buf[0] >> 1)
+    }
+
+    /// Device mode: Assert or release the SMBA (SMBus alert) pin. "SMBus alert enable, device mode:
+    /// SMBA pin is driven low when set. Releasing the SMBA pin is done when ALERTEN is cleared."
+    pub fn smbus_set_alert(&mut self, asserted: bool) {
+        self.regs.cr1.modify(|_, w| w.alerten().bit(asserted));
+    }
+
     /// Helper function to prevent repetition between `write`, `write_read`, and `write_dma`.
-    fn set_cr2_write(&mut self, addr: u8, len: u8, autoend: bool) {
+    fn set_cr2_write(&mut self, addr: u8, len: usize, autoend: bool) {
+        // The PEC is only sent at the end of a message; not before a repeated start.
+        let pec = autoend && self.pec_enabled();
+        let (nbytes, reload) = self.first_chunk(len + pec as usize);
+
         // L44 RM: "Master communication initialization (address phase)
         // In order to initiate the communication, the user must program the following parameters for
         // the addressed slave in the I2C_CR2 register:
@@ -523,7 +1018,10 @@ where
                 w.rd_wrn().clear_bit(); // write
                                         // The number of bytes to be transferred: NBYTES[7:0]. If the number of bytes is equal to
                                         // or greater than 255 bytes, NBYTES[7:0] must initially be filled with 0xFF.
-                w.nbytes().bits(len);
+                w.nbytes().bits(nbytes);
+                // "RELOAD: The transfer is not completed after the NBYTES data transfer (NBYTES
+                // will be reloaded)." "AUTOEND: This bit has no effect when RELOAD is set."
+                w.reload().bit(reload);
                 w.autoend().bit(autoend); // software end mode
                                           // The user must then set the START bit in I2C_CR2 register. Changing all the above bits is
                                           // not allowed when START bit is set.
@@ -534,7 +1032,7 @@ where
                                           // If the SMBus master wants to send a STOP condition after the PEC, automatic end mode
                                           // must be selected (AUTOEND=1). In this case, the STOP condition automatically follows the
                                           // PEC transmission.
-                w.pecbyte().bit(self.cfg.smbus);
+                w.pecbyte().bit(pec);
                 w.start().set_bit()
             }
         });
@@ -546,32 +1044,215 @@ where
     }
 
     /// Helper function to prevent repetition between `read`, `write_read`, and `read_dma`.
-    fn set_cr2_read(&mut self, addr: u8, len: u8) {
+    fn set_cr2_read(&mut self, addr: u8, len: usize, autoend: bool) {
+        let pec = autoend && self.pec_enabled();
+        let (nbytes, reload) = self.first_chunk(len + pec as usize);
+
         self.regs.cr2.write(|w| {
             unsafe {
                 w.add10().bit(self.cfg.address_bits as u8 != 0);
                 w.sadd().bits((addr << 1) as u16);
                 w.rd_wrn().set_bit(); // read
-                w.nbytes().bits(len);
-                w.autoend().set_bit(); // automatic end mode
-                                       // When the SMBus master wants to receive the PEC followed by a STOP at the end of the
-                                       // transfer, automatic end mode can be selected (AUTOEND=1). The PECBYTE bit must be
-                                       // set and the slave address must be programmed, before setting the START bit. In this case,
-                                       // after NBYTES-1 data have been received, the next received byte is automatically checked
-                                       // versus the I2C_PECR register content. A NACK response is given to the PEC byte, followed
-                                       // by a STOP condition.
-                w.pecbyte().bit(self.cfg.smbus);
+                w.nbytes().bits(nbytes);
+                w.reload().bit(reload);
+                w.autoend().bit(autoend); // automatic end mode, unless a repeated start follows
+                                          // When the SMBus master wants to receive the PEC followed by a STOP at the end of the
+                                          // transfer, automatic end mode can be selected (AUTOEND=1). The PECBYTE bit must be
+                                          // set and the slave address must be programmed, before setting the START bit. In this case,
+                                          // after NBYTES-1 data have been received, the next received byte is automatically checked
+                                          // versus the I2C_PECR register content. A NACK response is given to the PEC byte, followed
+                                          // by a STOP condition.
+                w.pecbyte().bit(pec);
                 w.start().set_bit()
             }
         });
     }
 
+    /// Split a transfer of `len` bytes (including PEC) into chunks of up to 255 bytes, and return the
+    /// NBYTES and RELOAD values for the first one. L44 RM, section 37.4.9: "Master transmitter":
+    /// "If the total number of data bytes to be sent is greater than 255, reload mode must be selected
+    /// by setting the RELOAD bit in the I2C_CR2 register. In this case, when NBYTES data have been
+    /// transferred, the TCR flag is set and the SCL line is stretched low until NBYTES[7:0] is written
+    /// to a non-zero value."
+    fn first_chunk(&mut self, len: usize) -> (u8, bool) {
+        let nbytes = len.min(255);
+        self.reload_remaining = len - nbytes;
+        (nbytes as u8, self.reload_remaining != 0)
+    }
+
+    /// Set NBYTES and RELOAD for the next chunk of a transfer longer than 255 bytes. This clears TCR.
+    fn load_next_chunk(&mut self) {
+        let nbytes = self.reload_remaining.min(255);
+        self.reload_remaining -= nbytes;
+        let reload = self.reload_remaining != 0;
+
+        self.regs.cr2.modify(|_, w| unsafe {
+            w.nbytes().bits(nbytes as u8);
+            w.reload().bit(reload)
+        });
+    }
+
+    /// In a blocking transfer, before transferring byte `i`: If a 255-byte chunk was just completed,
+    /// wait for TCR, and load the next chunk.
+    fn wait_reload(&mut self, i: usize) -> Result<(), Error> {
+        if i == 0 || i % 255 != 0 {
+            return Ok(());
+        }
+
+        busy_wait!(self.regs, tcr);
+        self.load_next_chunk();
+
+        Ok(())
+    }
+
+    /// For DMA transfers longer than 255 bytes: Run this when the transfer complete reload (TCR) flag
+    /// is set to continue the transfer; eg in the I2C event interrupt, with the `TransferComplete`
+    /// interrupt enabled. The SCL line is stretched low until this is run. Returns `true` if it loaded
+    /// a new chunk; `false` if TCR wasn't set.
+    pub fn reload(&mut self) -> bool {
+        if self.regs.isr.read().tcr().bit_is_clear() || self.reload_remaining == 0 {
+            return false;
+        }
+
+        self.load_next_chunk();
+        true
+    }
+
+    /// Wait for any previous address sequence to end, and for the bus to be free. Returns `BusBusy`
+    /// if the bus doesn't become free; eg if a device is holding SDA low.
+    fn wait_bus_ready(&mut self) -> Result<(), Error> {
+        let mut i = 0;
+        while self.regs.cr2.read().start().bit_is_set() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::Hardware);
+            }
+        }
+
+        // "BUSY: This flag indicates that a communication is in progress on the bus. It is set by
+        // hardware when a START condition is detected. It is cleared by hardware when a STOP
+        // condition is detected, or when PE=0."
+        i = 0;
+        while self.regs.isr.read().busy().bit_is_set() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::BusBusy);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recover the bus when a device is holding SDA low; eg after it was reset mid-transfer. This
+    /// temporarily configures the SCL and SDA pins as open-drain outputs, sends up to 9 clock pulses
+    /// until the device releases SDA, then sends a STOP condition. Afterwards, it sets the pins back
+    /// to their alternate functions (`scl_af` and `sda_af`), and resets the I2C peripheral.
+    /// See the I2C specification (UM10204), section 3.1.16: "Bus clear". Returns `BusBusy` if SDA is
+    /// still held low.
+    pub fn recover_bus(
+        &mut self,
+        scl: &mut Pin,
+        sda: &mut Pin,
+        scl_af: u8,
+        sda_af: u8,
+        clocks: &Clocks,
+    ) -> Result<(), Error> {
+        // "If the data line (SDA) is stuck LOW, the controller should send nine clock pulses. The
+        // device that held the bus LOW should release it sometime within those nine clocks."
+
+        // Software reset: "PE must be kept low during at least 3 APB clock cycles in order to
+        // perform the software reset."
+        self.regs.cr1.modify(|_, w| w.pe().clear_bit());
+
+        // A half-period at 100kHz.
+        let half_period = clocks.sysclk() / 200_000;
+
+        for pin in [&mut *scl, &mut *sda] {
+            pin.set_high();
+            pin.output_type(OutputType::OpenDrain);
+            pin.mode(PinMode::Output);
+        }
+        cortex_m::asm::delay(half_period);
+
+        for _ in 0..9 {
+            if sda.is_high() {
+                break;
+            }
+
+            scl.set_low();
+            cortex_m::asm::delay(half_period);
+            scl.set_high();
+
+            // Allow the device to stretch the clock.
+            let mut i = 0;
+            while scl.is_low() {
+                i += 1;
+                if i >= MAX_ITERS {
+                    break;
+                }
+            }
+            cortex_m::asm::delay(half_period);
+        }
+
+        // STOP condition: SDA goes high while SCL is high.
+        scl.set_low();
+        cortex_m::asm::delay(half_period);
+        sda.set_low();
+        cortex_m::asm::delay(half_period);
+        scl.set_high();
+        cortex_m::asm::delay(half_period);
+        sda.set_high();
+        cortex_m::asm::delay(half_period);
+
+        let released = sda.is_high();
+
+        scl.mode(PinMode::Alt(scl_af));
+        sda.mode(PinMode::Alt(sda_af));
+
+        self.regs.cr1.modify(|_, w| w.pe().set_bit());
+
+        if released {
+            Ok(())
+        } else {
+            Err(Error::BusBusy)
+        }
+    }
+
+    /// If PEC is sent and checked at the end of each transfer.
+    fn pec_enabled(&self) -> bool {
+        self.cfg.smbus && self.cfg.smbus_cfg.pec
+    }
+
+    /// After reading data, receive the PEC byte, and check for a PEC error. No effect if PEC is
+    /// disabled. "The PEC byte is compared to the internal PEC computed value. If the comparison
+    /// doesn't match, PECERR is set, and the PEC byte is NACKed, followed by a STOP condition."
+    fn read_pec(&mut self, len: usize) -> Result<(), Error> {
+        if !self.pec_enabled() {
+            return Ok(());
+        }
+
+        self.wait_reload(len)?;
+        busy_wait!(self.regs, rxne);
+        let _ = self.regs.rxdr.read().rxdata().bits();
+
+        busy_wait!(self.regs, stopf);
+        self.regs.icr.write(|w| w.stopcf().set_bit());
+
+        if self.regs.isr.read().pecerr().bit_is_set() {
+            self.regs.icr.write(|w| w.peccf().set_bit());
+            return Err(Error::Pec);
+        }
+
+        Ok(())
+    }
+
     #[cfg(not(feature = "g0"))]
     /// Read data, using DMA. See L44 RM, 37.4.16: "Transmission using DMA"
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
     /// For a single write, set `autoend` to `true`. For a write_read and other use cases,
-    /// set it to `false`.
+    /// set it to `false`. For buffers longer than 255 bytes, run `reload()` on each TCR event.
     #[cfg(not(any(feature = "l552", feature = "h5")))]
     pub unsafe fn write_dma(
         &mut self,
@@ -585,11 +1266,9 @@ where
         let (ptr, len) = (buf.as_ptr(), buf.len());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::write_chan();
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
-        #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::write_sel(dma_periph);
 
         // DMA (Direct Memory Access) can be enabled for transmission by setting the TXDMAEN bit
         // in the I2C_CR1 register. Data is loaded from an SRAM area configured using the DMA
@@ -605,7 +1284,7 @@ where
         // initialized before setting the START bit. The end of transfer is managed with the
         // NBYTES counter. Refer to Master transmitter on page 1151.
         // (The steps above are handled in the write this function performs.)
-        self.set_cr2_write(addr, len as u8, autoend);
+        self.set_cr2_write(addr, len, autoend);
 
         // • In slave mode:
         // – With NOSTRETCH=0, when all data are transferred using DMA, the DMA must be
@@ -619,47 +1298,22 @@ where
         // page 1169.
         // Note: If DMA is used for transmission, the TXIE bit does not need to be enabled
 
-        #[cfg(feature = "h7")]
-        let num_data = len as u32;
-        #[cfg(not(feature = "h7"))]
-        let num_data = len as u16;
-
-        match dma_periph {
-            dma::DmaPeriph::Dma1 => {
-                let mut regs = unsafe { &(*DMA1::ptr()) };
-                dma::cfg_channel(
-                    &mut regs,
-                    channel,
-                    &self.regs.txdr as *const _ as u32,
-                    ptr as u32,
-                    num_data,
-                    dma::Direction::ReadFromMem,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
-                    channel_cfg,
-                );
-            }
-            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
-            dma::DmaPeriph::Dma2 => {
-                let mut regs = unsafe { &(*pac::DMA2::ptr()) };
-                dma::cfg_channel(
-                    &mut regs,
-                    channel,
-                    &self.regs.txdr as *const _ as u32,
-                    ptr as u32,
-                    num_data,
-                    dma::Direction::ReadFromMem,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
-                    channel_cfg,
-                );
-            }
-        }
+        cfg_dma(
+            &self.regs.txdr as *const _ as u32,
+            ptr as u32,
+            len,
+            dma::Direction::ReadFromMem,
+            channel,
+            channel_cfg,
+            dma_periph,
+        );
     }
 
     /// Read data, using DMA. See L44 RM, 37.4.16: "Reception using DMA"
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
+    /// For buffers longer than 255 bytes, run `reload()` on each TCR event.
     #[cfg(not(any(feature = "l552", feature = "h5")))]
     pub unsafe fn read_dma(
         &mut self,
@@ -672,11 +1326,9 @@ where
         let (ptr, len) = (buf.as_mut_ptr(), buf.len());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::read_chan();
-        #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::read_sel(dma_periph);
 
         // DMA (Direct Memory Access) can be enabled for reception by setting the RXDMAEN bit in
         // the I2C_CR1 register. Data is loaded from the I2C_RXDR register to an SRAM area
@@ -690,7 +1342,7 @@ where
         // START bit are programmed by software. When all data are transferred using DMA, the
         // DMA must be initialized before setting the START bit. The end of transfer is managed
         // with the NBYTES counter.
-        self.set_cr2_read(addr, len as u8);
+        self.set_cr2_read(addr, len, true);
 
         // • In slave mode with NOSTRETCH=0, when all data are transferred using DMA, the
         // DMA must be initialized before the address match event, or in the ADDR interrupt
@@ -700,42 +1352,212 @@ where
         // SMBus Master receiver on page 1171.
         // Note: If DMA is used for reception, the RXIE bit does not need to be enabled
 
-        #[cfg(feature = "h7")]
-        let num_data = len as u32;
-        #[cfg(not(feature = "h7"))]
-        let num_data = len as u16;
-
-        match dma_periph {
-            dma::DmaPeriph::Dma1 => {
-                let mut regs = unsafe { &(*DMA1::ptr()) };
-                dma::cfg_channel(
-                    &mut regs,
-                    channel,
-                    &self.regs.rxdr as *const _ as u32,
-                    ptr as u32,
-                    num_data,
-                    dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
-                    channel_cfg,
-                );
-            }
-            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
-            dma::DmaPeriph::Dma2 => {
-                let mut regs = unsafe { &(*pac::DMA2::ptr()) };
-                dma::cfg_channel(
-                    &mut regs,
-                    channel,
-                    &self.regs.rxdr as *const _ as u32,
-                    ptr as u32,
-                    num_data,
-                    dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
-                    channel_cfg,
-                );
+        cfg_dma(
+            &self.regs.rxdr as *const _ as u32,
+            ptr as u32,
+            len,
+            dma::Direction::ReadFromPeriph,
+            channel,
+            channel_cfg,
+            dma_periph,
+        );
+    }
+
+    /// Handle the next pending slave mode event, and clear its flag. Returns `None` if no event is
+    /// pending. Run this in the I2C event interrupt handler, with the `Address`, `Rx`, `Tx`, `Nack`,
+    /// and `Stop` interrupts enabled, or poll it. See L44 RM, section 37.4.8: "I2C slave mode".
+    pub fn slave_poll(&mut self) -> Result<Option<SlaveEvent>, Error> {
+        let isr = self.regs.isr.read();
+
+        if isr.berr().bit_is_set() {
+            self.regs.icr.write(|w| w.berrcf().set_bit());
+            return Err(Error::Bus);
+        }
+        if isr.ovr().bit_is_set() {
+            self.regs.icr.write(|w| w.ovrcf().set_bit());
+            return Err(Error::Overrun);
+        }
+
+        if isr.addr().bit_is_set() {
+            let direction = if isr.dir().bit_is_set() {
+                TransferDirection::Read
+            } else {
+                TransferDirection::Write
+            };
+
+            // "In order to ensure that the slave transmits the expected data, TXE must be set (by
+            // software) before clearing the ADDR flag, to flush the data remaining in TXDR from a
+            // previous transfer." With NOSTRETCH=1, the first byte must already be in TXDR.
+            if direction == TransferDirection::Read && !self.cfg.nostretch {
+                self.regs.isr.write(|w| w.txe().set_bit());
             }
+
+            let address = isr.addcode().bits();
+
+            // "When NOSTRETCH=0, the SCL clock is stretched low as long as the ADDR flag is set,
+            // until it is cleared by software by setting the ADDRCF bit."
+            self.regs.icr.write(|w| w.addrcf().set_bit());
+
+            return Ok(Some(SlaveEvent::AddressMatch { address, direction }));
+        }
+
+        // Handle received data before the stop condition, so the last byte isn't missed.
+        if isr.rxne().bit_is_set() {
+            return Ok(Some(SlaveEvent::Received(
+                self.regs.rxdr.read().rxdata().bits(),
+            )));
+        }
+
+        if isr.txis().bit_is_set() {
+            return Ok(Some(SlaveEvent::TransmitRequest));
+        }
+
+        if isr.nackf().bit_is_set() {
+            self.regs.icr.write(|w| w.nackcf().set_bit());
+            return Ok(Some(SlaveEvent::Nack));
+        }
+
+        if isr.stopf().bit_is_set() {
+            self.regs.icr.write(|w| w.stopcf().set_bit());
+            return Ok(Some(SlaveEvent::Stop));
         }
+
+        Ok(None)
+    }
+
+    /// Write a byte to be sent to the master, in response to `SlaveEvent::TransmitRequest`.
+    /// "When the TXIS flag is set, the next data byte must be written in the I2C_TXDR register."
+    pub fn slave_write_byte(&mut self, byte: u8) {
+        self.regs.txdr.write(|w| unsafe { w.txdata().bits(byte) });
+    }
+
+    /// Enable a specific type of interrupt.
+    pub fn enable_interrupt(&mut self, interrupt: I2cInterrupt) {
+        self.regs.cr1.modify(|_, w| match interrupt {
+            I2cInterrupt::Address => w.addrie().set_bit(),
+            I2cInterrupt::Stop => w.stopie().set_bit(),
+            I2cInterrupt::Nack => w.nackie().set_bit(),
+            I2cInterrupt::Rx => w.rxie().set_bit(),
+            I2cInterrupt::Tx => w.txie().set_bit(),
+            I2cInterrupt::TransferComplete => w.tcie().set_bit(),
+            I2cInterrupt::Error => w.errie().set_bit(),
+        });
+    }
+
+    /// Disable a specific type of interrupt.
+    pub fn disable_interrupt(&mut self, interrupt: I2cInterrupt) {
+        self.regs.cr1.modify(|_, w| match interrupt {
+            I2cInterrupt::Address => w.addrie().clear_bit(),
+            I2cInterrupt::Stop => w.stopie().clear_bit(),
+            I2cInterrupt::Nack => w.nackie().clear_bit(),
+            I2cInterrupt::Rx => w.rxie().clear_bit(),
+            I2cInterrupt::Tx => w.txie().clear_bit(),
+            I2cInterrupt::TransferComplete => w.tcie().clear_bit(),
+            I2cInterrupt::Error => w.errie().clear_bit(),
+        });
+    }
+
+    /// Clear an interrupt flag. Rx, Tx, and TransferComplete flags are cleared by hardware, when
+    /// reading RXDR, writing TXDR, or starting a new transfer respectively.
+    pub fn clear_interrupt(&mut self, interrupt: I2cInterrupt) {
+        match interrupt {
+            I2cInterrupt::Address => self.regs.icr.write(|w| w.addrcf().set_bit()),
+            I2cInterrupt::Stop => self.regs.icr.write(|w| w.stopcf().set_bit()),
+            I2cInterrupt::Nack => self.regs.icr.write(|w| w.nackcf().set_bit()),
+            I2cInterrupt::Rx | I2cInterrupt::Tx | I2cInterrupt::TransferComplete => (),
+            I2cInterrupt::Error => self.regs.icr.write(|w| {
+                w.berrcf().set_bit();
+                w.arlocf().set_bit();
+                w.ovrcf().set_bit();
+                w.peccf().set_bit();
+                w.timoutcf().set_bit();
+                w.alertcf().set_bit()
+            }),
+        }
+    }
+
+    /// Send data to the master using DMA, in slave mode. See L44 RM, 37.4.16: "Transmission using DMA":
+    /// "With NOSTRETCH=0, when all data are transferred using DMA, the DMA must be initialized before
+    /// the address match event, or in ADDR interrupt subroutine, before clearing ADDR." Ie, run this
+    /// before the address match, or on `SlaveEvent::AddressMatch` with clock stretching enabled.
+    /// The master ends the transfer with a NACK; use `stop_dma()` on the `Stop` event.
+    #[cfg(not(any(feature = "l552", feature = "h5")))]
+    pub unsafe fn slave_write_dma(
+        &mut self,
+        buf: &[u8],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
+        #[cfg(feature = "l4")]
+        R::write_sel(dma_periph);
+
+        cfg_dma(
+            &self.regs.txdr as *const _ as u32,
+            ptr as u32,
+            len,
+            dma::Direction::ReadFromMem,
+            channel,
+            channel_cfg,
+            dma_periph,
+        );
+
+        self.regs.cr1.modify(|_, w| w.txdmaen().set_bit());
+    }
+
+    /// Receive data from the master using DMA, in slave mode. See `slave_write_dma()`. Use
+    /// `stop_dma()` on the `Stop` event; the number of bytes received is the buffer length, minus
+    /// the `dma::transfers_remaining()` at that point.
+    #[cfg(not(any(feature = "l552", feature = "h5")))]
+    pub unsafe fn slave_read_dma(
+        &mut self,
+        buf: &mut [u8],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        let (ptr, len) = (buf.as_mut_ptr(), buf.len());
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+        #[cfg(feature = "l4")]
+        R::read_sel(dma_periph);
+
+        cfg_dma(
+            &self.regs.rxdr as *const _ as u32,
+            ptr as u32,
+            len,
+            dma::Direction::ReadFromPeriph,
+            channel,
+            channel_cfg,
+            dma_periph,
+        );
+
+        self.regs.cr1.modify(|_, w| w.rxdmaen().set_bit());
+    }
+
+    /// Stop a DMA transfer, and disable DMA requests. `channel2` is an optional second channel
+    /// to stop; eg if both transmit and receive channels are set up.
+    #[cfg(not(any(feature = "l552", feature = "h5")))]
+    pub fn stop_dma(
+        &mut self,
+        channel: DmaChannel,
+        channel2: Option<DmaChannel>,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        dma::stop(dma_periph, channel);
+        if let Some(ch2) = channel2 {
+            dma::stop(dma_periph, ch2);
+        };
+
+        self.regs.cr1.modify(|_, w| {
+            w.txdmaen().clear_bit();
+            w.rxdmaen().clear_bit()
+        });
     }
 
     /// Print the (raw) contents of the status register.
@@ -744,6 +1566,358 @@ where
     }
 }
 
+/// Helper function to configure a DMA channel for I2C byte transfers.
+#[cfg(not(any(feature = "l552", feature = "h5")))]
+unsafe fn cfg_dma(
+    periph_addr: u32,
+    mem_addr: u32,
+    len: usize,
+    direction: dma::Direction,
+    channel: DmaChannel,
+    channel_cfg: ChannelCfg,
+    dma_periph: dma::DmaPeriph,
+) {
+    #[cfg(feature = "h7")]
+    let num_data = len as u32;
+    #[cfg(not(feature = "h7"))]
+    let num_data = len as u16;
+
+    match dma_periph {
+        dma::DmaPeriph::Dma1 => {
+            let mut regs = unsafe { &(*DMA1::ptr()) };
+            dma::cfg_channel(
+                &mut regs,
+                channel,
+                periph_addr,
+                mem_addr,
+                num_data,
+                direction,
+                dma::DataSize::S8,
+                dma::DataSize::S8,
+                channel_cfg,
+            );
+        }
+        #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
+        dma::DmaPeriph::Dma2 => {
+            let mut regs = unsafe { &(*pac::DMA2::ptr()) };
+            dma::cfg_channel(
+                &mut regs,
+                channel,
+                periph_addr,
+                mem_addr,
+                num_data,
+                direction,
+                dma::DataSize::S8,
+                dma::DataSize::S8,
+                channel_cfg,
+            );
+        }
+    }
+}
+
+/// Maximum number of operations in a transaction submitted to `I2cTransactions`.
+pub const MAX_OPS: usize = 8;
+
+/// One part of a transaction submitted to `I2cTransactions`.
+pub enum Operation<'a> {
+    /// Write the bytes to the device.
+    Write(&'a [u8]),
+    /// Read from the device, filling the buffer.
+    Read(&'a mut [u8]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The state of the transaction most recently submitted to `I2cTransactions`.
+pub enum TransactionStatus {
+    /// No transaction has been submitted.
+    Idle,
+    /// The transaction is underway.
+    InProgress,
+    /// The transaction completed, and a STOP was sent.
+    Complete,
+    /// The transaction was aborted.
+    Failed(Error),
+}
+
+#[derive(Clone, Copy)]
+/// An operation, with its buffer stored as a raw pointer, so it can be used from the interrupt
+/// handlers after `submit()` returns.
+struct RawOp {
+    read: bool,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl RawOp {
+    const EMPTY: Self = Self {
+        read: false,
+        ptr: core::ptr::null_mut(),
+        len: 0,
+    };
+}
+
+/// An interrupt-driven I2C master, which runs a transaction (a sequence of writes and reads) without
+/// blocking. Call `on_event()` from the I2C event interrupt handler, and `on_error()` from the
+/// error interrupt handler. (On G0, both run from the same handler.) Completion is reported with
+/// `status()`, and optionally a callback. This is usually stored in a global `Mutex<RefCell<Option<>>>`,
+/// or as a shared resource, so several tasks can share one bus without spinning.
+///
+/// Adjacent operations in the same direction are merged into one transfer, without a repeated start;
+/// eg a register address write followed by a data write. A repeated start is sent between operations
+/// with different directions, and a STOP after the last one.
+pub struct I2cTransactions<R> {
+    pub i2c: I2c<R>,
+    addr: u8,
+    ops: [RawOp; MAX_OPS],
+    num_ops: usize,
+    /// The operation, and byte within it, to transfer next.
+    op_i: usize,
+    byte_i: usize,
+    /// The index after the last operation in the current transfer.
+    run_end: usize,
+    status: TransactionStatus,
+    callback: Option<fn(Result<(), Error>)>,
+}
+
+impl<R> I2cTransactions<R>
+where
+    R: Deref<Target = pac::i2c1::RegisterBlock> + RccPeriph,
+{
+    /// Create a transaction engine from a master-mode I2C peripheral, and enable its event and error
+    /// interrupts. You must unmask the I2C event and error interrupt lines in the NVIC.
+    pub fn new(mut i2c: I2c<R>) -> Self {
+        for interrupt in [
+            I2cInterrupt::Tx,
+            I2cInterrupt::Rx,
+            I2cInterrupt::TransferComplete,
+            I2cInterrupt::Stop,
+            I2cInterrupt::Nack,
+            I2cInterrupt::Error,
+        ] {
+            i2c.enable_interrupt(interrupt);
+        }
+
+        Self {
+            i2c,
+            addr: 0,
+            ops: [RawOp::EMPTY; MAX_OPS],
+            num_ops: 0,
+            op_i: 0,
+            byte_i: 0,
+            run_end: 0,
+            status: TransactionStatus::Idle,
+            callback: None,
+        }
+    }
+
+    /// Set a function to run from the interrupt handler when a transaction completes or fails.
+    pub fn set_callback(&mut self, callback: fn(Result<(), Error>)) {
+        self.callback = Some(callback);
+    }
+
+    /// Start a transaction with the device at 7-bit address `addr`. This doesn't block. Returns
+    /// `BusBusy` if a transaction is already in progress, or if the bus is busy, and `OutOfRange` if
+    /// there are more than `MAX_OPS` operations.
+    ///
+    /// # Safety
+    /// The buffers in `ops` are accessed from the interrupt handlers after this returns. They must
+    /// remain valid, and not be otherwise accessed, until the transaction completes or fails.
+    pub unsafe fn submit(&mut self, addr: u8, ops: &mut [Operation]) -> Result<(), Error> {
+        if ops.len() > MAX_OPS {
+            return Err(Error::OutOfRange);
+        }
+
+        if self.status == TransactionStatus::InProgress
+            || self.i2c.regs.isr.read().busy().bit_is_set()
+        {
+            return Err(Error::BusBusy);
+        }
+
+        for (raw, op) in self.ops.iter_mut().zip(ops.iter_mut()) {
+            *raw = match op {
+                Operation::Write(buf) => RawOp {
+                    read: false,
+                    ptr: buf.as_ptr() as *mut u8,
+                    len: buf.len(),
+                },
+                Operation::Read(buf) => RawOp {
+                    read: true,
+                    ptr: buf.as_mut_ptr(),
+                    len: buf.len(),
+                },
+            };
+        }
+
+        self.addr = addr;
+        self.num_ops = ops.len();
+        self.op_i = 0;
+        self.byte_i = 0;
+
+        if self.num_ops == 0 {
+            self.finish(Ok(()));
+            return Ok(());
+        }
+
+        self.status = TransactionStatus::InProgress;
+        self.start_transfer();
+
+        Ok(())
+    }
+
+    /// The state of the most recently submitted transaction.
+    pub fn status(&self) -> TransactionStatus {
+        self.status
+    }
+
+    /// Returns `true` if no transaction is in progress.
+    pub fn is_done(&self) -> bool {
+        self.status != TransactionStatus::InProgress
+    }
+
+    /// Run this from the I2C event interrupt handler.
+    pub fn on_event(&mut self) {
+        let isr = self.i2c.regs.isr.read();
+
+        if self.status != TransactionStatus::InProgress {
+            // Eg the STOP following a NACK.
+            if isr.stopf().bit_is_set() {
+                self.i2c.regs.icr.write(|w| w.stopcf().set_bit());
+            }
+            return;
+        }
+
+        // "In master mode, when a NACK is received, a STOP condition is automatically sent."
+        if isr.nackf().bit_is_set() {
+            self.i2c.regs.icr.write(|w| w.nackcf().set_bit());
+            // Flush TXDR, so a pending byte isn't sent in the next transaction.
+            self.i2c.regs.isr.write(|w| w.txe().set_bit());
+            self.finish(Err(Error::Nack));
+            return;
+        }
+
+        if isr.rxne().bit_is_set() {
+            let byte = self.i2c.regs.rxdr.read().rxdata().bits();
+            // Bytes past the end of the operations are the PEC, which is checked by hardware.
+            if self.op_i < self.run_end {
+                let op = self.ops[self.op_i];
+                unsafe { *op.ptr.add(self.byte_i) = byte };
+                self.advance();
+            }
+        }
+
+        if isr.txis().bit_is_set() && self.op_i < self.run_end {
+            let op = self.ops[self.op_i];
+            let byte = unsafe { *op.ptr.add(self.byte_i) };
+            self.i2c
+                .regs
+                .txdr
+                .write(|w| unsafe { w.txdata().bits(byte) });
+            self.advance();
+        }
+
+        if isr.tcr().bit_is_set() {
+            self.i2c.reload();
+        }
+
+        // Set at the end of a transfer without AUTOEND; start the next one with a repeated start.
+        if isr.tc().bit_is_set() && self.run_end < self.num_ops {
+            self.start_transfer();
+        }
+
+        if isr.stopf().bit_is_set() {
+            self.i2c.regs.icr.write(|w| w.stopcf().set_bit());
+            self.finish(Ok(()));
+        }
+    }
+
+    /// Run this from the I2C error interrupt handler. Aborts the transaction on a bus error,
+    /// arbitration loss, overrun, PEC error, or SMBus timeout.
+    pub fn on_error(&mut self) {
+        let isr = self.i2c.regs.isr.read();
+
+        let error = if isr.berr().bit_is_set() {
+            Error::Bus
+        } else if isr.arlo().bit_is_set() {
+            Error::Arbitration
+        } else if isr.ovr().bit_is_set() {
+            Error::Overrun
+        } else if isr.pecerr().bit_is_set() {
+            Error::Pec
+        } else if isr.timeout().bit_is_set() {
+            Error::Timeout
+        } else {
+            return;
+        };
+
+        self.i2c.clear_interrupt(I2cInterrupt::Error);
+
+        if self.status == TransactionStatus::InProgress {
+            self.finish(Err(error));
+        }
+    }
+
+    /// Start a transfer of the current operation, and any following ones in the same direction.
+    fn start_transfer(&mut self) {
+        let read = self.ops[self.op_i].read;
+
+        let mut len = 0;
+        let mut end = self.op_i;
+        while end < self.num_ops && self.ops[end].read == read {
+            len += self.ops[end].len;
+            end += 1;
+        }
+        self.run_end = end;
+        self.skip_empty();
+
+        let autoend = end == self.num_ops;
+        if read {
+            self.i2c.set_cr2_read(self.addr, len, autoend);
+        } else {
+            self.i2c.set_cr2_write(self.addr, len, autoend);
+        }
+    }
+
+    /// Move to the next byte to transfer.
+    fn advance(&mut self) {
+        self.byte_i += 1;
+        self.skip_empty();
+    }
+
+    /// Skip past finished and zero-length operations in the current transfer.
+    fn skip_empty(&mut self) {
+        while self.op_i < self.run_end && self.byte_i >= self.ops[self.op_i].len {
+            self.op_i += 1;
+            self.byte_i = 0;
+        }
+    }
+
+    fn finish(&mut self, result: Result<(), Error>) {
+        self.status = match result {
+            Ok(()) => TransactionStatus::Complete,
+            Err(e) => TransactionStatus::Failed(e),
+        };
+
+        if let Some(callback) = self.callback {
+            callback(result);
+        }
+    }
+
+    /// Release the I2C peripheral, disabling its interrupts.
+    pub fn free(mut self) -> I2c<R> {
+        for interrupt in [
+            I2cInterrupt::Tx,
+            I2cInterrupt::Rx,
+            I2cInterrupt::TransferComplete,
+            I2cInterrupt::Stop,
+            I2cInterrupt::Nack,
+            I2cInterrupt::Error,
+        ] {
+            self.i2c.disable_interrupt(interrupt);
+        }
+        self.i2c
+    }
+}
+
 #[cfg(feature = "embedded_hal")]
 // #[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
 impl<R> Write for I2c<R>
@@ -782,3 +1956,183 @@ where
         I2c::write_read(self, addr, bytes, buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SCL period, in ps, using the model in `calc_timings()`, with the analog filter enabled.
+    fn t_scl(i2c_clock: u32, t: &I2cTimings, rise_time: u32, fall_time: u32) -> i64 {
+        let t_i2cclk = 1_000_000_000_000 / i2c_clock as i64;
+        let t_presc = (t.presc as i64 + 1) * t_i2cclk;
+        let t_sync = AF_DELAY_MIN as i64 * 1_000 + 2 * t_i2cclk;
+
+        (t.scll as i64 + 1) * t_presc
+            + (t.sclh as i64 + 1) * t_presc
+            + 2 * t_sync
+            + (rise_time + fall_time) as i64 * 1_000
+    }
+
+    fn timings(presc: u8, scll: u8, sclh: u8, sdadel: u8, scldel: u8) -> I2cTimings {
+        I2cTimings {
+            presc,
+            scldel,
+            sdadel,
+            sclh,
+            scll,
+        }
+    }
+
+    /// The examples from L44 RM, section 37.4.10: "I2C_TIMINGR register configuration examples".
+    fn rm_examples() -> [(u32, I2cSpeed, I2cTimings); 9] {
+        [
+            (
+                8_000_000,
+                I2cSpeed::Standard100K,
+                timings(1, 0x13, 0xf, 0x2, 0x4),
+            ),
+            (
+                8_000_000,
+                I2cSpeed::Fast400K,
+                timings(0, 0x9, 0x3, 0x1, 0x3),
+            ),
+            (
+                8_000_000,
+                I2cSpeed::FastPlus1M,
+                timings(0, 0x6, 0x3, 0x0, 0x1),
+            ),
+            (
+                16_000_000,
+                I2cSpeed::Standard100K,
+                timings(3, 0x13, 0xf, 0x2, 0x4),
+            ),
+            (
+                16_000_000,
+                I2cSpeed::Fast400K,
+                timings(1, 0x9, 0x3, 0x2, 0x3),
+            ),
+            (
+                16_000_000,
+                I2cSpeed::FastPlus1M,
+                timings(0, 0x4, 0x2, 0x0, 0x2),
+            ),
+            (
+                48_000_000,
+                I2cSpeed::Standard100K,
+                timings(0xb, 0x13, 0xf, 0x2, 0x4),
+            ),
+            (
+                48_000_000,
+                I2cSpeed::Fast400K,
+                timings(5, 0x9, 0x3, 0x3, 0x3),
+            ),
+            (
+                48_000_000,
+                I2cSpeed::FastPlus1M,
+                timings(5, 0x3, 0x1, 0x0, 0x1),
+            ),
+        ]
+    }
+
+    #[test]
+    fn timings_match_rm_examples() {
+        for (clock, speed, rm) in rm_examples() {
+            let t = calc_timings(clock, speed, 100, 10, NoiseFilter::Analog).unwrap();
+            let t_bus = 1_000_000_000_000 / speed.bus_spec().freq as i64;
+
+            // Within the target frequency, and 80% of it.
+            let period = t_scl(clock, &t, 100, 10);
+            assert!(period >= t_bus && period <= t_bus * 10 / 8);
+
+            // At least as close to the target as the RM's example, where that doesn't exceed it.
+            let rm_period = t_scl(clock, &rm, 100, 10);
+            if rm_period >= t_bus {
+                assert!(period <= rm_period);
+            }
+        }
+    }
+
+    #[test]
+    fn timings_meet_bus_spec() {
+        for (clock, speed, _) in rm_examples() {
+            for (rise_time, fall_time) in [(0, 0), (100, 10), (120, 120)] {
+                let t = match calc_timings(clock, speed, rise_time, fall_time, NoiseFilter::Analog)
+                {
+                    Some(t) => t,
+                    None => continue,
+                };
+                let spec = speed.bus_spec();
+
+                let t_i2cclk = 1_000_000_000_000 / clock as i64;
+                let t_presc = (t.presc as i64 + 1) * t_i2cclk;
+                let ps = |ns: u32| ns as i64 * 1_000;
+
+                // tSCLDEL >= tr + tSU;DAT(min)
+                assert!((t.scldel as i64 + 1) * t_presc >= ps(rise_time) + ps(spec.sudat_min));
+
+                // tSDADEL >= tf + tHD;DAT(min) - tAF(min) - 3 x tI2CCLK
+                let t_sdadel = t.sdadel as i64 * t_presc;
+                let sdadel_min =
+                    ps(fall_time) + ps(spec.hddat_min) - ps(AF_DELAY_MIN) - 3 * t_i2cclk;
+                assert!(t_sdadel >= sdadel_min);
+
+                // tSDADEL <= tVD;DAT(max) - tr - tAF(max) - 4 x tI2CCLK, or 0 if that's negative.
+                let sdadel_max =
+                    ps(spec.vddat_max) - ps(rise_time) - ps(AF_DELAY_MAX) - 4 * t_i2cclk;
+                assert!(t_sdadel <= sdadel_max.max(0));
+
+                let t_sync = ps(AF_DELAY_MIN) + 2 * t_i2cclk;
+                assert!((t.scll as i64 + 1) * t_presc + t_sync >= ps(spec.lscl_min));
+                assert!((t.sclh as i64 + 1) * t_presc + t_sync >= ps(spec.hscl_min));
+            }
+        }
+    }
+
+    #[test]
+    fn timings_none() {
+        // No I2C clock.
+        assert!(calc_timings(0, I2cSpeed::Standard100K, 100, 10, NoiseFilter::Analog).is_none());
+        // The I2C clock is too slow for the SCL low period to be 4 x tI2CCLK.
+        assert!(calc_timings(
+            1_000_000,
+            I2cSpeed::FastPlus1M,
+            100,
+            10,
+            NoiseFilter::Analog
+        )
+        .is_none());
+        // The rise and fall times use up the whole SCL period.
+        assert!(calc_timings(
+            48_000_000,
+            I2cSpeed::FastPlus1M,
+            600,
+            600,
+            NoiseFilter::Analog
+        )
+        .is_none());
+        // The slowest SCL possible is faster than 10 kHz.
+        assert!(calc_timings(
+            480_000_000,
+            I2cSpeed::Standard10K,
+            100,
+            10,
+            NoiseFilter::Analog
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn smbus_pec_known_values() {
+        // The CRC-8 check value: the CRC of ASCII "123456789".
+        assert_eq!(smbus_pec(b"123456789"), 0xf4);
+        assert_eq!(smbus_pec(&[]), 0);
+        assert_eq!(smbus_pec(&[0x01]), 0x07);
+        assert_eq!(smbus_pec(&[0xff]), 0xf3);
+
+        // Appending the PEC to a message gives a PEC of 0 over the whole thing; this is how a
+        // receiver checks it. Eg a write word to address 0x5a: address byte, command, and data.
+        let msg = [0x5a << 1, 0x06, 0x34, 0x12];
+        let pec = smbus_pec(&msg);
+        assert_eq!(smbus_pec(&[msg[0], msg[1], msg[2], msg[3], pec]), 0);
+    }
+}
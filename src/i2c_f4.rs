@@ -1,9 +1,10 @@
 //! I2C support for F4, which uses an older peripheral than the other families supported
-//! by this library.
+//! by this library. This module provides the same API as the `i2c` module, and is re-exported
+//! as `i2c` on F4. Master mode only.
 
 // todo: Merge this with the other i2c module?
 
-// Based on `stm32f4xx-hal`.
+// Based on `stm32f4xx-hal`, and RM0090, section 27.3: "I2C functional description".
 
 use core::ops::Deref;
 
@@ -14,70 +15,118 @@ use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
 
 use crate::{
     clocks::Clocks,
-    pac::{self, i2c1, RCC},
-    util::rcc_en_reset,
+    dma_f4::{self, DataSize},
+    pac::{i2c1, RCC},
+    util::RccPeriph,
+    MAX_ITERS,
 };
 
-use paste::paste;
+/// I2C error
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// Bus error. Note: This is not currently returned; see `check_and_clear_error_flags`.
+    Bus,
+    /// Arbitration loss
+    Arbitration,
+    /// NACK
+    Nack,
+    /// Overrun or underrun
+    Overrun,
+    /// The bus stayed busy when starting a transfer; eg a device is holding SDA low.
+    BusBusy,
+    /// The received PEC doesn't match the calculated one. SMBus mode only.
+    Pec,
+    /// SCL was held low for longer than the SMBus timeout. SMBus mode only.
+    Timeout,
+    Hardware,
+}
 
 #[derive(Clone, Copy)]
-pub enum I2cDevice {
-    One,
-    Two,
-    #[cfg(not(feature = "f410"))]
-    Three,
+/// Set a preset I2C speed. Sets the CCR and TRISE registers.
+pub enum I2cSpeed {
+    /// Standard-mode: 10kHz.
+    Standard10K,
+    /// Standard-mode: 100kHz.
+    Standard100K,
+    /// Fast-mode: 400kHz.
+    Fast400K,
 }
 
-#[derive(Debug)]
-pub enum Error {
-    OVERRUN,
-    NACK,
-    TIMEOUT,
-    // Note: The BUS error type is not currently returned, but is maintained for backwards
-    // compatibility.
-    BUS,
-    CRC,
-    ARBITRATION,
+impl I2cSpeed {
+    /// The SCL frequency, in Hz.
+    fn freq(&self) -> u32 {
+        match self {
+            Self::Standard10K => 10_000,
+            Self::Standard100K => 100_000,
+            Self::Fast400K => 400_000,
+        }
+    }
 }
 
+#[derive(Clone, Copy)]
+#[repr(u8)]
+/// Fast mode SCL duty cycle, as Tlow/Thigh. Sets the CCR register, DUTY field.
+pub enum FastModeDuty {
+    /// Tlow/Thigh = 2
+    Duty2_1 = 0,
+    /// Tlow/Thigh = 16/9. Allows reaching 400kHz with a PCLK1 that's a multiple of 10MHz.
+    Duty16_9 = 1,
+}
+
+/// Configuration data for the I2C peripheral.
+pub struct I2cConfig {
+    /// Select between one of 3 preset speeds. Defaults to Standard mode, 100kHz.
+    pub speed: I2cSpeed,
+    /// The SCL duty cycle, in Fast mode. Defaults to 2:1.
+    pub fast_mode_duty: FastModeDuty,
+}
+
+impl Default for I2cConfig {
+    fn default() -> Self {
+        Self {
+            speed: I2cSpeed::Standard100K,
+            fast_mode_duty: FastModeDuty::Duty2_1,
+        }
+    }
+}
+
+/// A DMA1 stream, for use with I2C DMA transfers. See RM0090, Table 42: "DMA1 request mapping":
+/// I2C1_RX: Stream 0 or 5, channel 1. I2C1_TX: Stream 6 or 7, channel 1. I2C2_RX: Stream 2 or 3,
+/// channel 7. I2C2_TX: Stream 7, channel 7. I2C3_RX: Stream 2, channel 3. I2C3_TX: Stream 4,
+/// channel 3.
+pub use crate::dma_f4::DmaStream;
+
 /// Represents an Inter-Integrated Circuit (I2C) peripheral.
 pub struct I2c<R> {
     pub regs: R,
+    pub cfg: I2cConfig,
 }
 
 impl<R> I2c<R>
 where
-    R: Deref<Target = i2c1::RegisterBlock>,
+    R: Deref<Target = i2c1::RegisterBlock> + RccPeriph,
 {
-    pub fn new(regs: R, device: I2cDevice, speed: u32, clocks: &Clocks) -> Self {
+    /// Initialize a I2C peripheral, including configuration register writes, and enabling and resetting
+    /// its RCC peripheral clock.
+    pub fn new(regs: R, cfg: I2cConfig, clocks: &Clocks) -> Self {
         free(|_| {
             let rcc = unsafe { &(*RCC::ptr()) };
-
-            match device {
-                I2cDevice::One => {
-                    rcc_en_reset!(apb1, i2c1, rcc);
-                }
-                I2cDevice::Two => {
-                    rcc_en_reset!(apb1, i2c2, rcc);
-                }
-                #[cfg(not(feature = "f410"))]
-                I2cDevice::Three => {
-                    rcc_en_reset!(apb1, i2c3, rcc);
-                }
-            }
+            R::en_reset(rcc);
         });
 
-        let result = Self { regs };
-        result.i2c_init(speed, clocks.apb1());
+        let result = Self { regs, cfg };
+        result.i2c_init(clocks.apb1());
         result
     }
 
-    fn i2c_init(&self, speed: u32, pclk: u32) {
+    fn i2c_init(&self, pclk: u32) {
         // Make sure the I2C unit is disabled so we can configure it
         self.regs.cr1.modify(|_, w| w.pe().clear_bit());
 
         // Calculate settings for I2C speed modes
         let clock = pclk;
+        let speed = self.cfg.speed.freq();
         let freq = clock / 1_000_000;
         assert!(freq >= 2 && freq <= 50);
 
@@ -116,24 +165,23 @@ where
                     .bits(ccr as u16)
             });
         } else {
-            const DUTYCYCLE: u8 = 0;
-            if DUTYCYCLE == 0 {
-                let ccr = clock / (speed * 3);
-                let ccr = if ccr < 1 { 1 } else { ccr };
-
-                // Set clock to fast mode with appropriate parameters for selected speed (2:1 duty cycle)
-                self.regs.ccr.write(|w| unsafe {
-                    w.f_s().set_bit().duty().clear_bit().ccr().bits(ccr as u16)
-                });
-            } else {
-                let ccr = clock / (speed * 25);
-                let ccr = if ccr < 1 { 1 } else { ccr };
+            // "Thigh = CCR * TPCLK1, Tlow = 2 * CCR * TPCLK1" if DUTY = 0, and
+            // "Thigh = 9 * CCR * TPCLK1, Tlow = 16 * CCR * TPCLK1" if DUTY = 1.
+            let ccr = match self.cfg.fast_mode_duty {
+                FastModeDuty::Duty2_1 => clock / (speed * 3),
+                FastModeDuty::Duty16_9 => clock / (speed * 25),
+            };
+            let ccr = if ccr < 1 { 1 } else { ccr };
 
-                // Set clock to fast mode with appropriate parameters for selected speed (16:9 duty cycle)
-                self.regs.ccr.write(|w| unsafe {
-                    w.f_s().set_bit().duty().set_bit().ccr().bits(ccr as u16)
-                });
-            }
+            // Set clock to fast mode with appropriate parameters for selected speed
+            self.regs.ccr.write(|w| unsafe {
+                w.f_s()
+                    .set_bit()
+                    .duty()
+                    .bit(self.cfg.fast_mode_duty as u8 != 0)
+                    .ccr()
+                    .bits(ccr as u16)
+            });
         }
 
         // Enable the I2C processing
@@ -147,27 +195,29 @@ where
 
         if sr1.timeout().bit_is_set() {
             self.regs.sr1.modify(|_, w| w.timeout().clear_bit());
-            return Err(Error::TIMEOUT);
+            return Err(Error::Timeout);
         }
 
         if sr1.pecerr().bit_is_set() {
             self.regs.sr1.modify(|_, w| w.pecerr().clear_bit());
-            return Err(Error::CRC);
+            return Err(Error::Pec);
         }
 
         if sr1.ovr().bit_is_set() {
             self.regs.sr1.modify(|_, w| w.ovr().clear_bit());
-            return Err(Error::OVERRUN);
+            return Err(Error::Overrun);
         }
 
         if sr1.af().bit_is_set() {
             self.regs.sr1.modify(|_, w| w.af().clear_bit());
-            return Err(Error::NACK);
+            // "In Master mode: a Stop or repeated Start condition must be generated by software."
+            self.regs.cr1.modify(|_, w| w.stop().set_bit());
+            return Err(Error::Nack);
         }
 
         if sr1.arlo().bit_is_set() {
             self.regs.sr1.modify(|_, w| w.arlo().clear_bit());
-            return Err(Error::ARBITRATION);
+            return Err(Error::Arbitration);
         }
 
         // The errata indicates that BERR may be incorrectly detected. It recommends ignoring and
@@ -179,114 +229,329 @@ where
         Ok(sr1)
     }
 
-    pub fn write_bytes(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
-        // Send a START condition
-        self.regs.cr1.modify(|_, w| w.start().set_bit());
+    /// Wait for a flag in the SR1 register to be set, returning early if an error is flagged.
+    fn wait_for(&self, flag: impl Fn(&i2c1::sr1::R) -> bool) -> Result<(), Error> {
+        let mut i = 0;
+        while !flag(&self.check_and_clear_error_flags()?) {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::Hardware);
+            }
+        }
+        Ok(())
+    }
 
-        // Wait until START condition was generated
-        while self.check_and_clear_error_flags()?.sb().bit_is_clear() {}
+    /// Wait for the bus to be free before starting a transfer. Returns `BusBusy` if it doesn't
+    /// become free; eg if a device is holding SDA low.
+    fn wait_bus_ready(&self) -> Result<(), Error> {
+        let mut i = 0;
+        while self.regs.sr2.read().busy().bit_is_set() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::BusBusy);
+            }
+        }
+        Ok(())
+    }
 
-        // Also wait until signalled we're master and everything is waiting for us
-        while {
-            self.check_and_clear_error_flags()?;
+    /// Send a START (or repeated START) condition, and the slave address. Waits for the address
+    /// to be acknowledged; the caller must clear ADDR.
+    fn start(&mut self, addr: u8, read: bool) -> Result<(), Error> {
+        // "Setting the START bit causes the interface to generate a Start condition and to switch
+        // to Master mode (MSL bit set) when the BUSY bit is cleared."
+        self.regs.cr1.modify(|_, w| w.start().set_bit());
 
-            let sr2 = self.regs.sr2.read();
-            sr2.msl().bit_is_clear() && sr2.busy().bit_is_clear()
-        } {}
+        // "The SB bit is cleared by software by reading the SR1 register followed by writing the
+        // DR register with the Slave address."
+        self.wait_for(|sr1| sr1.sb().bit_is_set())?;
 
-        // Set up current address, we're trying to talk to
         self.regs
             .dr
-            .write(|w| unsafe { w.bits(u32::from(addr) << 1) });
-
-        // Wait until address was sent
-        while {
-            // Check for any I2C errors. If a NACK occurs, the ADDR bit will never be set.
-            let sr1 = self.check_and_clear_error_flags()?;
+            .write(|w| unsafe { w.bits((u32::from(addr) << 1) | read as u32) });
 
-            // Wait for the address to be acknowledged
-            sr1.addr().bit_is_clear()
-        } {}
+        // If a NACK occurs, the ADDR bit will never be set; `wait_for` returns the error.
+        self.wait_for(|sr1| sr1.addr().bit_is_set())
+    }
 
-        // Clear condition by reading SR2
+    /// "ADDR is cleared by software reading SR1 register followed reading SR2."
+    fn clear_addr(&self) {
         self.regs.sr2.read();
+    }
+
+    /// Send a STOP condition, and wait for it to transmit.
+    fn stop(&self) -> Result<(), Error> {
+        self.regs.cr1.modify(|_, w| w.stop().set_bit());
+        self.wait_stop()
+    }
+
+    /// Wait for a STOP condition that has been programmed to transmit.
+    fn wait_stop(&self) -> Result<(), Error> {
+        let mut i = 0;
+        while self.regs.cr1.read().stop().bit_is_set() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::Hardware);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_dr(&self) -> u8 {
+        self.regs.dr.read().bits() as u8
+    }
 
-        // Send bytes
-        for c in bytes {
-            self.send_byte(*c)?;
+    /// Send the address and data, without a STOP condition.
+    fn write_bytes(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.start(addr, false)?;
+        self.clear_addr();
+
+        for byte in bytes {
+            self.send_byte(*byte)?;
+        }
+
+        // "EV8_2: TxE=1, BTF = 1, Program Stop request." Wait for the last byte to be sent, before
+        // a STOP or repeated START.
+        if !bytes.is_empty() {
+            self.wait_for(|sr1| sr1.btf().bit_is_set())?;
         }
 
-        // Fallthrough is success
         Ok(())
     }
 
+    /// Send the address, read data, then send a STOP condition. Uses the sequences in RM0090,
+    /// section 27.3.3: "Closing the communication". The last byte received must be NACKed, and the
+    /// STOP programmed, before it's received; the timing of this depends on the number of bytes.
+    fn read_bytes(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error> {
+        // A read of 0 bytes isn't supported by the peripheral; read one byte and discard it.
+        let mut scratch = [0];
+        let buf = if buf.is_empty() {
+            &mut scratch[..]
+        } else {
+            buf
+        };
+        let len = buf.len();
+
+        self.regs
+            .cr1
+            .modify(|_, w| w.ack().set_bit().pos().clear_bit());
+
+        self.start(addr, true)?;
+
+        match len {
+            1 => {
+                // "Case of a single byte to be received: In the ADDR event, clear the ACK bit.
+                // Clear ADDR. Program the STOP/START bit. Read the data after the RxNE flag is set."
+                self.regs.cr1.modify(|_, w| w.ack().clear_bit());
+                // Per the errata, clearing ADDR and programming STOP must not be interrupted.
+                free(|_| {
+                    self.clear_addr();
+                    self.regs.cr1.modify(|_, w| w.stop().set_bit());
+                });
+
+                self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+                buf[0] = self.read_dr();
+            }
+            2 => {
+                // "Case of two bytes to be received: Set POS and ACK. Wait for the ADDR flag to be
+                // set. Clear ADDR. Clear ACK. Wait for BTF to be set. Program STOP. Read DR twice."
+                self.regs
+                    .cr1
+                    .modify(|_, w| w.ack().clear_bit().pos().set_bit());
+                self.clear_addr();
+
+                self.wait_for(|sr1| sr1.btf().bit_is_set())?;
+                free(|_| {
+                    self.regs.cr1.modify(|_, w| w.stop().set_bit());
+                    buf[0] = self.read_dr();
+                });
+                buf[1] = self.read_dr();
+
+                self.regs.cr1.modify(|_, w| w.pos().clear_bit());
+            }
+            _ => {
+                self.clear_addr();
+
+                let (head, tail) = buf.split_at_mut(len - 3);
+                for byte in head {
+                    *byte = self.recv_byte()?;
+                }
+
+                // "When 3 bytes remain to be read: RxNE = 1 => Nothing (DataN-2 not read).
+                // DataN-1 received. BTF = 1 because both shift and data registers are full:
+                // DataN-2 in DR and DataN-1 in the shift register => SCL tied low: no other data
+                // will be received on the bus. Clear ACK bit. Read DataN-2 in DR. => This will
+                // launch the DataN reception in the shift register. DataN received (with a NACK).
+                // Program START/STOP. Read DataN-1. RxNE = 1. Read DataN."
+                self.wait_for(|sr1| sr1.btf().bit_is_set())?;
+                self.regs.cr1.modify(|_, w| w.ack().clear_bit());
+                tail[0] = self.read_dr();
+
+                self.wait_for(|sr1| sr1.btf().bit_is_set())?;
+                free(|_| {
+                    self.regs.cr1.modify(|_, w| w.stop().set_bit());
+                    tail[1] = self.read_dr();
+                });
+
+                self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
+                tail[2] = self.read_dr();
+            }
+        }
+
+        self.wait_stop()
+    }
+
+    /// Read multiple words to a buffer. Can return an error due to Bus, Arbitration, or NACK.
+    pub fn read(&mut self, addr: u8, bytes: &mut [u8]) -> Result<(), Error> {
+        self.wait_bus_ready()?;
+        self.read_bytes(addr, bytes)
+    }
+
+    /// Write an array of words. Can return an error due to Bus, Arbitration, or NACK.
+    pub fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.wait_bus_ready()?;
+        self.write_bytes(addr, bytes)?;
+        self.stop()
+    }
+
+    /// Write and read an array of words, with a repeated START between them. Can return an error
+    /// due to Bus, Arbitration, or NACK.
+    pub fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        self.wait_bus_ready()?;
+        self.write_bytes(addr, bytes)?;
+        self.read_bytes(addr, buffer)
+    }
+
+    /// Send a single byte, after the address phase.
     pub fn send_byte(&self, byte: u8) -> Result<(), Error> {
         // Wait until we're ready for sending
-        while {
-            // Check for any I2C errors. If a NACK occurs, the ADDR bit will never be set.
-            self.check_and_clear_error_flags()?.tx_e().bit_is_clear()
-        } {}
+        self.wait_for(|sr1| sr1.tx_e().bit_is_set())?;
 
         // Push out a byte of data
         self.regs.dr.write(|w| unsafe { w.bits(u32::from(byte)) });
 
-        // Wait until byte is transferred
-        while {
-            // Check for any potential error conditions.
-            self.check_and_clear_error_flags()?.btf().bit_is_clear()
-        } {}
-
         Ok(())
     }
 
+    /// Receive a single byte, after the address phase. This doesn't handle NACKing the last byte.
     pub fn recv_byte(&self) -> Result<u8, Error> {
-        while {
-            // Check for any potential error conditions.
-            self.check_and_clear_error_flags()?;
+        self.wait_for(|sr1| sr1.rx_ne().bit_is_set())?;
 
-            self.regs.sr1.read().rx_ne().bit_is_clear()
-        } {}
+        Ok(self.read_dr())
+    }
+
+    /// Write data using DMA. See RM0090, section 27.3.7: "DMA requests". The START condition and
+    /// address are sent before this returns. "In the interrupt routine after the EOT interrupt,
+    /// disable DMA requests then wait for a BTF event before programming the Stop condition":
+    /// run `stop_dma()` in the DMA stream's transfer complete interrupt.
+    pub unsafe fn write_dma(
+        &mut self,
+        addr: u8,
+        buf: &[u8],
+        stream: DmaStream,
+        channel: u8,
+    ) -> Result<(), Error> {
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+
+        self.wait_bus_ready()?;
+
+        // "DMA mode can be enabled for transmission by setting the DMAEN bit in the I2C_CR2
+        // register. Data will be loaded from a Memory area configured using the DMA peripheral
+        // to the I2C_DR register whenever the TxE bit is set."
+        dma_f4::cfg_stream(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            true,
+            stream,
+            channel,
+            DataSize::S8,
+            false,
+        );
+
+        self.regs.cr2.modify(|_, w| w.dmaen().set_bit());
+
+        self.start(addr, false)?;
+        self.clear_addr();
 
-        let value = self.regs.dr.read().bits() as u8;
-        Ok(value)
+        Ok(())
     }
-}
 
-#[cfg(feature = "embedded_hal")]
-#[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
-impl<R> WriteRead for I2c<R>
-where
-    R: Deref<Target = i2c1::RegisterBlock>,
-{
-    type Error = Error;
+    /// Read data using DMA. `buf` must be at least 2 bytes long. "In master receiver mode, when
+    /// the number of bytes to be received is equal to or greater than two, the DMA controller
+    /// sends a hardware signal, EOT_1, corresponding to the last but one data byte (number_of_bytes
+    /// – 1). If, in the I2C_CR2 register, the LAST bit is set, I2C automatically sends a NACK after
+    /// the next byte following EOT_1." Run `stop_dma()` in the DMA stream's transfer complete
+    /// interrupt.
+    pub unsafe fn read_dma(
+        &mut self,
+        addr: u8,
+        buf: &mut [u8],
+        stream: DmaStream,
+        channel: u8,
+    ) -> Result<(), Error> {
+        let (ptr, len) = (buf.as_mut_ptr(), buf.len());
+        assert!(len >= 2);
+
+        self.wait_bus_ready()?;
+
+        dma_f4::cfg_stream(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            false,
+            stream,
+            channel,
+            DataSize::S8,
+            false,
+        );
 
-    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
-        self.write_bytes(addr, bytes)?;
-        self.read(addr, buffer)?;
+        self.regs
+            .cr2
+            .modify(|_, w| w.dmaen().set_bit().last().set_bit());
+        self.regs
+            .cr1
+            .modify(|_, w| w.ack().set_bit().pos().clear_bit());
+
+        self.start(addr, true)?;
+        self.clear_addr();
 
         Ok(())
     }
+
+    /// Stop a DMA transfer, clear the stream's interrupt flags, and send a STOP condition. Run this
+    /// in the DMA stream's transfer complete interrupt.
+    pub fn stop_dma(&mut self, stream: DmaStream) -> Result<(), Error> {
+        dma_f4::stop(stream);
+
+        self.regs
+            .cr2
+            .modify(|_, w| w.dmaen().clear_bit().last().clear_bit());
+
+        // For transmission, wait for the last byte to be sent. For reception, the last byte has
+        // been NACKed due to the LAST bit.
+        if self.regs.sr2.read().tra().bit_is_set() {
+            self.wait_for(|sr1| sr1.btf().bit_is_set())?;
+        }
+
+        self.stop()
+    }
+
+    /// Print the (raw) contents of the status register.
+    pub fn read_status(&self) -> u32 {
+        self.regs.sr1.read().bits()
+    }
 }
 
 #[cfg(feature = "embedded_hal")]
 #[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
 impl<R> Write for I2c<R>
 where
-    R: Deref<Target = i2c1::RegisterBlock>,
+    R: Deref<Target = i2c1::RegisterBlock> + RccPeriph,
 {
     type Error = Error;
 
-    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.write_bytes(addr, bytes)?;
-
-        // Send a STOP condition
-        self.regs.cr1.modify(|_, w| w.stop().set_bit());
-
-        // Wait for STOP condition to transmit.
-        while self.regs.cr1.read().stop().bit_is_set() {}
-
-        // Fallthrough is success
-        Ok(())
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        I2c::write(self, addr, bytes)
     }
 }
 
@@ -294,60 +559,24 @@ where
 #[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
 impl<R> Read for I2c<R>
 where
-    R: Deref<Target = i2c1::RegisterBlock>,
+    R: Deref<Target = i2c1::RegisterBlock> + RccPeriph,
 {
     type Error = Error;
 
-    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        if let Some((last, buffer)) = buffer.split_last_mut() {
-            // Send a START condition and set ACK bit
-            self.regs
-                .cr1
-                .modify(|_, w| w.start().set_bit().ack().set_bit());
-
-            // Wait until START condition was generated
-            while self.regs.sr1.read().sb().bit_is_clear() {}
-
-            // Also wait until signalled we're master and everything is waiting for us
-            while {
-                let sr2 = self.regs.sr2.read();
-                sr2.msl().bit_is_clear() && sr2.busy().bit_is_clear()
-            } {}
-
-            // Set up current address, we're trying to talk to
-            self.regs
-                .dr
-                .write(|w| unsafe { w.bits((u32::from(addr) << 1) + 1) });
-
-            // Wait until address was sent
-            while {
-                self.check_and_clear_error_flags()?;
-                self.regs.sr1.read().addr().bit_is_clear()
-            } {}
-
-            // Clear condition by reading SR2
-            self.regs.sr2.read();
-
-            // Receive bytes into buffer
-            for c in buffer {
-                *c = self.recv_byte()?;
-            }
-
-            // Prepare to send NACK then STOP after next byte
-            self.regs
-                .cr1
-                .modify(|_, w| w.ack().clear_bit().stop().set_bit());
-
-            // Receive last byte
-            *last = self.recv_byte()?;
+    fn read(&mut self, addr: u8, bytes: &mut [u8]) -> Result<(), Error> {
+        I2c::read(self, addr, bytes)
+    }
+}
 
-            // Wait for the STOP to be sent.
-            while self.regs.cr1.read().stop().bit_is_set() {}
+#[cfg(feature = "embedded_hal")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded_hal")))]
+impl<R> WriteRead for I2c<R>
+where
+    R: Deref<Target = i2c1::RegisterBlock> + RccPeriph,
+{
+    type Error = Error;
 
-            // Fallthrough is success
-            Ok(())
-        } else {
-            Err(Error::OVERRUN)
-        }
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        I2c::write_read(self, addr, bytes, buffer)
     }
 }
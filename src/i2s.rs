@@ -0,0 +1,806 @@
+//! Inter-IC Sound (I2S) support, using the SPI peripherals in I2S mode. For digital audio input
+//! and output on parts that don't have a SAI, or on SPI peripherals not covered by one.
+//! See G4 RM, section 39.6: "SPI/I2S: I2S functional description", or F303 RM, section 30.7.
+//!
+//! This uses the same register blocks as `Spi`; only use one of the two for a given peripheral.
+//! Data is transferred in 16-bit half-words; 24 and 32-bit samples are sent as 2 half-words each,
+//! MSB first.
+//!
+//! On F4, DMA uses the DMA1 streams, through the `dma_f4` module, since the `dma` module doesn't support
+//! its DMA peripheral. Full-duplex streaming uses the I2Sx_ext peripherals, available on F3, and F4
+//! parts other than F410, F413 and F446.
+
+use core::{ops::Deref, ptr};
+
+use cfg_if::cfg_if;
+use cortex_m::interrupt::free;
+
+use crate::{
+    pac::{self, RCC},
+    util::RccPeriph,
+    MAX_ITERS,
+};
+
+cfg_if! {
+    if #[cfg(feature = "f4")] {
+        use crate::dma_f4::{self, DataSize, DmaStream};
+    } else if #[cfg(all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))))] {
+        use crate::dma::{self, ChannelCfg, DmaChannel};
+        use crate::pac::DMA as DMA1;
+    } else {
+        use crate::dma::{self, ChannelCfg, DmaChannel};
+        use crate::pac::DMA1;
+    }
+}
+
+// SPI_SR bits used in I2S mode. Not all of these are exposed by all PACs.
+const SR_RXNE: u32 = 1 << 0;
+const SR_TXE: u32 = 1 << 1;
+const SR_CHSIDE: u32 = 1 << 2;
+const SR_UDR: u32 = 1 << 3;
+const SR_OVR: u32 = 1 << 6;
+const SR_BSY: u32 = 1 << 7;
+const SR_FRE: u32 = 1 << 8;
+
+/// I2S error
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug)]
+pub enum I2sError {
+    /// Overrun occurred (receiving)
+    Overrun,
+    /// Underrun occurred (slave transmitting)
+    Underrun,
+    /// Frame error occurred (slave; the WS signal changed at an unexpected time)
+    Frame,
+    Hardware,
+    /// The sample rate can't be reached from this I2S clock.
+    SampleRate,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+/// Select master or slave, and transmit or receive. Sets SPI_I2SCFGR register, I2SCFG field.
+pub enum I2sMode {
+    /// The I2S generates the CK and WS signals, and transmits on SD.
+    MasterTransmit = 0b10,
+    /// The I2S generates the CK and WS signals, and receives on SD.
+    MasterReceive = 0b11,
+    /// CK and WS are received from an external master, and the I2S transmits on SD.
+    SlaveTransmit = 0b00,
+    /// CK and WS are received from an external master, and the I2S receives on SD.
+    SlaveReceive = 0b01,
+}
+
+impl I2sMode {
+    fn is_master(self) -> bool {
+        matches!(self, Self::MasterTransmit | Self::MasterReceive)
+    }
+
+    fn is_transmit(self) -> bool {
+        matches!(self, Self::MasterTransmit | Self::SlaveTransmit)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// The audio protocol used. Sets SPI_I2SCFGR register, I2SSTD and PCMSYNC fields.
+pub enum I2sStandard {
+    /// I2S Philips standard: Data is sent one CK cycle after the WS edge. WS is low for the left
+    /// channel.
+    Philips,
+    /// MSB-justified (left-justified) standard: Data is sent on the WS edge. WS is high for the
+    /// left channel.
+    Msb,
+    /// LSB-justified (right-justified) standard: Data is aligned to the end of the channel frame.
+    /// WS is high for the left channel. Use `DataFormat::D16C16` or `DataFormat::D32C32` with this
+    /// when transmitting in full-duplex mode.
+    Lsb,
+    /// PCM standard, with a 1-CK-cycle frame synchronization pulse.
+    PcmShort,
+    /// PCM standard, with a 13-CK-cycle frame synchronization pulse.
+    PcmLong,
+}
+
+impl I2sStandard {
+    /// The I2SSTD field value.
+    fn i2sstd(self) -> u8 {
+        match self {
+            Self::Philips => 0b00,
+            Self::Msb => 0b01,
+            Self::Lsb => 0b10,
+            Self::PcmShort | Self::PcmLong => 0b11,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// Data length, and channel length. Sets SPI_I2SCFGR register, DATLEN and CHLEN fields.
+pub enum DataFormat {
+    /// 16-bit data, in a 16-bit channel frame.
+    D16C16,
+    /// 16-bit data, extended to a 32-bit channel frame.
+    D16C32,
+    /// 24-bit data, in a 32-bit channel frame.
+    D24C32,
+    /// 32-bit data, in a 32-bit channel frame.
+    D32C32,
+}
+
+impl DataFormat {
+    /// The DATLEN field value.
+    fn datlen(self) -> u8 {
+        match self {
+            Self::D16C16 | Self::D16C32 => 0b00,
+            Self::D24C32 => 0b01,
+            Self::D32C32 => 0b10,
+        }
+    }
+
+    /// The number of bits in each channel's frame.
+    fn channel_len(self) -> u32 {
+        match self {
+            Self::D16C16 => 16,
+            _ => 32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+/// The steady state (idle) level of the clock. Sets SPI_I2SCFGR register, CKPOL field.
+pub enum ClockPolarity {
+    /// Data is changed on the falling edge of CK, and read on the rising edge.
+    IdleLow = 0,
+    /// Data is changed on the rising edge of CK, and read on the falling edge.
+    IdleHigh = 1,
+}
+
+/// Configuration data for I2S.
+pub struct I2sConfig {
+    /// Master or slave, and transmit or receive. Defaults to master transmit.
+    pub mode: I2sMode,
+    /// The audio standard used. Defaults to I2S Philips.
+    pub standard: I2sStandard,
+    /// Data and channel lengths. Defaults to 16-bit data, in a 16-bit channel frame.
+    pub data_format: DataFormat,
+    /// Clock steady state. Defaults to low.
+    pub clock_polarity: ClockPolarity,
+    /// Output the master clock (256 x the sample rate) on the MCK pin. Master mode only. Defaults
+    /// to disabled.
+    pub mclk_output: bool,
+    /// The audio sample rate, in Hz. Used to calculate the clock divider in master mode; ignored
+    /// in slave mode. Defaults to 48kHz.
+    pub sample_rate: u32,
+}
+
+impl Default for I2sConfig {
+    fn default() -> Self {
+        Self {
+            mode: I2sMode::MasterTransmit,
+            standard: I2sStandard::Philips,
+            data_format: DataFormat::D16C16,
+            clock_polarity: ClockPolarity::IdleLow,
+            mclk_output: false,
+            sample_rate: 48_000,
+        }
+    }
+}
+
+/// Calculate the I2S clock prescaler for a given sample rate. Returns the (I2SDIV, ODD) fields,
+/// or `None` if the sample rate can't be reached from this I2S clock. `i2s_clock` is the I2S kernel
+/// clock speed, in Hz. See G4 RM, section 39.6.8: "Clock generator", or F303 RM, section 30.7.4.
+///
+/// When the master clock is generated (MCKOE = 1):
+/// Fs = I2SxCLK / [256 * ((2 * I2SDIV) + ODD)]
+///
+/// When the master clock is disabled (MCKOE = 0):
+/// Fs = I2SxCLK / [(16 * 2) * ((2 * I2SDIV) + ODD)] when the channel frame is 16-bit wide, and
+/// Fs = I2SxCLK / [(32 * 2) * ((2 * I2SDIV) + ODD)] when the channel frame is 32-bit wide.
+pub fn calc_prescaler(
+    i2s_clock: u32,
+    sample_rate: u32,
+    data_format: DataFormat,
+    mclk_output: bool,
+) -> Option<(u8, bool)> {
+    let frame_len = if mclk_output {
+        256
+    } else {
+        data_format.channel_len() * 2
+    };
+
+    // Round to the nearest divider value.
+    let bit_rate = sample_rate as u64 * frame_len as u64;
+    if bit_rate == 0 {
+        return None;
+    }
+    let div = (i2s_clock as u64 + bit_rate / 2) / bit_rate;
+
+    // "I2SDIV: It is not allowed to configure the I2SDIV bitfield to 0 or 1."
+    if div < 4 || div > 0xff * 2 + 1 {
+        return None;
+    }
+
+    Some(((div / 2) as u8, div % 2 != 0))
+}
+
+/// Calculate the actual sample rate produced by a given prescaler, in Hz. Use this to check the
+/// error compared to the requested rate, after `calc_prescaler()`.
+pub fn actual_sample_rate(
+    i2s_clock: u32,
+    i2sdiv: u8,
+    odd: bool,
+    data_format: DataFormat,
+    mclk_output: bool,
+) -> u32 {
+    let frame_len = if mclk_output {
+        256
+    } else {
+        data_format.channel_len() * 2
+    };
+
+    let div = 2 * i2sdiv as u32 + odd as u32;
+    i2s_clock / (frame_len * div)
+}
+
+/// Represents an SPI peripheral, configured in I2S mode.
+pub struct I2s<R> {
+    pub regs: R,
+    pub cfg: I2sConfig,
+}
+
+impl<R> I2s<R>
+where
+    R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+{
+    /// Initialize an SPI peripheral in I2S mode, including configuration register writes, and enabling
+    /// and resetting its RCC peripheral clock. `i2s_clock` is the I2S kernel clock speed, in Hz; it's used
+    /// to set the sample rate in master mode. Doesn't enable the peripheral; use `enable()` for that.
+    /// Returns `I2sError::SampleRate` if the sample rate can't be reached from this I2S clock.
+    pub fn new(regs: R, cfg: I2sConfig, i2s_clock: u32) -> Result<Self, I2sError> {
+        free(|_| {
+            let rcc = unsafe { &(*RCC::ptr()) };
+            R::en_reset(rcc);
+        });
+
+        // G4 RM, section 39.6.9: "I2S master mode"
+        // The following procedure is used to configure the I2S:
+        // 1. Select the I2SDIV[7:0] bits in the SPIx_I2SPR register to define the serial audio clock
+        // bit rate to reach the proper audio sample frequency. The ODD bit in the
+        // SPIx_I2SPR register also has to be defined.
+        // 2. Select the CKPOL bit to define the steady level for the communication clock. Set the
+        // MCKOE bit in the SPIx_I2SPR register if the master clock MCK needs to be provided
+        // to the external DAC/ADC audio component.
+        if cfg.mode.is_master() {
+            let (i2sdiv, odd) =
+                calc_prescaler(i2s_clock, cfg.sample_rate, cfg.data_format, cfg.mclk_output)
+                    .ok_or(I2sError::SampleRate)?;
+
+            regs.i2spr.write(|w| unsafe {
+                w.i2sdiv().bits(i2sdiv);
+                w.odd().bit(odd);
+                w.mckoe().bit(cfg.mclk_output)
+            });
+        }
+
+        // 3. Set the I2SMOD bit in the SPIx_I2SCFGR register to activate the I2S functions and
+        // define the I2S standard through the I2SSTD[1:0] and PCMSYNC bits, the data length
+        // through the DATLEN[1:0] bits and the number of bits per channel by configuring the
+        // CHLEN bit. Select also the I2S master mode and direction (Transmitter or Receiver)
+        // through the I2SCFG[1:0] bits in the SPIx_I2SCFGR register.
+        regs.i2scfgr.write(|w| unsafe {
+            w.i2smod().set_bit();
+            w.i2scfg().bits(cfg.mode as u8);
+            w.i2sstd().bits(cfg.standard.i2sstd());
+            w.pcmsync().bit(cfg.standard == I2sStandard::PcmLong);
+            w.datlen().bits(cfg.data_format.datlen());
+            w.chlen().bit(cfg.data_format != DataFormat::D16C16);
+            w.ckpol().bit(cfg.clock_polarity as u8 != 0)
+        });
+
+        Ok(Self { regs, cfg })
+    }
+
+    /// Enable the peripheral. In master mode, this starts the clock, and the first frame.
+    /// "4. If needed, select all the potential interrupt sources and the DMA capabilities by
+    /// writing the SPIx_CR2 register. 5. The I2SE bit in SPIx_I2SCFGR register must be set."
+    pub fn enable(&mut self) {
+        self.regs.i2scfgr.modify(|_, w| w.i2se().set_bit());
+    }
+
+    /// Disable the peripheral. G4 RM, section 39.6.9: "To switch off the I2S, by clearing I2SE, it
+    /// is mandatory to wait for TXE = 1 and BSY = 0." In receive mode, the RM specifies waiting
+    /// for a specific point in the last frame; call this from the RXNE interrupt, or after the
+    /// DMA transfer-complete interrupt, to meet that.
+    pub fn disable(&mut self) -> Result<(), I2sError> {
+        if self.cfg.mode.is_transmit() {
+            let mut i = 0;
+            while self.regs.sr.read().bits() & SR_TXE == 0 {
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(I2sError::Hardware);
+                }
+            }
+            i = 0;
+            while self.regs.sr.read().bits() & SR_BSY != 0 {
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(I2sError::Hardware);
+                }
+            }
+        }
+
+        self.regs.i2scfgr.modify(|_, w| w.i2se().clear_bit());
+        Ok(())
+    }
+
+    /// Change the sample rate. Master mode only. Disables the peripheral while changing it; re-enable
+    /// with `enable()` afterwards. Returns `I2sError::SampleRate`, without disabling the peripheral, if
+    /// the sample rate can't be reached from this I2S clock.
+    pub fn set_sample_rate(&mut self, sample_rate: u32, i2s_clock: u32) -> Result<(), I2sError> {
+        let (i2sdiv, odd) = calc_prescaler(
+            i2s_clock,
+            sample_rate,
+            self.cfg.data_format,
+            self.cfg.mclk_output,
+        )
+        .ok_or(I2sError::SampleRate)?;
+
+        self.disable()?;
+
+        self.regs.i2spr.modify(|_, w| unsafe {
+            w.i2sdiv().bits(i2sdiv);
+            w.odd().bit(odd)
+        });
+
+        self.cfg.sample_rate = sample_rate;
+        Ok(())
+    }
+
+    /// Check the status register for errors, and clear them. "The OVR flag is cleared by a read
+    /// operation on the SPIx_DR register followed by a read access to the SPIx_SR register."
+    /// UDR and FRE are cleared by reading SPIx_SR.
+    fn check_errors(&mut self, sr: u32) -> Result<(), I2sError> {
+        if sr & SR_OVR != 0 {
+            unsafe { ptr::read_volatile(&self.regs.dr as *const _ as *const u16) };
+            let _ = self.regs.sr.read();
+            Err(I2sError::Overrun)
+        } else if sr & SR_UDR != 0 {
+            Err(I2sError::Underrun)
+        } else if sr & SR_FRE != 0 {
+            Err(I2sError::Frame)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write half-words, blocking until each is sent. For stereo, alternate left and right channel
+    /// data; for 24 and 32-bit formats, each sample is 2 half-words, MSB first.
+    pub fn write(&mut self, words: &[u16]) -> Result<(), I2sError> {
+        for word in words {
+            let mut i = 0;
+            loop {
+                let sr = self.regs.sr.read().bits();
+                self.check_errors(sr)?;
+                if sr & SR_TXE != 0 {
+                    break;
+                }
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(I2sError::Hardware);
+                }
+            }
+
+            unsafe { ptr::write_volatile(&self.regs.dr as *const _ as *mut u16, *word) };
+        }
+
+        Ok(())
+    }
+
+    /// Read half-words, blocking until the buffer is filled. In the order they're received;
+    /// see `write()`.
+    pub fn read(&mut self, buf: &mut [u16]) -> Result<(), I2sError> {
+        for word in buf {
+            let mut i = 0;
+            loop {
+                let sr = self.regs.sr.read().bits();
+                self.check_errors(sr)?;
+                if sr & SR_RXNE != 0 {
+                    break;
+                }
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(I2sError::Hardware);
+                }
+            }
+
+            *word = unsafe { ptr::read_volatile(&self.regs.dr as *const _ as *const u16) };
+        }
+
+        Ok(())
+    }
+
+    /// The channel the data currently in the data register belongs to; `false` for left, and `true`
+    /// for right. Meaningless in PCM mode. (SPI_SR register, CHSIDE field)
+    pub fn channel_side(&self) -> bool {
+        self.regs.sr.read().bits() & SR_CHSIDE != 0
+    }
+
+    /// Transmit audio data using DMA. Set `channel_cfg.circular` to stream continuously from a
+    /// buffer; eg use the half and full transfer-complete interrupts to refill each half of it.
+    /// Note that the `channel` argument is unused on F3, since it is hard-coded, and can't be
+    /// configured using the DMAMUX peripheral. (`dma::mux()` fn). It's only used there if this
+    /// peripheral isn't connected to `dma_periph`. Enables the peripheral.
+    #[cfg(not(feature = "f4"))]
+    pub unsafe fn write_dma(
+        &mut self,
+        buf: &[u16],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+
+        #[cfg(feature = "f3")]
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
+
+        cfg_dma(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            channel,
+            channel_cfg,
+            dma::Direction::ReadFromMem,
+            dma_periph,
+        );
+
+        self.regs.cr2.modify(|_, w| w.txdmaen().set_bit());
+        self.enable();
+    }
+
+    /// Receive audio data using DMA. See `write_dma()`. Enables the peripheral.
+    #[cfg(not(feature = "f4"))]
+    pub unsafe fn read_dma(
+        &mut self,
+        buf: &mut [u16],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        let (ptr, len) = (buf.as_mut_ptr(), buf.len());
+
+        #[cfg(feature = "f3")]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+
+        cfg_dma(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            channel,
+            channel_cfg,
+            dma::Direction::ReadFromPeriph,
+            dma_periph,
+        );
+
+        self.regs.cr2.modify(|_, w| w.rxdmaen().set_bit());
+        self.enable();
+    }
+
+    /// Transmit audio data using a DMA1 stream. Set `circular` to stream continuously from a buffer;
+    /// this enables the half transfer interrupt as well as the transfer complete one, eg to refill each
+    /// half of it. See RM0090, Table 42: "DMA1 request mapping": SPI2_TX: Stream 4, channel 0.
+    /// SPI3_TX: Stream 5 or 7, channel 0. Enables the peripheral.
+    #[cfg(feature = "f4")]
+    pub unsafe fn write_dma(
+        &mut self,
+        buf: &[u16],
+        stream: DmaStream,
+        channel: u8,
+        circular: bool,
+    ) {
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+
+        dma_f4::cfg_stream(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            true,
+            stream,
+            channel,
+            DataSize::S16,
+            circular,
+        );
+
+        self.regs.cr2.modify(|_, w| w.txdmaen().set_bit());
+        self.enable();
+    }
+
+    /// Receive audio data using a DMA1 stream. See `write_dma()`. SPI2_RX: Stream 3, channel 0.
+    /// SPI3_RX: Stream 0 or 2, channel 0. Enables the peripheral.
+    #[cfg(feature = "f4")]
+    pub unsafe fn read_dma(
+        &mut self,
+        buf: &mut [u16],
+        stream: DmaStream,
+        channel: u8,
+        circular: bool,
+    ) {
+        let (ptr, len) = (buf.as_mut_ptr(), buf.len());
+
+        dma_f4::cfg_stream(
+            &self.regs.dr as *const _ as u32,
+            ptr as u32,
+            len as u16,
+            false,
+            stream,
+            channel,
+            DataSize::S16,
+            circular,
+        );
+
+        self.regs.cr2.modify(|_, w| w.rxdmaen().set_bit());
+        self.enable();
+    }
+
+    /// Configure the I2Sx_ext peripheral for full-duplex mode. "The I2Sx_ext is configured as the same
+    /// standard, data format and clock polarity as the I2Sx, in the opposite direction."
+    #[cfg(any(feature = "f3", feature = "f4"))]
+    fn cfg_ext<E>(&self, ext: &E)
+    where
+        E: Deref<Target = pac::spi1::RegisterBlock>,
+    {
+        let ext_mode = if self.cfg.mode.is_transmit() {
+            I2sMode::SlaveReceive
+        } else {
+            I2sMode::SlaveTransmit
+        };
+
+        ext.i2scfgr.write(|w| unsafe {
+            w.i2smod().set_bit();
+            w.i2scfg().bits(ext_mode as u8);
+            w.i2sstd().bits(self.cfg.standard.i2sstd());
+            w.pcmsync().bit(self.cfg.standard == I2sStandard::PcmLong);
+            w.datlen().bits(self.cfg.data_format.datlen());
+            w.chlen().bit(self.cfg.data_format != DataFormat::D16C16);
+            w.ckpol().bit(self.cfg.clock_polarity as u8 != 0)
+        });
+    }
+
+    /// Enable DMA requests on this peripheral and its I2Sx_ext, then enable both. Used for full-duplex
+    /// mode, after configuring the DMA.
+    #[cfg(any(feature = "f3", feature = "f4"))]
+    fn start_duplex<E>(&mut self, ext: &E)
+    where
+        E: Deref<Target = pac::spi1::RegisterBlock>,
+    {
+        if self.cfg.mode.is_transmit() {
+            self.regs.cr2.modify(|_, w| w.txdmaen().set_bit());
+            ext.cr2.modify(|_, w| w.rxdmaen().set_bit());
+        } else {
+            self.regs.cr2.modify(|_, w| w.rxdmaen().set_bit());
+            ext.cr2.modify(|_, w| w.txdmaen().set_bit());
+        }
+
+        // The slave extension must be enabled before the master starts the clock.
+        ext.i2scfgr.modify(|_, w| w.i2se().set_bit());
+        self.enable();
+    }
+
+    /// Stream audio in both directions at once using DMA, with the I2Sx_ext peripheral (`ext`) handling
+    /// the opposite direction of this one. F3 RM, section 30.7.3: "Full duplex": "The I2Sx_ext is
+    /// used only in full-duplex mode. The I2Sx_ext always works in slave mode." The extension shares
+    /// the CK and WS signals of this peripheral.
+    ///
+    /// `channel_ext` is the DMA channel of the extension peripheral; it's not hard-coded here, so make
+    /// sure to use the one listed in the RM's DMA request table. This peripheral's channel is hard-coded;
+    /// `channel` is only used if it isn't connected to `dma_periph`.
+    /// Enables both peripherals. F3 only among the families using the `dma` module; the others don't
+    /// have I2Sx_ext.
+    #[cfg(feature = "f3")]
+    pub unsafe fn transfer_dma<E>(
+        &mut self,
+        ext: &E,
+        buf_write: &[u16],
+        buf_read: &mut [u16],
+        channel: DmaChannel,
+        channel_ext: DmaChannel,
+        channel_cfg_write: ChannelCfg,
+        channel_cfg_read: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) where
+        E: Deref<Target = pac::spi1::RegisterBlock>,
+    {
+        self.cfg_ext(ext);
+
+        let (periph_write, periph_read, channel_write, channel_read) =
+            if self.cfg.mode.is_transmit() {
+                (
+                    &self.regs.dr as *const _ as u32,
+                    &ext.dr as *const _ as u32,
+                    R::write_chan(dma_periph).unwrap_or(channel),
+                    channel_ext,
+                )
+            } else {
+                (
+                    &ext.dr as *const _ as u32,
+                    &self.regs.dr as *const _ as u32,
+                    channel_ext,
+                    R::read_chan(dma_periph).unwrap_or(channel),
+                )
+            };
+
+        cfg_dma(
+            periph_read,
+            buf_read.as_mut_ptr() as u32,
+            buf_read.len() as u16,
+            channel_read,
+            channel_cfg_read,
+            dma::Direction::ReadFromPeriph,
+            dma_periph,
+        );
+
+        cfg_dma(
+            periph_write,
+            buf_write.as_ptr() as u32,
+            buf_write.len() as u16,
+            channel_write,
+            channel_cfg_write,
+            dma::Direction::ReadFromMem,
+            dma_periph,
+        );
+
+        self.start_duplex(ext);
+    }
+
+    /// Stream audio in both directions at once using DMA1 streams, with the I2Sx_ext peripheral (`ext`)
+    /// handling the opposite direction of this one. See the F3 variant of this function for details.
+    /// `stream_write` and `channel_write` are for the peripheral transmitting; `stream_read` and
+    /// `channel_read` for the one receiving. See RM0090, Table 42: "DMA1 request mapping":
+    /// I2S2_EXT_RX: Stream 3, channel 3. I2S2_EXT_TX: Stream 4, channel 2. I2S3_EXT_RX: Stream 2 or
+    /// 0, channel 2 or 3. I2S3_EXT_TX: Stream 5, channel 2. For SPI2 and SPI3, see `write_dma()` and
+    /// `read_dma()`. `circular` applies to both streams. Enables both peripherals.
+    #[cfg(feature = "f4")]
+    pub unsafe fn transfer_dma<E>(
+        &mut self,
+        ext: &E,
+        buf_write: &[u16],
+        buf_read: &mut [u16],
+        stream_write: DmaStream,
+        channel_write: u8,
+        stream_read: DmaStream,
+        channel_read: u8,
+        circular: bool,
+    ) where
+        E: Deref<Target = pac::spi1::RegisterBlock>,
+    {
+        self.cfg_ext(ext);
+
+        let (periph_write, periph_read) = if self.cfg.mode.is_transmit() {
+            (&self.regs.dr as *const _ as u32, &ext.dr as *const _ as u32)
+        } else {
+            (&ext.dr as *const _ as u32, &self.regs.dr as *const _ as u32)
+        };
+
+        dma_f4::cfg_stream(
+            periph_read,
+            buf_read.as_mut_ptr() as u32,
+            buf_read.len() as u16,
+            false,
+            stream_read,
+            channel_read,
+            DataSize::S16,
+            circular,
+        );
+
+        dma_f4::cfg_stream(
+            periph_write,
+            buf_write.as_ptr() as u32,
+            buf_write.len() as u16,
+            true,
+            stream_write,
+            channel_write,
+            DataSize::S16,
+            circular,
+        );
+
+        self.start_duplex(ext);
+    }
+
+    /// Stop a DMA stream. `channel2` is an optional second channel to stop; eg if streaming
+    /// in both directions. Disables the peripheral.
+    #[cfg(not(feature = "f4"))]
+    pub fn stop_dma(
+        &mut self,
+        channel: DmaChannel,
+        channel2: Option<DmaChannel>,
+        dma_periph: dma::DmaPeriph,
+    ) -> Result<(), I2sError> {
+        dma::stop(dma_periph, channel);
+        if let Some(ch2) = channel2 {
+            dma::stop(dma_periph, ch2);
+        };
+
+        self.disable()?;
+
+        self.regs.cr2.modify(|_, w| {
+            w.txdmaen().clear_bit();
+            w.rxdmaen().clear_bit()
+        });
+
+        Ok(())
+    }
+
+    /// Stop a DMA1 stream, and clear its interrupt flags. `stream2` is an optional second stream to
+    /// stop; eg if streaming in both directions. Disables the peripheral.
+    #[cfg(feature = "f4")]
+    pub fn stop_dma(
+        &mut self,
+        stream: DmaStream,
+        stream2: Option<DmaStream>,
+    ) -> Result<(), I2sError> {
+        dma_f4::stop(stream);
+        if let Some(st2) = stream2 {
+            dma_f4::stop(st2);
+        };
+
+        self.disable()?;
+
+        self.regs.cr2.modify(|_, w| {
+            w.txdmaen().clear_bit();
+            w.rxdmaen().clear_bit()
+        });
+
+        Ok(())
+    }
+
+    /// Read the status register.
+    pub fn read_status(&self) -> u32 {
+        self.regs.sr.read().bits()
+    }
+}
+
+/// Configure a DMA channel for I2S half-word transfers.
+#[cfg(not(feature = "f4"))]
+unsafe fn cfg_dma(
+    periph_addr: u32,
+    mem_addr: u32,
+    num_data: u16,
+    channel: DmaChannel,
+    channel_cfg: ChannelCfg,
+    direction: dma::Direction,
+    dma_periph: dma::DmaPeriph,
+) {
+    match dma_periph {
+        dma::DmaPeriph::Dma1 => {
+            let mut regs = unsafe { &(*DMA1::ptr()) };
+            dma::cfg_channel(
+                &mut regs,
+                channel,
+                periph_addr,
+                mem_addr,
+                num_data,
+                direction,
+                dma::DataSize::S16,
+                dma::DataSize::S16,
+                channel_cfg,
+            );
+        }
+        #[cfg(not(any(
+            feature = "f3x4",
+            all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+            feature = "wb"
+        )))]
+        dma::DmaPeriph::Dma2 => {
+            let mut regs = unsafe { &(*pac::DMA2::ptr()) };
+            dma::cfg_channel(
+                &mut regs,
+                channel,
+                periph_addr,
+                mem_addr,
+                num_data,
+                direction,
+                dma::DataSize::S16,
+                dma::DataSize::S16,
+                channel_cfg,
+            );
+        }
+    }
+}
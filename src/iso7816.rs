@@ -0,0 +1,572 @@
+//! Answer-To-Reset (ATR) parsing, and the T=0 command exchange, for smartcards and secure elements
+//! using the ISO 7816-3 protocol. For use with the USART in Smartcard mode. (See `Usart::enable_smartcard()`)
+//!
+//! This module is independent of hardware; the exchange is performed over any type implementing
+//! `Transport`, and can be used on its own.
+//!
+//! See ISO/IEC 7816-3, section 8: "Answer-to-Reset", and section 10: "Character-level transmission protocol"
+
+/// The maximum length of an ATR, including TS.
+pub const MAX_ATR_LEN: usize = 33;
+
+/// The maximum number of historical bytes in an ATR.
+pub const MAX_HISTORICAL_LEN: usize = 15;
+
+/// The procedure byte a card sends to request more time. (NULL)
+const NULL: u8 = 0x60;
+
+/// The instruction of the GET RESPONSE command, used to retrieve response data in T=0.
+const INS_GET_RESPONSE: u8 = 0xc0;
+
+/// The maximum number of times a command is re-sent due to 6Cxx, or followed by GET RESPONSE due
+/// to 61xx, before giving up on a card that keeps requesting them.
+const MAX_RETRIES: usize = 16;
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// ATR and T=0 protocol errors.
+pub enum Error {
+    /// The initial character (TS) wasn't 0x3B or 0x3F.
+    InitialChar,
+    /// The ATR's check character (TCK) doesn't match its contents.
+    Checksum,
+    /// The ATR was longer than 33 bytes, a command had more than 255 data bytes, or a buffer
+    /// passed wasn't large enough.
+    Length,
+    /// The card sent an invalid procedure byte, or kept requesting that a command be re-sent.
+    Procedure,
+}
+
+/// The operations the T=0 exchange requires from the hardware. `Usart` implements this when
+/// in Smartcard mode.
+pub trait Transport {
+    type Error: From<Error>;
+
+    /// Send bytes to the card.
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receive bytes from the card, filling `buf`.
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The encoding of characters, as indicated by the ATR's initial character. (TS)
+pub enum Convention {
+    /// Logic high is a high level on the line, and the least significant bit is sent first. (0x3B)
+    Direct,
+    /// Logic high is a low level on the line, and the most significant bit is sent first. (0x3F)
+    Inverse,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// A parsed Answer-To-Reset.
+pub struct Atr {
+    pub convention: Convention,
+    /// TA1: Clock rate conversion integer (FI), and baud rate adjustment integer (DI).
+    pub ta1: Option<u8>,
+    /// TC1: Extra guard time integer. (N)
+    pub tc1: Option<u8>,
+    /// TA2: Present if the card is in specific mode.
+    pub ta2: Option<u8>,
+    /// Bit n is set if protocol T=n is indicated. T=0 is implied if no protocol is indicated.
+    pub protocols: u16,
+    historical: [u8; MAX_HISTORICAL_LEN],
+    historical_len: usize,
+}
+
+impl Atr {
+    /// Parse an ATR from a byte source; eg bytes received from the card after reset. Bytes are
+    /// read only as the ATR's structure requires, since its length isn't known in advance.
+    pub fn read<E: From<Error>>(mut next: impl FnMut() -> Result<u8, E>) -> Result<Self, E> {
+        let convention = match next()? {
+            0x3b => Convention::Direct,
+            0x3f => Convention::Inverse,
+            _ => return Err(Error::InitialChar.into()),
+        };
+
+        let mut result = Self {
+            convention,
+            ta1: None,
+            tc1: None,
+            ta2: None,
+            protocols: 0,
+            historical: [0; MAX_HISTORICAL_LEN],
+            historical_len: 0,
+        };
+
+        // TS isn't included in the check.
+        let mut check = 0;
+        let mut len = 1;
+        let mut read = |check: &mut u8, len: &mut usize| -> Result<u8, E> {
+            *len += 1;
+            if *len > MAX_ATR_LEN {
+                return Err(Error::Length.into());
+            }
+
+            let byte = next()?;
+            *check ^= byte;
+            Ok(byte)
+        };
+
+        // T0 contains Y1 in its upper nibble, and the number of historical bytes in its lower one.
+        let t0 = read(&mut check, &mut len)?;
+        result.historical_len = (t0 & 0xf) as usize;
+
+        let mut y = t0 >> 4;
+        let mut i = 1;
+        // TCK is present if any protocol other than T=0 is indicated.
+        let mut tck_present = false;
+
+        loop {
+            // Y indicates which of TAi, TBi, TCi, and TDi are present, in that order.
+            if y & 0b0001 != 0 {
+                let ta = read(&mut check, &mut len)?;
+                match i {
+                    1 => result.ta1 = Some(ta),
+                    2 => result.ta2 = Some(ta),
+                    _ => (),
+                }
+            }
+            if y & 0b0010 != 0 {
+                // TBi is deprecated; its value isn't used.
+                read(&mut check, &mut len)?;
+            }
+            if y & 0b0100 != 0 {
+                let tc = read(&mut check, &mut len)?;
+                if i == 1 {
+                    result.tc1 = Some(tc);
+                }
+            }
+            if y & 0b1000 == 0 {
+                break;
+            }
+
+            let td = read(&mut check, &mut len)?;
+            let protocol = td & 0xf;
+            result.protocols |= 1 << protocol;
+            if protocol != 0 {
+                tck_present = true;
+            }
+
+            y = td >> 4;
+            i += 1;
+        }
+
+        if result.protocols == 0 {
+            result.protocols = 1;
+        }
+
+        for i in 0..result.historical_len {
+            result.historical[i] = read(&mut check, &mut len)?;
+        }
+
+        if tck_present {
+            // The XOR of all bytes from T0 to TCK, inclusive, is 0.
+            read(&mut check, &mut len)?;
+            if check != 0 {
+                return Err(Error::Checksum.into());
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Parse an ATR from a buffer containing it.
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let mut bytes = data.iter();
+        Self::read(|| bytes.next().copied().ok_or(Error::Length))
+    }
+
+    /// The historical bytes; these identify the card, and aren't interpreted here.
+    pub fn historical(&self) -> &[u8] {
+        &self.historical[..self.historical_len]
+    }
+
+    /// If the card supports protocol T=`protocol`.
+    pub fn supports_protocol(&self, protocol: u8) -> bool {
+        protocol < 16 && self.protocols & (1 << protocol) != 0
+    }
+
+    /// The clock rate conversion factor, F. Defaults to 372 if TA1 is absent. `None` if reserved.
+    pub fn fi(&self) -> Option<u16> {
+        let fi = match self.ta1 {
+            Some(ta1) => ta1 >> 4,
+            None => return Some(372),
+        };
+
+        match fi {
+            0 | 1 => Some(372),
+            2 => Some(558),
+            3 => Some(744),
+            4 => Some(1_116),
+            5 => Some(1_488),
+            6 => Some(1_860),
+            9 => Some(512),
+            10 => Some(768),
+            11 => Some(1_024),
+            12 => Some(1_536),
+            13 => Some(2_048),
+            _ => None,
+        }
+    }
+
+    /// The baud rate adjustment factor, D. Defaults to 1 if TA1 is absent. `None` if reserved.
+    pub fn di(&self) -> Option<u8> {
+        let di = match self.ta1 {
+            Some(ta1) => ta1 & 0xf,
+            None => return Some(1),
+        };
+
+        match di {
+            1 => Some(1),
+            2 => Some(2),
+            3 => Some(4),
+            4 => Some(8),
+            5 => Some(16),
+            6 => Some(32),
+            7 => Some(64),
+            8 => Some(12),
+            9 => Some(20),
+            _ => None,
+        }
+    }
+
+    /// The extra guard time, in ETUs, to add after each character sent to the card. Defaults to 0.
+    /// (A value of 255 indicates the minimum guard time; this returns 0 in that case)
+    pub fn extra_guard_time(&self) -> u8 {
+        match self.tc1 {
+            Some(255) | None => 0,
+            Some(n) => n,
+        }
+    }
+}
+
+/// The baud rate to communicate with a card at, given its clock frequency, and its F and D factors.
+/// Before any baud rate negotiation, F is 372, and D is 1.
+pub fn baud(card_clock: u32, fi: u16, di: u8) -> u32 {
+    (card_clock as u64 * di as u64 / fi as u64) as u32
+}
+
+#[derive(Clone, Copy)]
+/// A command APDU: The header, and optional command data and expected response length.
+pub struct Command<'a> {
+    pub cla: u8,
+    pub ins: u8,
+    pub p1: u8,
+    pub p2: u8,
+    /// Command data. Up to 255 bytes.
+    pub data: &'a [u8],
+    /// The maximum number of response bytes expected. 0 means 256.
+    pub le: Option<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// The result of a command: The number of response data bytes written to the buffer, and the status word.
+pub struct Response {
+    pub len: usize,
+    pub sw1: u8,
+    pub sw2: u8,
+}
+
+impl Response {
+    /// The status word, as a single value; eg 0x9000 on success.
+    pub fn sw(&self) -> u16 {
+        u16::from_be_bytes([self.sw1, self.sw2])
+    }
+}
+
+/// Exchange a command with a card using the T=0 protocol, and write response data into `resp`. This
+/// handles procedure bytes, wrong-length (6Cxx) retries, and retrieving response data with
+/// GET RESPONSE (61xx). Returns `Error::Procedure` if the card requests more than 16 of these in a
+/// row. See ISO/IEC 7816-3, section 10.3: "Character protocol T=0".
+pub fn transmit_t0<T: Transport>(
+    transport: &mut T,
+    cmd: &Command,
+    resp: &mut [u8],
+) -> Result<Response, T::Error> {
+    if cmd.data.len() > 255 {
+        return Err(Error::Length.into());
+    }
+
+    // P3 is the command data length, or the expected response length.
+    let p3 = if !cmd.data.is_empty() {
+        cmd.data.len() as u8
+    } else {
+        cmd.le.unwrap_or(0)
+    };
+
+    let mut header = [cmd.cla, cmd.ins, cmd.p1, cmd.p2, p3];
+    let mut data = cmd.data;
+    let mut resp_len = 0;
+    let mut retries = 0;
+
+    loop {
+        let (sw1, sw2) = exchange_t0(transport, &header, data, resp, &mut resp_len)?;
+
+        match sw1 {
+            // Wrong length: Re-send the command, with P3 set to the length the card indicates.
+            0x6c if data.is_empty() => header[4] = sw2,
+            // Response bytes are available; retrieve them, if the command expects a response.
+            0x61 if cmd.le.is_some() => {
+                header = [cmd.cla, INS_GET_RESPONSE, 0, 0, sw2];
+                data = &[];
+            }
+            _ => {
+                return Ok(Response {
+                    len: resp_len,
+                    sw1,
+                    sw2,
+                })
+            }
+        }
+
+        retries += 1;
+        if retries > MAX_RETRIES {
+            return Err(Error::Procedure.into());
+        }
+    }
+}
+
+/// Send a single T=0 command header, and transfer its data as the card's procedure bytes request.
+/// Returns the status bytes. Received data is appended to `resp`, at `resp_len`.
+fn exchange_t0<T: Transport>(
+    transport: &mut T,
+    header: &[u8; 5],
+    data: &[u8],
+    resp: &mut [u8],
+    resp_len: &mut usize,
+) -> Result<(u8, u8), T::Error> {
+    let ins = header[1];
+    // The number of bytes to transfer in the data phase. If sending no data, P3 = 0 means 256.
+    let mut remaining = if !data.is_empty() {
+        data.len()
+    } else if header[4] == 0 {
+        256
+    } else {
+        header[4] as usize
+    };
+    let mut sent = 0;
+
+    transport.write(header)?;
+
+    loop {
+        let mut pb = [0];
+        transport.read(&mut pb)?;
+        let pb = pb[0];
+
+        if pb == NULL {
+            continue;
+        }
+
+        // The card sends INS to request all remaining bytes, or its complement to request one.
+        let count = if pb == ins {
+            remaining
+        } else if pb == !ins {
+            remaining.min(1)
+        } else if pb & 0xf0 == 0x60 || pb & 0xf0 == 0x90 {
+            let mut sw2 = [0];
+            transport.read(&mut sw2)?;
+            return Ok((pb, sw2[0]));
+        } else {
+            return Err(Error::Procedure.into());
+        };
+
+        if count == 0 {
+            return Err(Error::Procedure.into());
+        }
+
+        if !data.is_empty() {
+            transport.write(&data[sent..sent + count])?;
+            sent += count;
+        } else {
+            if *resp_len + count > resp.len() {
+                return Err(Error::Length.into());
+            }
+            transport.read(&mut resp[*resp_len..*resp_len + count])?;
+            *resp_len += count;
+        }
+
+        remaining -= count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A card that replies with the bytes in `rx`, in order, and records the bytes sent to it.
+    struct MockCard<'a> {
+        rx: &'a [u8],
+        rx_pos: usize,
+        tx: [u8; 128],
+        tx_len: usize,
+    }
+
+    impl<'a> MockCard<'a> {
+        fn new(rx: &'a [u8]) -> Self {
+            Self {
+                rx,
+                rx_pos: 0,
+                tx: [0; 128],
+                tx_len: 0,
+            }
+        }
+
+        fn sent(&self) -> &[u8] {
+            &self.tx[..self.tx_len]
+        }
+    }
+
+    impl Transport for MockCard<'_> {
+        type Error = Error;
+
+        fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+            self.tx[self.tx_len..self.tx_len + data.len()].copy_from_slice(data);
+            self.tx_len += data.len();
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+            buf.copy_from_slice(&self.rx[self.rx_pos..self.rx_pos + buf.len()]);
+            self.rx_pos += buf.len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn atr_minimal() {
+        // No interface bytes, or historical bytes: T=0 is implied, and there's no TCK.
+        let atr = Atr::parse(&[0x3b, 0x00]).unwrap();
+        assert_eq!(atr.convention, Convention::Direct);
+        assert_eq!(atr.ta1, None);
+        assert_eq!(atr.tc1, None);
+        assert!(atr.supports_protocol(0));
+        assert!(!atr.supports_protocol(1));
+        assert_eq!(atr.fi(), Some(372));
+        assert_eq!(atr.di(), Some(1));
+        assert_eq!(atr.historical(), &[]);
+
+        assert_eq!(
+            Atr::parse(&[0x3f, 0x00]).unwrap().convention,
+            Convention::Inverse
+        );
+        assert!(Atr::parse(&[0x3a, 0x00]) == Err(Error::InitialChar));
+    }
+
+    #[test]
+    fn atr_interface_chain() {
+        // T0: TA1, TB1, TC1, and TD1, and 2 historical bytes. TD1: TD2 only, T=1. TD2: TA3 and TB3,
+        // T=1. Then the historical bytes, and TCK.
+        let data = [
+            0x3b, 0xf2, 0x95, 0x00, 0x05, 0x81, 0x31, 0xfe, 0x45, 0x01, 0x02, 0x6a,
+        ];
+        let atr = Atr::parse(&data).unwrap();
+        assert_eq!(atr.ta1, Some(0x95));
+        assert_eq!(atr.tc1, Some(0x05));
+        assert_eq!(atr.ta2, None);
+        assert!(!atr.supports_protocol(0));
+        assert!(atr.supports_protocol(1));
+        assert_eq!(atr.fi(), Some(512));
+        assert_eq!(atr.di(), Some(16));
+        assert_eq!(atr.extra_guard_time(), 5);
+        assert_eq!(atr.historical(), &[0x01, 0x02]);
+
+        let mut bad_tck = data;
+        bad_tck[11] ^= 1;
+        assert!(Atr::parse(&bad_tck) == Err(Error::Checksum));
+        assert!(Atr::parse(&data[..11]) == Err(Error::Length));
+    }
+
+    #[test]
+    fn atr_pcsc() {
+        // A PC/SC contactless card ATR: TD1 indicates T=0, and TD2 T=1, with 15 historical bytes.
+        let data = [
+            0x3b, 0x8f, 0x80, 0x01, 0x80, 0x4f, 0x0c, 0xa0, 0x00, 0x00, 0x03, 0x06, 0x03, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x6a,
+        ];
+        let atr = Atr::parse(&data).unwrap();
+        assert!(atr.supports_protocol(0));
+        assert!(atr.supports_protocol(1));
+        assert_eq!(atr.ta1, None);
+        assert_eq!(atr.historical(), &data[4..19]);
+    }
+
+    #[test]
+    fn t0_procedure_bytes() {
+        // The card waits (NULL), requests one data byte (~INS), then the rest (INS).
+        let mut card = MockCard::new(&[0x60, 0x29, 0x60, 0xd6, 0x90, 0x00]);
+        let cmd = Command {
+            cla: 0,
+            ins: 0xd6,
+            p1: 0,
+            p2: 0,
+            data: &[1, 2, 3],
+            le: None,
+        };
+        let resp = transmit_t0(&mut card, &cmd, &mut []).unwrap();
+        assert_eq!(resp.sw(), 0x9000);
+        assert_eq!(resp.len, 0);
+        assert_eq!(card.sent(), &[0, 0xd6, 0, 0, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn t0_get_response() {
+        // The card accepts the data, then indicates 2 response bytes are available with 61xx.
+        let mut card = MockCard::new(&[0xa4, 0x61, 0x02, 0xc0, 0x11, 0x22, 0x90, 0x00]);
+        let cmd = Command {
+            cla: 0,
+            ins: 0xa4,
+            p1: 4,
+            p2: 0,
+            data: &[0x3f],
+            le: Some(0),
+        };
+        let mut buf = [0; 4];
+        let resp = transmit_t0(&mut card, &cmd, &mut buf).unwrap();
+        assert_eq!(resp.sw(), 0x9000);
+        assert_eq!(&buf[..resp.len], &[0x11, 0x22]);
+        assert_eq!(card.sent(), &[0, 0xa4, 4, 0, 1, 0x3f, 0, 0xc0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn t0_wrong_length() {
+        // The card rejects Le = 256 with 6Cxx; the command is re-sent with the length it indicates.
+        let mut card = MockCard::new(&[0x6c, 0x02, 0xb0, 0x11, 0x22, 0x90, 0x00]);
+        let cmd = Command {
+            cla: 0,
+            ins: 0xb0,
+            p1: 0,
+            p2: 0,
+            data: &[],
+            le: Some(0),
+        };
+        let mut buf = [0; 4];
+        let resp = transmit_t0(&mut card, &cmd, &mut buf).unwrap();
+        assert_eq!(resp.sw(), 0x9000);
+        assert_eq!(&buf[..resp.len], &[0x11, 0x22]);
+        assert_eq!(card.sent(), &[0, 0xb0, 0, 0, 0, 0, 0xb0, 0, 0, 2]);
+
+        // A card that keeps answering 6Cxx.
+        let mut rx = [0; 2 * (MAX_RETRIES + 2)];
+        for sw in rx.chunks_mut(2) {
+            sw.copy_from_slice(&[0x6c, 0x02]);
+        }
+        let mut card = MockCard::new(&rx);
+        assert!(transmit_t0(&mut card, &cmd, &mut buf) == Err(Error::Procedure));
+    }
+
+    #[test]
+    fn t0_response_too_long() {
+        let mut card = MockCard::new(&[0xb0, 0x11, 0x22, 0x33, 0x90, 0x00]);
+        let cmd = Command {
+            cla: 0,
+            ins: 0xb0,
+            p1: 0,
+            p2: 0,
+            data: &[],
+            le: Some(3),
+        };
+        let mut buf = [0; 2];
+        assert!(transmit_t0(&mut card, &cmd, &mut buf) == Err(Error::Length));
+    }
+}
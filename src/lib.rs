@@ -448,6 +448,8 @@ pub mod dfsdm;
 
 #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
 pub mod dma;
+#[cfg(feature = "f4")]
+pub mod dma_f4;
 
 #[cfg(all(feature = "h7", feature = "net"))]
 pub mod ethernet;
@@ -529,12 +531,38 @@ pub mod sai;
 #[cfg(not(feature = "h5"))] // todo: Add H5 SPI!
 pub mod spi;
 
+// I2S on the SPI peripherals. (H7 and L5 SPI register blocks differ; L4 and WB don't support it)
+#[cfg(any(feature = "f3", feature = "f4", feature = "g0", feature = "g4"))]
+pub mod i2s;
+
 #[cfg(not(feature = "h5"))] // todo temp
 pub mod timer;
 
 // #[cfg(not(feature = "h5"))] // todo temp. Needs CR1 and ISR added, among other things.
 pub mod usart;
 
+// LIN frame handling, for use with the USART in LIN mode.
+pub mod lin;
+
+// Modbus RTU frame handling, for use with the USART.
+pub mod modbus;
+
+// ATR parsing, and the T=0 protocol, for use with the USART in Smartcard mode.
+pub mod iso7816;
+
+#[cfg(any(
+    feature = "l4",
+    feature = "l5",
+    feature = "g031",
+    feature = "g041",
+    feature = "g071",
+    feature = "g081",
+    feature = "g4",
+    all(feature = "h7", not(feature = "h7b3")),
+    feature = "wb",
+))]
+pub mod lpuart;
+
 #[cfg(any(
     feature = "l4",
     feature = "g4",
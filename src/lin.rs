@@ -0,0 +1,246 @@
+//! Frame encoding and decoding for the Local Interconnect Network (LIN) protocol, as used
+//! with the USART peripheral in LIN mode. (See `Usart::enable_lin()`)
+//!
+//! This module is independent of hardware; it handles the protected identifier and checksum
+//! calculations, and can be used on its own. The break field is generated and detected by the
+//! USART hardware, and isn't handled here.
+//!
+//! See the LIN Specification Package, Revision 2.2A, section 2: "LIN protocol specification".
+
+/// The value of the synch byte, sent after the break field by the master.
+pub const SYNC: u8 = 0x55;
+
+/// The maximum number of data bytes in a frame.
+pub const MAX_DATA_LEN: usize = 8;
+
+/// Frame identifiers at or above this value are reserved for diagnostic frames, which always
+/// use the classic checksum. (IDs 0x3C and 0x3D; 0x3E and 0x3F are reserved)
+const DIAGNOSTIC_ID_START: u8 = 0x3c;
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// LIN frame errors.
+pub enum Error {
+    /// The synch byte wasn't 0x55.
+    Sync,
+    /// The parity bits of the protected identifier don't match its identifier.
+    Parity,
+    /// The received checksum doesn't match the one calculated from the frame's contents.
+    Checksum,
+    /// A frame identifier above 0x3F, or more than 8 data bytes, was specified. Or, a buffer
+    /// passed wasn't large enough.
+    Length,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// The checksum model used by a frame. Classic is used by LIN 1.x, and for diagnostic frames.
+/// Enhanced is used by LIN 2.x, and includes the protected identifier in the checksum.
+pub enum ChecksumModel {
+    Classic,
+    Enhanced,
+}
+
+/// Calculate the protected identifier from a 6-bit frame identifier. The upper 2 bits are
+/// parity bits: P0 = ID0 ⊕ ID1 ⊕ ID2 ⊕ ID4, and P1 = ¬(ID1 ⊕ ID3 ⊕ ID4 ⊕ ID5).
+pub fn protected_id(id: u8) -> u8 {
+    let id = id & 0x3f;
+    let bit = |n: u8| (id >> n) & 1;
+
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+
+    id | (p0 << 6) | (p1 << 7)
+}
+
+/// Extract the frame identifier from a protected identifier, checking its parity bits.
+pub fn id_from_protected(pid: u8) -> Result<u8, Error> {
+    let id = pid & 0x3f;
+
+    if protected_id(id) != pid {
+        return Err(Error::Parity);
+    }
+
+    Ok(id)
+}
+
+/// Calculate a frame's checksum: The inverted eight bit sum with carry over all data bytes,
+/// and the protected identifier if using the enhanced model.
+pub fn checksum(model: ChecksumModel, pid: u8, data: &[u8]) -> u8 {
+    let mut sum: u16 = match model {
+        ChecksumModel::Classic => 0,
+        ChecksumModel::Enhanced => pid as u16,
+    };
+
+    for byte in data {
+        sum += *byte as u16;
+        // The carry is added back into the least significant bit.
+        if sum > 0xff {
+            sum -= 0xff;
+        }
+    }
+
+    !(sum as u8)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// A LIN frame: Its identifier, and response data.
+pub struct Frame {
+    /// The 6-bit frame identifier, without parity bits.
+    pub id: u8,
+    data: [u8; MAX_DATA_LEN],
+    len: usize,
+}
+
+impl Frame {
+    /// Create a frame from a 6-bit identifier and up to 8 data bytes.
+    pub fn new(id: u8, data: &[u8]) -> Result<Self, Error> {
+        if id > 0x3f || data.len() > MAX_DATA_LEN {
+            return Err(Error::Length);
+        }
+
+        let mut result = Self {
+            id,
+            data: [0; MAX_DATA_LEN],
+            len: data.len(),
+        };
+        result.data[..data.len()].copy_from_slice(data);
+
+        Ok(result)
+    }
+
+    /// The frame's data bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// The frame's protected identifier; ie its identifier, with parity bits.
+    pub fn protected_id(&self) -> u8 {
+        protected_id(self.id)
+    }
+
+    /// Calculate this frame's checksum. Diagnostic frames always use the classic model, regardless
+    /// of the model passed.
+    pub fn checksum(&self, model: ChecksumModel) -> u8 {
+        let model = if self.id >= DIAGNOSTIC_ID_START {
+            ChecksumModel::Classic
+        } else {
+            model
+        };
+
+        checksum(model, self.protected_id(), self.data())
+    }
+
+    /// The header bytes sent by the master task after the break field: The synch byte, and the
+    /// protected identifier.
+    pub fn header(&self) -> [u8; 2] {
+        [SYNC, self.protected_id()]
+    }
+
+    /// Write the response (data bytes, then checksum) into a buffer. Returns the number of bytes written.
+    pub fn encode_response(&self, model: ChecksumModel, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.len + 1;
+        if buf.len() < len {
+            return Err(Error::Length);
+        }
+
+        buf[..self.len].copy_from_slice(self.data());
+        buf[self.len] = self.checksum(model);
+
+        Ok(len)
+    }
+
+    /// Decode a frame from its protected identifier, and a response: data bytes followed by the checksum.
+    pub fn decode(pid: u8, response: &[u8], model: ChecksumModel) -> Result<Self, Error> {
+        if response.is_empty() {
+            return Err(Error::Length);
+        }
+
+        let id = id_from_protected(pid)?;
+        let (data, checksum) = response.split_at(response.len() - 1);
+
+        let result = Self::new(id, data)?;
+        if result.checksum(model) != checksum[0] {
+            return Err(Error::Checksum);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Decode a header (synch byte, and protected identifier), as received by a slave task after the
+/// break field. Returns the frame identifier.
+pub fn decode_header(header: &[u8; 2]) -> Result<u8, Error> {
+    if header[0] != SYNC {
+        return Err(Error::Sync);
+    }
+
+    id_from_protected(header[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_id_parity() {
+        // From the LIN 2.2A specification's table of valid frame identifiers.
+        for (id, pid) in [
+            (0x00, 0x80),
+            (0x01, 0xc1),
+            (0x02, 0x42),
+            (0x03, 0x03),
+            (0x10, 0x50),
+            (0x3c, 0x3c),
+            (0x3d, 0x7d),
+            (0x3f, 0xbf),
+        ] {
+            assert_eq!(protected_id(id), pid);
+            assert_eq!(id_from_protected(pid), Ok(id));
+        }
+
+        // Flipping either parity bit is detected.
+        assert_eq!(id_from_protected(0x80 ^ 0x40), Err(Error::Parity));
+        assert_eq!(id_from_protected(0x80 ^ 0x80), Err(Error::Parity));
+
+        assert_eq!(decode_header(&[SYNC, 0x7d]), Ok(0x3d));
+        assert_eq!(decode_header(&[0x54, 0x7d]), Err(Error::Sync));
+    }
+
+    #[test]
+    fn checksums() {
+        // LIN 2.2A, section 2.8.3: "Example of checksum calculation".
+        let data = [0x4a, 0x55, 0x93, 0xe5];
+        assert_eq!(checksum(ChecksumModel::Classic, 0x50, &data), 0xe6);
+        // The same data, with the protected identifier 0x50 included.
+        assert_eq!(checksum(ChecksumModel::Enhanced, 0x50, &data), 0x96);
+
+        let frame = Frame::new(0x10, &data).unwrap();
+        assert_eq!(frame.checksum(ChecksumModel::Enhanced), 0x96);
+
+        // Diagnostic frames always use the classic checksum.
+        let diag = Frame::new(0x3c, &data).unwrap();
+        assert_eq!(
+            diag.checksum(ChecksumModel::Enhanced),
+            checksum(ChecksumModel::Classic, 0x3c, &data)
+        );
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let frame = Frame::new(0x10, &[0x4a, 0x55, 0x93, 0xe5]).unwrap();
+        let mut buf = [0; MAX_DATA_LEN + 1];
+        let len = frame
+            .encode_response(ChecksumModel::Enhanced, &mut buf)
+            .unwrap();
+        assert_eq!(&buf[..len], &[0x4a, 0x55, 0x93, 0xe5, 0x96]);
+
+        let decoded = Frame::decode(0x50, &buf[..len], ChecksumModel::Enhanced).unwrap();
+        assert_eq!(decoded.id, 0x10);
+        assert_eq!(decoded.data(), frame.data());
+
+        buf[0] ^= 1;
+        assert!(Frame::decode(0x50, &buf[..len], ChecksumModel::Enhanced) == Err(Error::Checksum));
+        assert!(Frame::new(0x40, &[]) == Err(Error::Length));
+        assert!(Frame::new(0, &[0; MAX_DATA_LEN + 1]) == Err(Error::Length));
+    }
+}
@@ -0,0 +1,535 @@
+//! This module allows for serial communication using the STM32 LPUART (Low-power UART) module.
+//! Its API mirrors that of the `usart` module, and shares its register-level helpers. The LPUART
+//! can be clocked from the LSE or HSI to operate, and wake the MCU, from Stop mode.
+//!
+//! See G4 RM, section 38: Low-power universal asynchronous receiver transmitter (LPUART).
+
+// todo: DMA, and FIFO threshold interrupts.
+
+use core::ops::Deref;
+
+use cortex_m::interrupt::free;
+
+#[cfg(any(feature = "l4", feature = "l5", feature = "g0", feature = "g4"))]
+use crate::low_power::{self, StopMode};
+use crate::{
+    clocks::Clocks,
+    pac::RCC,
+    usart::{cr1, isr, wait_flag, while_disabled, Error, Parity, StopBits, WordLen},
+    util::{BaudPeriph, RccPeriph},
+    MAX_ITERS,
+};
+
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "wb")] {
+        // On WB, the PAC uses the USART register block for LPUART.
+        use crate::pac::usart1 as lpuart1;
+    } else if #[cfg(feature = "g0")] {
+        use crate::pac::lpuart as lpuart1;
+    } else {
+        use crate::pac::lpuart1;
+    }
+}
+
+#[cfg(not(feature = "h7"))]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+/// The LPUART kernel clock source. Only the HSI and LSE allow operation in Stop mode.
+/// (RCC_CCIPR, LPUART1SEL)
+pub enum LpuartClkSrc {
+    /// APB clock. Not available in Stop mode.
+    Pclk = 0b00,
+    /// System clock. Not available in Stop mode.
+    Sysclk = 0b01,
+    /// HSI16 clock.
+    Hsi = 0b10,
+    /// LSE clock. This must be enabled separately; eg when setting up the RTC.
+    Lse = 0b11,
+}
+
+#[cfg(feature = "h7")]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+/// The LPUART kernel clock source. Only the HSI, CSI, and LSE allow operation in Stop mode.
+/// (RCC_D3CCIPR, LPUART1SEL)
+pub enum LpuartClkSrc {
+    /// APB4 clock. Not available in Stop mode.
+    Pclk = 0b000,
+    /// HSI kernel clock. Assumes the HSI divider is set to 1. (64Mhz)
+    Hsi = 0b011,
+    /// CSI kernel clock.
+    Csi = 0b100,
+    /// LSE clock. This must be enabled separately; eg when setting up the RTC.
+    Lse = 0b101,
+}
+
+impl LpuartClkSrc {
+    /// The kernel clock frequency, in Hz.
+    fn speed<R: BaudPeriph>(&self, clock_cfg: &Clocks) -> u32 {
+        match self {
+            Self::Pclk => R::baud(clock_cfg),
+            #[cfg(not(feature = "h7"))]
+            Self::Sysclk => clock_cfg.sysclk(),
+            #[cfg(not(feature = "h7"))]
+            Self::Hsi => 16_000_000,
+            #[cfg(feature = "h7")]
+            Self::Hsi => 64_000_000,
+            #[cfg(feature = "h7")]
+            Self::Csi => 4_000_000,
+            Self::Lse => 32_768,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+/// The event that wakes the MCU from Stop mode. (LPUART_CR3, WUS)
+pub enum WakeupSource {
+    /// Wake when the 8-bit address (or character) specified is received.
+    AddressMatch(u8),
+    /// Wake on start bit detection.
+    StartBit,
+    /// Wake when a character is received. (RXNE)
+    ReadNotEmpty,
+}
+
+impl WakeupSource {
+    /// The value to write to the WUS field.
+    fn bits(&self) -> u8 {
+        match self {
+            Self::AddressMatch(_) => 0b00,
+            Self::StartBit => 0b10,
+            Self::ReadNotEmpty => 0b11,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+/// The type of LPUART interrupt to configure. Reference the LPUART_ISR register.
+pub enum LpuartInterrupt {
+    /// If the inner value of this is `Some`, its inner value will set
+    /// the character to match on `enable_interrupt`. The option's value doesn't
+    /// affect anything when stopping or clearing interrupts.
+    CharDetect(Option<u8>),
+    Cts,
+    Idle,
+    FramingError,
+    Overrun,
+    ParityError,
+    ReadNotEmpty,
+    TransmissionComplete,
+    TransmitEmpty,
+    /// Wakeup from Stop mode. See `Lpuart::enable_wakeup()`.
+    Wakeup,
+}
+
+/// Configuration for Lpuart. Can be used with default::Default.
+pub struct LpuartConfig {
+    /// The kernel clock source. Defaults to HSI, allowing for operation in Stop mode.
+    pub clock_src: LpuartClkSrc,
+    /// Word length. Defaults to 8-bits.
+    pub word_len: WordLen,
+    /// Stop bits: Defaults to 1.
+    pub stop_bits: StopBits,
+    /// Enable or disable parity control. Defaults to disabled.
+    pub parity: Parity,
+    #[cfg(not(feature = "l4"))]
+    /// The first-in, first-out buffer is enabled. Defaults to enabled.
+    pub fifo_enabled: bool,
+    /// Optionally, disable the overrun functionality. Defaults to `false`.
+    pub overrun_disabled: bool,
+}
+
+impl Default for LpuartConfig {
+    fn default() -> Self {
+        Self {
+            clock_src: LpuartClkSrc::Hsi,
+            word_len: WordLen::W8,
+            stop_bits: StopBits::S1,
+            parity: Parity::Disabled,
+            #[cfg(not(feature = "l4"))]
+            fifo_enabled: true,
+            overrun_disabled: false,
+        }
+    }
+}
+
+/// Represents the LPUART peripheral, for low-power serial communications.
+pub struct Lpuart<R> {
+    pub regs: R,
+    baud: u32,
+    config: LpuartConfig,
+}
+
+impl<R> Lpuart<R>
+where
+    R: Deref<Target = lpuart1::RegisterBlock> + RccPeriph + BaudPeriph,
+{
+    /// Initialize the LPUART peripheral, including configuration register writes, selecting its
+    /// kernel clock, and enabling and resetting its RCC peripheral clock. `baud` is the baud rate,
+    /// in bytes-per-second. Returns `Error::BaudRate` if the kernel clock can't generate it.
+    pub fn new(
+        regs: R,
+        baud: u32,
+        config: LpuartConfig,
+        clock_cfg: &Clocks,
+    ) -> Result<Self, Error> {
+        free(|_| {
+            let rcc = unsafe { &(*RCC::ptr()) };
+            R::en_reset(rcc);
+
+            if config.clock_src == LpuartClkSrc::Hsi {
+                rcc.cr.modify(|_, w| w.hsion().set_bit());
+                while rcc.cr.read().hsirdy().bit_is_clear() {}
+            }
+
+            cfg_if! {
+                if #[cfg(feature = "h7")] {
+                    rcc.d3ccipr
+                        .modify(|_, w| unsafe { w.lpuart1sel().bits(config.clock_src as u8) });
+                } else if #[cfg(feature = "l5")] {
+                    rcc.ccipr1
+                        .modify(|_, w| unsafe { w.lpuart1sel().bits(config.clock_src as u8) });
+                } else {
+                    rcc.ccipr
+                        .modify(|_, w| unsafe { w.lpuart1sel().bits(config.clock_src as u8) });
+                }
+            }
+        });
+
+        let mut result = Self { regs, baud, config };
+
+        // Some bits can't be set with the LPUART enabled.
+        result.disable();
+
+        // See G4 RM, section 38.4.6: "LPUART transmitter", and 38.4.7: "LPUART receiver".
+        let word_len_bits = result.config.word_len.bits();
+        result.regs.cr1.modify(|_, w| {
+            w.pce().bit(result.config.parity != Parity::Disabled);
+            w.m1().bit(word_len_bits.0 != 0);
+            w.m0().bit(word_len_bits.1 != 0);
+            w.ps().bit(result.config.parity == Parity::EnabledOdd)
+        });
+
+        result
+            .regs
+            .cr3
+            .modify(|_, w| w.ovrdis().bit(result.config.overrun_disabled));
+
+        #[cfg(not(feature = "l4"))]
+        result
+            .regs
+            .cr1
+            .modify(|_, w| w.fifoen().bit(result.config.fifo_enabled));
+
+        result.set_baud(baud, clock_cfg)?;
+
+        result
+            .regs
+            .cr2
+            .modify(|_, w| unsafe { w.stop().bits(result.config.stop_bits as u8) });
+
+        result.enable();
+
+        result.regs.cr1.modify(|_, w| {
+            w.te().set_bit();
+            w.re().set_bit()
+        });
+
+        Ok(result)
+    }
+
+    /// Enable this LPUART peripheral.
+    pub fn enable(&mut self) {
+        cr1!(self.regs).modify(|_, w| w.ue().set_bit());
+        while cr1!(self.regs).read().ue().bit_is_clear() {}
+    }
+
+    /// Disable this LPUART peripheral.
+    pub fn disable(&mut self) {
+        cr1!(self.regs).modify(|_, w| w.ue().clear_bit());
+        while cr1!(self.regs).read().ue().bit_is_set() {}
+    }
+
+    /// Set the BAUD rate. Called during init, and can be called later to change BAUD
+    /// during program execution. The kernel clock must be between 3x and 4096x the baud rate;
+    /// returns `Error::BaudRate` otherwise.
+    pub fn set_baud(&mut self, baud: u32, clock_cfg: &Clocks) -> Result<(), Error> {
+        let fclk = self.config.clock_src.speed::<R>(clock_cfg);
+
+        // See G4 RM, section 38.4.8: "Generation of the baud rate".
+        // LPUARTDIV = 256 x fck / baud.
+        // "It is forbidden to write values lower than 0x300 in the LPUART_BRR register.
+        // fCK must range from 3 x baud rate to 4096 x baud rate."
+        let brr = lpuart_brr(fclk, baud);
+        if !(0x300..=0xf_ffff).contains(&brr) {
+            return Err(Error::BaudRate);
+        }
+
+        while_disabled!(self.regs, {
+            self.regs.brr.write(|w| unsafe { w.bits(brr) });
+        });
+
+        self.baud = baud;
+
+        Ok(())
+    }
+
+    /// Transmit data, as a sequence of u8. See G4 RM, section 38.4.6: "Character transmission procedure"
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        for word in data {
+            // Note: TXFNF and TXE share a field, so this checks TXFNF if the FIFO is enabled.
+            wait_flag!(self.regs, txe);
+
+            self.regs
+                .tdr
+                .modify(|_, w| unsafe { w.tdr().bits(*word as u16) });
+        }
+
+        wait_flag!(self.regs, tc);
+
+        Ok(())
+    }
+
+    /// Write a single word, without waiting until ready for the next. Compared to the `write()` function, this
+    /// does not block.
+    pub fn write_one(&mut self, word: u8) {
+        self.regs
+            .tdr
+            .modify(|_, w| unsafe { w.tdr().bits(word as u16) });
+    }
+
+    /// Receive data into a u8 buffer. See G4 RM, section 38.4.7: "Character reception procedure"
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for byte in buf {
+            wait_flag!(self.regs, rxne);
+
+            *byte = self.regs.rdr.read().rdr().bits() as u8;
+        }
+
+        Ok(())
+    }
+
+    /// Read a single word, without waiting  until ready for the next. Compared to the `read()` function, this
+    /// does not block.
+    pub fn read_one(&mut self) -> u8 {
+        self.regs.rdr.read().rdr().bits() as u8
+    }
+
+    /// Flush the transmit buffer: Block until the last frame has been shifted out. (TC flag set)
+    pub fn flush(&self) -> Result<(), Error> {
+        wait_flag!(self.regs, txe);
+        wait_flag!(self.regs, tc);
+
+        Ok(())
+    }
+
+    /// Configure the LPUART to wake the MCU from Stop mode on a given event, and enable the wakeup
+    /// interrupt. The kernel clock must be HSI or LSE (or CSI on H7). See G4 RM, section 38.4.12:
+    /// "LPUART wakeup from low-power mode". Note that on some MCUs (eg H7), the LPUART's EXTI
+    /// line must be configured separately.
+    pub fn enable_wakeup(&mut self, source: WakeupSource) {
+        // WUS can only be written when the LPUART is disabled.
+        self.disable();
+
+        if let WakeupSource::AddressMatch(addr) = source {
+            self.set_address(addr);
+        }
+
+        self.regs.cr3.modify(|_, w| unsafe {
+            w.wus().bits(source.bits());
+            w.wufie().set_bit()
+        });
+
+        // "UESM: USART enable in low-power mode. When this bit is cleared, the LPUART is not able
+        // to wake up the MCU from low-power mode."
+        self.regs.cr1.modify(|_, w| w.uesm().set_bit());
+
+        // Keep the peripheral clock enabled in Sleep and Stop modes.
+        free(|_| {
+            let rcc = unsafe { &(*RCC::ptr()) };
+            cfg_if! {
+                if #[cfg(feature = "h7")] {
+                    rcc.apb4lpenr.modify(|_, w| w.lpuart1lpen().set_bit());
+                } else if #[cfg(feature = "g0")] {
+                    rcc.apbsmenr1.modify(|_, w| w.lpuart1smen().set_bit());
+                } else {
+                    rcc.apb1smenr2.modify(|_, w| w.lpuart1smen().set_bit());
+                }
+            }
+        });
+
+        self.enable();
+    }
+
+    /// Stop the LPUART from waking the MCU from Stop mode.
+    pub fn disable_wakeup(&mut self) {
+        self.regs.cr1.modify(|_, w| w.uesm().clear_bit());
+        self.regs.cr3.modify(|_, w| w.wufie().clear_bit());
+    }
+
+    #[cfg(any(feature = "l4", feature = "l5", feature = "g0", feature = "g4"))]
+    /// Enter Stop mode, and wake on the event set up with `enable_wakeup()`. This waits for any
+    /// transmission or reception in progress to complete first, and clears the wakeup flag on exit.
+    /// Run `Clocks::reselect_input()` after to re-enable PLL etc after exiting this mode.
+    pub fn stop(&mut self, mode: StopMode) -> Result<(), Error> {
+        // "Before entering low-power mode, make sure that no LPUART transfer is ongoing."
+        self.flush()?;
+
+        wait_flag!(self.regs, busy, clear);
+
+        // Make sure the receiver is enabled, so it can detect the wakeup event.
+        wait_flag!(self.regs, reack);
+
+        self.regs.icr.write(|w| w.wucf().set_bit());
+
+        low_power::stop(mode);
+
+        self.regs.icr.write(|w| w.wucf().set_bit());
+
+        Ok(())
+    }
+
+    /// Set the address (or character) to match, for the character match interrupt, and
+    /// wakeup on address match. This must be called while the LPUART is disabled.
+    fn set_address(&mut self, addr: u8) {
+        // Allow an 8-bit address to be set in `add`. We write ADD (CR2 bits 31:24) directly,
+        // since some PACs split this field in two.
+        self.regs.cr2.modify(|_, w| w.addm7().set_bit());
+        self.regs
+            .cr2
+            .modify(|r, w| unsafe { w.bits((r.bits() & 0x00ff_ffff) | ((addr as u32) << 24)) });
+    }
+
+    /// Enable a specific type of interrupt. See G4 RM, Table 367: LPUART interrupt requests.
+    /// If `Some`, the inner value of `CharDetect` sets the address of the char to match.
+    /// If `None`, the interrupt is enabled without changing the char to match.
+    pub fn enable_interrupt(&mut self, interrupt: LpuartInterrupt) {
+        match interrupt {
+            LpuartInterrupt::CharDetect(char_wrapper) => {
+                if let Some(char) = char_wrapper {
+                    self.disable();
+                    self.set_address(char);
+                    self.enable();
+                }
+
+                self.regs.cr1.modify(|_, w| w.cmie().set_bit());
+            }
+            LpuartInterrupt::Cts => {
+                self.regs.cr3.modify(|_, w| w.ctsie().set_bit());
+            }
+            LpuartInterrupt::Idle => {
+                self.regs.cr1.modify(|_, w| w.idleie().set_bit());
+            }
+            LpuartInterrupt::FramingError => {
+                self.regs.cr3.modify(|_, w| w.eie().set_bit());
+            }
+            LpuartInterrupt::Overrun => {
+                self.regs.cr3.modify(|_, w| w.eie().set_bit());
+            }
+            LpuartInterrupt::ParityError => {
+                self.regs.cr1.modify(|_, w| w.peie().set_bit());
+            }
+            LpuartInterrupt::ReadNotEmpty => {
+                self.regs.cr1.modify(|_, w| w.rxneie().set_bit());
+            }
+            LpuartInterrupt::TransmissionComplete => {
+                self.regs.cr1.modify(|_, w| w.tcie().set_bit());
+            }
+            LpuartInterrupt::TransmitEmpty => {
+                self.regs.cr1.modify(|_, w| w.txeie().set_bit());
+            }
+            LpuartInterrupt::Wakeup => {
+                self.regs.cr3.modify(|_, w| w.wufie().set_bit());
+            }
+        }
+    }
+
+    /// Disable a specific type of interrupt. See G4 RM, Table 367: LPUART interrupt requests.
+    /// Note that the inner value of `CharDetect` doesn't do anything here.
+    pub fn disable_interrupt(&mut self, interrupt: LpuartInterrupt) {
+        match interrupt {
+            LpuartInterrupt::CharDetect(_) => {
+                self.regs.cr1.modify(|_, w| w.cmie().clear_bit());
+            }
+            LpuartInterrupt::Cts => {
+                self.regs.cr3.modify(|_, w| w.ctsie().clear_bit());
+            }
+            LpuartInterrupt::Idle => {
+                self.regs.cr1.modify(|_, w| w.idleie().clear_bit());
+            }
+            LpuartInterrupt::FramingError => {
+                self.regs.cr3.modify(|_, w| w.eie().clear_bit());
+            }
+            LpuartInterrupt::Overrun => {
+                self.regs.cr3.modify(|_, w| w.eie().clear_bit());
+            }
+            LpuartInterrupt::ParityError => {
+                self.regs.cr1.modify(|_, w| w.peie().clear_bit());
+            }
+            LpuartInterrupt::ReadNotEmpty => {
+                self.regs.cr1.modify(|_, w| w.rxneie().clear_bit());
+            }
+            LpuartInterrupt::TransmissionComplete => {
+                self.regs.cr1.modify(|_, w| w.tcie().clear_bit());
+            }
+            LpuartInterrupt::TransmitEmpty => {
+                self.regs.cr1.modify(|_, w| w.txeie().clear_bit());
+            }
+            LpuartInterrupt::Wakeup => {
+                self.regs.cr3.modify(|_, w| w.wufie().clear_bit());
+            }
+        }
+    }
+
+    /// Print the (raw) contents of the status register.
+    pub fn read_status(&self) -> u32 {
+        unsafe { isr!(self.regs).read().bits() }
+    }
+
+    /// Clears the interrupt pending flag for a specific type of interrupt. Note that
+    /// it can also clear error flags, like Overrun and framing errors.
+    /// Note that the inner value of `CharDetect` doesn't do anything here.
+    pub fn clear_interrupt(&mut self, interrupt: LpuartInterrupt) {
+        match interrupt {
+            LpuartInterrupt::CharDetect(_) => self.regs.icr.write(|w| w.cmcf().set_bit()),
+            LpuartInterrupt::Cts => self.regs.icr.write(|w| w.ctscf().set_bit()),
+            LpuartInterrupt::Idle => self.regs.icr.write(|w| w.idlecf().set_bit()),
+            LpuartInterrupt::FramingError => self.regs.icr.write(|w| w.fecf().set_bit()),
+            LpuartInterrupt::Overrun => self.regs.icr.write(|w| w.orecf().set_bit()),
+            LpuartInterrupt::ParityError => self.regs.icr.write(|w| w.pecf().set_bit()),
+            LpuartInterrupt::ReadNotEmpty => self.regs.rqr.write(|w| w.rxfrq().set_bit()),
+            LpuartInterrupt::TransmissionComplete => self.regs.icr.write(|w| w.tccf().set_bit()),
+            #[cfg(feature = "l4")]
+            LpuartInterrupt::TransmitEmpty => (), // No TXFRQ bit on L4's LPUART.
+            #[cfg(not(feature = "l4"))]
+            LpuartInterrupt::TransmitEmpty => self.regs.rqr.write(|w| w.txfrq().set_bit()),
+            LpuartInterrupt::Wakeup => self.regs.icr.write(|w| w.wucf().set_bit()),
+        }
+    }
+
+    /// Checks if a given status flag is set. Returns `true` if the status flag is set. Note that this preforms
+    /// a read each time called. If checking multiple flags, this isn't optimal.
+    pub fn check_status_flag(&mut self, flag: LpuartInterrupt) -> bool {
+        let status = isr!(self.regs).read();
+
+        match flag {
+            LpuartInterrupt::CharDetect(_) => status.cmf().bit_is_set(),
+            LpuartInterrupt::Cts => status.ctsif().bit_is_set(),
+            LpuartInterrupt::Idle => status.idle().bit_is_set(),
+            LpuartInterrupt::FramingError => status.fe().bit_is_set(),
+            LpuartInterrupt::Overrun => status.ore().bit_is_set(),
+            LpuartInterrupt::ParityError => status.pe().bit_is_set(),
+            LpuartInterrupt::ReadNotEmpty => status.rxne().bit_is_set(),
+            LpuartInterrupt::TransmissionComplete => status.tc().bit_is_set(),
+            LpuartInterrupt::TransmitEmpty => status.txe().bit_is_set(),
+            LpuartInterrupt::Wakeup => status.wuf().bit_is_set(),
+        }
+    }
+}
+
+/// Calculate the LPUART_BRR value: 256 x the kernel clock speed, divided by the baud rate.
+fn lpuart_brr(fclk: u32, baud: u32) -> u32 {
+    ((256 * fclk as u64 + baud as u64 / 2) / baud as u64) as u32
+}
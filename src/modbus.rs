@@ -0,0 +1,173 @@
+//! Frame encoding and decoding for the Modbus RTU protocol, as used with the USART peripheral.
+//! (See `Usart::modbus_read_dma()`)
+//!
+//! This module is independent of hardware; it handles the CRC, addressing, and frame
+//! boundary timing calculations, and can be used on its own. On the USART, the end of a frame
+//! (a t3.5 silent interval) is detected by the receiver timeout.
+//!
+//! See the Modbus over Serial Line Specification and Implementation Guide, V1.02,
+//! section 2.5.1: "RTU Transmission Mode".
+
+/// The maximum size of an RTU frame: Address, PDU, and CRC.
+pub const MAX_ADU_LEN: usize = 256;
+
+/// The maximum size of a PDU: Function code, and data.
+pub const MAX_PDU_LEN: usize = 253;
+
+/// The broadcast address. Slaves don't reply to requests sent to it.
+pub const BROADCAST_ADDR: u8 = 0;
+
+/// The number of bits in an RTU character: Start bit, 8 data bits, parity or a second stop bit,
+/// and a stop bit.
+const BITS_PER_CHAR: u32 = 11;
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Modbus RTU frame errors.
+pub enum Error {
+    /// The received CRC doesn't match the one calculated from the frame's contents.
+    Crc,
+    /// A frame was shorter than the minimum of 4 bytes, or a PDU was longer than 253 bytes.
+    /// Or, a buffer passed wasn't large enough.
+    Length,
+}
+
+/// Calculate the CRC-16/MODBUS of a sequence of bytes. (Polynomial 0xA001, reflected,
+/// initial value 0xFFFF). It's transmitted low byte first.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xa001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+/// The minimum silent interval that separates frames (t3.5), in bit-times, for a given baud rate.
+/// This is the value to use with `Usart::enable_receiver_timeout()`. Above 19200 baud, the
+/// specification fixes this at 1.75ms.
+pub fn t35_bit_times(baud: u32) -> u32 {
+    if baud > 19_200 {
+        // Round up.
+        (1_750 * baud as u64).div_ceil(1_000_000) as u32
+    } else {
+        (35 * BITS_PER_CHAR).div_ceil(10)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// A Modbus RTU frame (Application Data Unit): A slave address, and a PDU. The PDU is the function
+/// code, followed by its data.
+pub struct Adu<'a> {
+    pub address: u8,
+    pub pdu: &'a [u8],
+}
+
+impl<'a> Adu<'a> {
+    /// Create a frame from a slave address, and a PDU of up to 253 bytes.
+    pub fn new(address: u8, pdu: &'a [u8]) -> Result<Self, Error> {
+        if pdu.is_empty() || pdu.len() > MAX_PDU_LEN {
+            return Err(Error::Length);
+        }
+
+        Ok(Self { address, pdu })
+    }
+
+    /// The function code; the first byte of the PDU.
+    pub fn function(&self) -> u8 {
+        self.pdu[0]
+    }
+
+    /// The data following the function code.
+    pub fn data(&self) -> &'a [u8] {
+        &self.pdu[1..]
+    }
+
+    /// Write the frame (address, PDU, then CRC) into a buffer. Returns the number of bytes written.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.pdu.len() + 3;
+        if buf.len() < len {
+            return Err(Error::Length);
+        }
+
+        buf[0] = self.address;
+        buf[1..len - 2].copy_from_slice(self.pdu);
+
+        let crc = crc16(&buf[..len - 2]);
+        buf[len - 2..len].copy_from_slice(&crc.to_le_bytes());
+
+        Ok(len)
+    }
+
+    /// Decode a received frame, checking its CRC. `frame` must contain exactly one frame, as
+    /// delimited by a t3.5 silent interval.
+    pub fn decode(frame: &'a [u8]) -> Result<Self, Error> {
+        // Address, function code, and 2 CRC bytes.
+        if frame.len() < 4 || frame.len() > MAX_ADU_LEN {
+            return Err(Error::Length);
+        }
+
+        let (contents, crc) = frame.split_at(frame.len() - 2);
+        if crc16(contents).to_le_bytes() != [crc[0], crc[1]] {
+            return Err(Error::Crc);
+        }
+
+        Ok(Self {
+            address: contents[0],
+            pdu: &contents[1..],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_known_values() {
+        // The CRC-16/MODBUS check value: the CRC of ASCII "123456789".
+        assert_eq!(crc16(b"123456789"), 0x4b37);
+        assert_eq!(crc16(&[]), 0xffff);
+        // Read Holding Registers requests; the CRCs are sent low byte first. (C5 CD, and 76 87)
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0a]), 0xcdc5);
+        assert_eq!(crc16(&[0x11, 0x03, 0x00, 0x6b, 0x00, 0x03]), 0x8776);
+    }
+
+    #[test]
+    fn adu_round_trip() {
+        let pdu = [0x03, 0x00, 0x6b, 0x00, 0x03];
+        let mut buf = [0; MAX_ADU_LEN];
+        let len = Adu::new(0x11, &pdu).unwrap().encode(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &[0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87]
+        );
+
+        let adu = Adu::decode(&buf[..len]).unwrap();
+        assert_eq!(adu.address, 0x11);
+        assert_eq!(adu.function(), 0x03);
+        assert_eq!(adu.data(), &pdu[1..]);
+
+        buf[len - 1] ^= 1;
+        assert!(Adu::decode(&buf[..len]) == Err(Error::Crc));
+        assert!(Adu::decode(&buf[..3]) == Err(Error::Length));
+        assert!(Adu::new(1, &[]) == Err(Error::Length));
+    }
+
+    #[test]
+    fn t35() {
+        // 3.5 characters of 11 bits, rounded up.
+        assert_eq!(t35_bit_times(9_600), 39);
+        assert_eq!(t35_bit_times(19_200), 39);
+        // 1.75ms above 19200 baud.
+        assert_eq!(t35_bit_times(115_200), 202);
+    }
+}
@@ -14,20 +14,13 @@ use crate::pac::sai;
 #[cfg(feature = "h7")]
 use crate::pac::sai4 as sai;
 
-#[cfg(feature = "g0")]
-use crate::pac::dma as dma_p;
-#[cfg(any(
-    feature = "f3",
-    feature = "l4",
-    feature = "l5",
-    feature = "g4",
-    feature = "h7",
-    feature = "wb"
-))]
-use crate::pac::dma1 as dma_p;
+#[cfg(all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))))]
+use crate::pac::DMA as DMA1;
+#[cfg(not(all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1")))))]
+use crate::pac::DMA1;
 
 #[cfg(not(any(feature = "f4", feature = "l552")))]
-use crate::dma::{self, ChannelCfg, Dma, DmaChannel};
+use crate::dma::{self, ChannelCfg, DmaChannel};
 
 #[cfg(any(feature = "f3", feature = "l4"))]
 use crate::dma::DmaInput;
@@ -976,33 +969,31 @@ It can generate an interrupt if WCKCFGIE bit is set in SAI_xIM register");
     /// There is one DMA channel per audio subblock supporting basic DMA request/acknowledge
     /// protocol.
     /// Before configuring the SAI block, the SAI DMA channel must be disabled.
+    /// `dma_periph` selects the DMA controller to use; it replaces the `dma: &mut Dma<D>` argument
+    /// this took previously.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
-    pub unsafe fn write_dma<D>(
+    pub unsafe fn write_dma(
         &mut self,
         buf: &[i32], // todo size?
         sai_channel: SaiChannel,
         dma_channel: DmaChannel,
         channel_cfg: ChannelCfg,
-        dma: &mut Dma<D>,
-    ) where
-        D: Deref<Target = dma_p::RegisterBlock>,
-    {
+        dma_periph: dma::DmaPeriph,
+    ) {
         let (ptr, len) = (buf.as_ptr(), buf.len());
 
-        // todo: DMA2 support.
-
-        // L44 RM, Table 41. "DMA1 requests for each channel"
+        // L44 RM, Table 41. "DMA1 requests for each channel", and Table 42: "DMA2 requests for each channel"
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let dma_channel = match sai_channel {
-            SaiChannel::A => DmaInput::Sai1A.dma1_channel(),
-            SaiChannel::B => DmaInput::Sai1B.dma1_channel(),
+        let dma_input = match sai_channel {
+            SaiChannel::A => DmaInput::Sai1A,
+            SaiChannel::B => DmaInput::Sai1B,
         };
 
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let dma_channel = dma_input.channel(dma_periph).unwrap_or(dma_channel);
+
         #[cfg(feature = "l4")]
-        match sai_channel {
-            SaiChannel::A => dma.channel_select(DmaInput::Sai1A),
-            SaiChannel::B => dma.channel_select(DmaInput::Sai1B),
-        };
+        dma::channel_select(dma_periph, dma_input);
 
         // To configure the audio subblock for DMA transfer, set DMAEN bit in the SAI_xCR1 register.
         // The DMA request is managed directly by the FIFO controller depending on the FIFO
@@ -1046,16 +1037,37 @@ It can generate an interrupt if WCKCFGIE bit is set in SAI_xIM register");
             _ => dma::DataSize::S32,
         };
 
-        dma.cfg_channel(
-            dma_channel,
-            periph_addr,
-            ptr as u32,
-            len,
-            dma::Direction::ReadFromMem,
-            datasize,
-            datasize,
-            channel_cfg,
-        );
+        match dma_periph {
+            dma::DmaPeriph::Dma1 => {
+                let mut regs = unsafe { &(*DMA1::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    dma_channel,
+                    periph_addr,
+                    ptr as u32,
+                    len,
+                    dma::Direction::ReadFromMem,
+                    datasize,
+                    datasize,
+                    channel_cfg,
+                );
+            }
+            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
+            dma::DmaPeriph::Dma2 => {
+                let mut regs = unsafe { &(*crate::pac::DMA2::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    dma_channel,
+                    periph_addr,
+                    ptr as u32,
+                    len,
+                    dma::Direction::ReadFromMem,
+                    datasize,
+                    datasize,
+                    channel_cfg,
+                );
+            }
+        }
 
         // 4. Enable the SAI interface. (handled by `Sai::enable() in user code`.)
     }
@@ -1064,36 +1076,32 @@ It can generate an interrupt if WCKCFGIE bit is set in SAI_xIM register");
     /// To free the CPU and to optimize bus bandwidth, each SAI audio block has an independent
     /// DMA interface to read/write from/to the SAI_xDR register (to access the internal FIFO).
     /// There is one DMA channel per audio subblock supporting basic DMA request/acknowledge
-    /// protocol.
+    /// protocol. `dma_periph` selects the DMA controller to use; it replaces the
+    /// `dma: &mut Dma<D>` argument this took previously.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
-    pub unsafe fn read_dma<D>(
+    pub unsafe fn read_dma(
         &mut self,
         buf: &mut [i32], // todo size?
         sai_channel: SaiChannel,
         dma_channel: DmaChannel,
         channel_cfg: ChannelCfg,
-        dma: &mut Dma<D>,
-    ) where
-        D: Deref<Target = dma_p::RegisterBlock>,
-    {
+        dma_periph: dma::DmaPeriph,
+    ) {
         let (ptr, len) = (buf.as_mut_ptr(), buf.len());
 
         // See commends on `write_dma`.
 
-        // L44 RM, Table 41. "DMA1 requests for each channel
-        // todo: DMA2 support.
-
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let dma_channel = match sai_channel {
-            SaiChannel::A => DmaInput::Sai1A.dma1_channel(),
-            SaiChannel::B => DmaInput::Sai1B.dma1_channel(),
+        let dma_input = match sai_channel {
+            SaiChannel::A => DmaInput::Sai1A,
+            SaiChannel::B => DmaInput::Sai1B,
         };
 
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let dma_channel = dma_input.channel(dma_periph).unwrap_or(dma_channel);
+
         #[cfg(feature = "l4")]
-        match sai_channel {
-            SaiChannel::A => dma.channel_select(DmaInput::Sai1A),
-            SaiChannel::B => dma.channel_select(DmaInput::Sai1B),
-        };
+        dma::channel_select(dma_periph, dma_input);
 
         match sai_channel {
             SaiChannel::A => self.regs.cha().cr1.modify(|_, w| w.dmaen().set_bit()),
@@ -1122,16 +1130,37 @@ It can generate an interrupt if WCKCFGIE bit is set in SAI_xIM register");
             _ => dma::DataSize::S32,
         };
 
-        dma.cfg_channel(
-            dma_channel,
-            periph_addr,
-            ptr as u32,
-            num_data,
-            dma::Direction::ReadFromPeriph,
-            datasize,
-            datasize,
-            channel_cfg,
-        );
+        match dma_periph {
+            dma::DmaPeriph::Dma1 => {
+                let mut regs = unsafe { &(*DMA1::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    dma_channel,
+                    periph_addr,
+                    ptr as u32,
+                    num_data,
+                    dma::Direction::ReadFromPeriph,
+                    datasize,
+                    datasize,
+                    channel_cfg,
+                );
+            }
+            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
+            dma::DmaPeriph::Dma2 => {
+                let mut regs = unsafe { &(*crate::pac::DMA2::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    dma_channel,
+                    periph_addr,
+                    ptr as u32,
+                    num_data,
+                    dma::Direction::ReadFromPeriph,
+                    datasize,
+                    datasize,
+                    channel_cfg,
+                );
+            }
+        }
 
         // 4. Enable the SAI interface. (handled by `Sai::enable() in user code`.)
     }
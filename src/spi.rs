@@ -8,6 +8,7 @@ use core::{ops::Deref, ptr};
 use embedded_hal::spi::FullDuplex;
 
 use crate::{
+    gpio::Pin,
     pac::{self, RCC},
     util::RccPeriph,
     MAX_ITERS,
@@ -17,16 +18,14 @@ use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))))] {
-        use crate::pac::dma as dma_p;
         use crate::pac::DMA as DMA1;
     } else {
-        use crate::pac::dma1 as dma_p;
         use crate::pac::DMA1;
     }
 }
 
 #[cfg(not(any(feature = "f4", feature = "l552")))]
-use crate::dma::{self, ChannelCfg, Dma, DmaChannel};
+use crate::dma::{self, ChannelCfg, Circular, DmaChannel, ReadBuffer, Transfer, WriteBuffer};
 
 #[cfg(any(feature = "f3", feature = "l4"))]
 use crate::dma::DmaInput;
@@ -156,6 +155,30 @@ pub enum DataSize {
     D32 = 31,
 }
 
+/// A word that can be written to, or read from the SPI data register: `u8`, `u16`, or on H7, `u32`.
+/// Its size sets the width of data register accesses and DMA transfers, and must be at least
+/// as large as the configured `DataSize`. On H7, a word wider than `DataSize` packs several frames;
+/// the FIFO threshold and TSIZE are set to match. `write()`, `transfer()`, and `read()` use `u8`;
+/// use `write_words()`, `transfer_words()`, and `read_word()` for other word sizes. (`read()` and
+/// `read_word()` aren't available on H7)
+pub trait SpiWord: Copy {
+    /// The word size, in bits.
+    const BITS: u8;
+}
+
+impl SpiWord for u8 {
+    const BITS: u8 = 8;
+}
+
+impl SpiWord for u16 {
+    const BITS: u8 = 16;
+}
+
+#[cfg(any(feature = "h5", feature = "h7"))]
+impl SpiWord for u32 {
+    const BITS: u8 = 32;
+}
+
 #[derive(Clone, Copy)]
 #[repr(u8)]
 /// FIFO reception threshold Sets `SPI_CR2` register, `FRXTH` field.
@@ -178,6 +201,52 @@ pub enum SpiCommMode {
     ReceiveOnly,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+/// Whether this device generates the clock as the master, or responds to an external master as a slave.
+/// Sets CR1 register, MSTR field. On H7, sets CFG2 register, MASTER field.
+pub enum SpiRole {
+    Master,
+    /// In slave mode, use `SlaveSelect::HardwareOutDisable` to use the NSS pin as a chip select
+    /// input, or `SlaveSelect::Software` to be always selected.
+    Slave,
+}
+
+#[cfg(feature = "h7")]
+#[derive(Clone, Copy)]
+/// The data a slave transmits if the master clocks a frame while the TX FIFO is empty. Sets CFG1 register,
+/// UDRCFG field. (On other families, the slave re-transmits the last data sent)
+pub enum UnderrunBehavior {
+    /// Transmit a constant pattern. Sets the UDRDR register.
+    Constant(u32),
+    /// Transmit the last data received from the master.
+    RepeatLastReceived,
+    /// Transmit the last data transmitted.
+    RepeatLastTransmitted,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// CRC length. Sets CR1 register, CRCL field. On H7, sets CFG1 register, CRCSIZE field. On F4, the
+/// CRC length is the data frame format, so this must match the word size used.
+pub enum CrcLen {
+    L8,
+    L16,
+    #[cfg(feature = "h7")]
+    L32,
+}
+
+#[derive(Clone, Copy)]
+/// Hardware CRC configuration. When enabled, `write()` and `transfer()` append a CRC to the data
+/// transmitted, and check the CRC received after the data, returning `SpiError::Crc` if it doesn't
+/// match. With DMA, the CRC is transmitted after the number of words configured. On H7, the DMA
+/// functions set TSIZE to the buffer length when CRC is enabled, so the CRC is sent at the end of the
+/// transaction; a mismatch sets the CRCE flag (`SpiInterrupt::CrcError`), and `stop_dma()` resets TSIZE.
+pub struct SpiCrc {
+    /// The CRC polynomial, without its most significant (x^n) term; eg 0x07 for x^8 + x^2 + x + 1.
+    /// Sets the CRCPR register. On H7, sets the CRCPOLY register.
+    pub polynomial: u32,
+    pub len: CrcLen,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 /// Used for managing NSS / CS pin. Sets CR1 register, SSM field.
 /// On H7, sets CFG2 register, `SSOE` field.
@@ -288,6 +357,13 @@ pub struct SpiConfig {
     pub data_size: DataSize,
     /// FIFO reception threshhold. Defaults to 8 bits.
     pub fifo_reception_thresh: ReceptionThresh,
+    /// Master or slave operation. Defaults to master.
+    pub role: SpiRole,
+    /// Hardware CRC calculation. Defaults to disabled.
+    pub crc: Option<SpiCrc>,
+    #[cfg(feature = "h7")]
+    /// In slave mode, the data transmitted on TX underrun. Defaults to a constant pattern of 0.
+    pub underrun: UnderrunBehavior,
     // pub cs_delay: f32,
     // pub swap_miso_mosi: bool,
     // pub suspend_when_inactive: bool,
@@ -309,6 +385,10 @@ impl Default for SpiConfig {
             slave_select: SlaveSelect::Software,
             data_size: DataSize::D8,
             fifo_reception_thresh: ReceptionThresh::D8,
+            role: SpiRole::Master,
+            crc: None,
+            #[cfg(feature = "h7")]
+            underrun: UnderrunBehavior::Constant(0),
         }
     }
 }
@@ -354,23 +434,55 @@ where
                 // [St forum thread on how to set up SPI in master mode avoiding mode faults:
                 // https://community.st.com/s/question/0D50X0000AFrHS6SQN/stm32h7-what-is-the-proper-
                 // way-to-make-spi-work-in-master-mode
+                let master = cfg.role == SpiRole::Master;
+
+                // In slave mode with software slave management, SSI cleared selects this device.
                 regs.cr1.modify(|_, w| {
-                    w.ssi().bit(cfg.slave_select == SlaveSelect::Software)
+                    w.ssi().bit(cfg.slave_select == SlaveSelect::Software && master)
                 });
 
-                regs.cfg1.modify(|_, w| {
+                // "UDRDET[1:0] and UDRCFG[1:0] are required at slave mode only"
+                let udrcfg = match cfg.underrun {
+                    UnderrunBehavior::Constant(pattern) => {
+                        regs.udrdr.write(|w| w.udrdr().bits(pattern));
+                        0b00
+                    }
+                    UnderrunBehavior::RepeatLastReceived => 0b01,
+                    UnderrunBehavior::RepeatLastTransmitted => 0b10,
+                };
+
+                // c) CRCSIZE[4:0] is required if CRCEN is set
+                let crc_size = match cfg.crc {
+                    Some(crc) => match crc.len {
+                        CrcLen::L8 => 7,
+                        CrcLen::L16 => 15,
+                        CrcLen::L32 => 31,
+                    },
+                    None => 7,
+                };
+
+                regs.cfg1.modify(|_, w| unsafe {
                     w.mbr().bits(baud_rate as u8);
                     w.dsize().bits(cfg.data_size as u8);
-                    w.crcen().clear_bit()
+                    w.crcen().bit(cfg.crc.is_some());
+                    w.crcsize().bits(crc_size);
+                    // Detect underrun at the beginning of each data frame.
+                    w.udrdet().bits(0b00);
+                    w.udrcfg().bits(udrcfg)
                 });
 
                 regs.cfg2.modify(|_, w| {
                     w.cpol().bit(cfg.mode.polarity as u8 != 0);
                     w.cpha().bit(cfg.mode.phase as u8 != 0);
-                    w.master().set_bit();
+                    w.master().bit(master);
                     w.ssm().bit(cfg.slave_select == SlaveSelect::Software);
-                    w.ssoe().bit(cfg.slave_select != SlaveSelect::Software);
-                    w.comm().bits(0b00) // Full-duplex mode
+                    w.ssoe().bit(cfg.slave_select != SlaveSelect::Software && master);
+                    w.comm().bits(match cfg.comm_mode {
+                        SpiCommMode::FullDuplex => 0b00,
+                        SpiCommMode::TransmitOnly => 0b01,
+                        SpiCommMode::ReceiveOnly => 0b10,
+                        SpiCommMode::HalfDuplex => 0b11,
+                    })
                     // w.comm().lsbfrst().clear_bit() // MSB first
                     // w.ssoe().bit(cfg.slave_select != SlaveSelect::Software)
                 });
@@ -378,17 +490,24 @@ where
                 // todo: You may not need this master line separate. TSing SS config issues.
                 // regs.cfg2.modify(|_, w| w.master().set_bit());
 
-                // todo: CR2.tsize should be > 0.
-
-
                 // 3. Write to the SPI_CR2 register to select length of the transfer, if it is not known TSIZE
                 // has to be programmed to zero.
+                // (`write()` and `transfer()` set this for each transfer.)
                 regs.cr2.modify(|_, w| {
-                    w.tsize().bits(0) // todo?
+                    w.tsize().bits(0)
                 });
 
                 // 4. Write to SPI_CRCPOLY and into TCRCINI, RCRCINI and CRC33_17 bits at
                 // SPI2S_CR1 register to configure the CRC polynomial and CRC calculation if needed.
+                if let Some(crc) = cfg.crc {
+                    regs.crcpoly.write(|w| unsafe { w.crcpoly().bits(crc.polynomial) });
+                    regs.cr1.modify(|_, w| {
+                        w.tcrcini().clear_bit();
+                        w.rcrcini().clear_bit();
+                        // The 33rd bit of a 32-bit polynomial isn't stored in CRCPOLY.
+                        w.crc33_17().bit(crc.len == CrcLen::L32)
+                    });
+                }
 
                 // 5. Configure DMA streams dedicated for the SPI Tx and Rx in DMA registers if the DMA
                 // streams are used (see chapter Communication using DMA).
@@ -408,6 +527,12 @@ where
                 // 1. Write proper GPIO registers: Configure GPIO for MOSI, MISO and SCK pins.
                 // (Handled in GPIO modules and user code)
 
+                // 4. Write to SPI_CRCPR register: Configure the CRC polynomial if needed.
+                // (We do this first, since the SPI is enabled in step 2)
+                if let Some(crc) = cfg.crc {
+                    regs.crcpr.write(|w| unsafe { w.crcpoly().bits(crc.polynomial as u16) });
+                }
+
                 // 2. Write to the SPI_CR1 register:
                 regs.cr1.modify(|_, w| unsafe {
                     // a) Configure the serial clock baud rate using the BR[2:0] bits (Note: 4)
@@ -426,19 +551,27 @@ where
                     w.lsbfirst().clear_bit();
                     // e) Configure the CRCL and CRCEN bits if CRC is needed (while SCK clock signal is
                     // at idle state).
-                    w.crcen().clear_bit();
-                    // f) Configure SSM and SSI (Notes: 2 & 3).
+                    #[cfg(not(feature = "f4"))]
+                    let crc16 = matches!(cfg.crc, Some(SpiCrc { len: CrcLen::L16, .. }));
+                    #[cfg(not(any(feature = "f4", feature = "g4", feature = "l5", feature = "wb")))]
+                    w.crcl().bit(crc16);
+                    // The G4, L5, and WB PACs call the CRCL field DFF.
+                    #[cfg(any(feature = "g4", feature = "l5", feature = "wb"))]
+                    w.dff().bit(crc16);
+                    w.crcen().bit(cfg.crc.is_some());
+                    // f) Configure SSM and SSI (Notes: 2 & 3). In slave mode with software slave
+                    // management, SSI cleared selects this device.
                     w.ssm().bit(cfg.slave_select == SlaveSelect::Software);
-                    w.ssi().bit(cfg.slave_select == SlaveSelect::Software);
+                    w.ssi().bit(cfg.slave_select == SlaveSelect::Software && cfg.role == SpiRole::Master);
                     // g) Configure the MSTR bit (in multimaster NSS configuration, avoid conflict state on
                     // NSS if master is configured to prevent MODF error).
-                    w.mstr().set_bit();
+                    w.mstr().bit(cfg.role == SpiRole::Master);
                     w.spe().set_bit() // Enable SPI
                 });
 
                 // 3. Write to SPI_CR2 register:
                 #[cfg(feature = "f4")]
-                regs.cr2.modify(|_, w| w.ssoe().bit(cfg.slave_select == SlaveSelect::HardwareOutEnable));
+                regs.cr2.modify(|_, w| w.ssoe().bit(cfg.slave_select == SlaveSelect::HardwareOutEnable && cfg.role == SpiRole::Master));
 
                 #[cfg(not(feature = "f4"))]
                 regs.cr2
@@ -446,7 +579,7 @@ where
                         // a) Configure the DS[3:0] bits to select the data length for the transfer.
                         w.ds().bits(cfg.data_size as u8);
                         // b) Configure SSOE (Notes: 1 & 2 & 3).
-                        w.ssoe().bit(cfg.slave_select == SlaveSelect::HardwareOutEnable);
+                        w.ssoe().bit(cfg.slave_select == SlaveSelect::HardwareOutEnable && cfg.role == SpiRole::Master);
                         // e) Configure the FRXTH bit. The RXFIFO threshold must be aligned to the read
                         // access size for the SPIx_DR register.
                         w.frxth().bit(cfg.fifo_reception_thresh as u8 != 0)
@@ -457,7 +590,6 @@ where
                 // CHPA and TI bits cleared in NSSP mode).
 
                 // f) Initialize LDMA_TX and LDMA_RX bits if DMA is used in packed mode.
-                // 4. Write to SPI_CRCPR register: Configure the CRC polynomial if needed.
                 // 5. Write proper DMA registers: Configure DMA streams dedicated for SPI Tx and Rx in
                 // DMA registers if the DMA streams are used.
             }
@@ -488,6 +620,30 @@ where
         self.regs.cr1.modify(|_, w| w.spe().set_bit());
     }
 
+    /// Change the SPI mode; ie clock polarity and phase. Sets CR1 register, CPOL and CPHA fields.
+    /// On H7, sets CFG2 register, CPOL and CPHA fields.
+    pub fn set_mode(&mut self, mode: SpiModeType) {
+        self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+
+        cfg_if! {
+            if #[cfg(any(feature = "h5", feature = "h7"))] {
+                self.regs.cfg2.modify(|_, w| {
+                    w.cpol().bit(mode.polarity as u8 != 0);
+                    w.cpha().bit(mode.phase as u8 != 0)
+                });
+                self.regs.cr1.modify(|_, w| w.spe().set_bit());
+            } else {
+                self.regs.cr1.modify(|_, w| {
+                    w.cpol().bit(mode.polarity as u8 != 0);
+                    w.cpha().bit(mode.phase as u8 != 0);
+                    w.spe().set_bit()
+                });
+            }
+        }
+
+        self.cfg.mode = mode;
+    }
+
     /// L44 RM, section 40.4.9: "Procedure for disabling the SPI"
     /// When SPI is disabled, it is mandatory to follow the disable procedures described in this
     /// paragraph. It is important to do this before the system enters a low-power mode when the
@@ -528,9 +684,36 @@ where
         }
     }
 
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    /// Set the FIFO reception threshold to match the word size. "The RXFIFO threshold must be aligned
+    /// to the read access size for the SPIx_DR register."
+    fn set_reception_thresh<W: SpiWord>(&mut self) {
+        #[cfg(not(feature = "f4"))]
+        {
+            let thresh = if W::BITS == 8 {
+                ReceptionThresh::D8
+            } else {
+                ReceptionThresh::D16
+            };
+
+            if thresh as u8 != self.cfg.fifo_reception_thresh as u8 {
+                self.regs
+                    .cr2
+                    .modify(|_, w| w.frxth().bit(thresh as u8 != 0));
+                self.cfg.fifo_reception_thresh = thresh;
+            }
+        }
+    }
+
     /// Read a single byte if available, or block until it's available.
     #[cfg(not(any(feature = "h5", feature = "h7")))]
     pub fn read(&mut self) -> Result<u8, SpiError> {
+        self.read_word()
+    }
+
+    /// Read a single word if available, or block until it's available. Use this for 16-bit words.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    pub fn read_word<W: SpiWord>(&mut self) -> Result<W, SpiError> {
         let sr = self.regs.sr.read();
         let crce = sr.crcerr().bit_is_set();
 
@@ -542,6 +725,15 @@ where
             return Err(SpiError::Crc);
         }
 
+        self.read_dr()
+    }
+
+    /// Block until RXNE is set, then read a word from the data register, without checking for
+    /// errors first.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    fn read_dr<W: SpiWord>(&mut self) -> Result<W, SpiError> {
+        self.set_reception_thresh::<W>();
+
         let mut i = 0;
         while !self.regs.sr.read().rxne().bit_is_set() {
             i += 1;
@@ -550,13 +742,19 @@ where
             }
         }
 
-        Ok(unsafe { ptr::read_volatile(&self.regs.dr as *const _ as *const u8) })
+        Ok(unsafe { ptr::read_volatile(&self.regs.dr as *const _ as *const W) })
     }
 
     /// Write a single byte if available, or block until it's available.
     /// See L44 RM, section 40.4.9: Data transmission and reception procedures.
     #[cfg(not(any(feature = "h5", feature = "h7")))]
     pub fn write_one(&mut self, byte: u8) -> Result<(), SpiError> {
+        self.write_word(byte)
+    }
+
+    /// Write a single word if available, or block until it's available. Use this for 16-bit words.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    pub fn write_word<W: SpiWord>(&mut self, word: W) -> Result<(), SpiError> {
         let sr = self.regs.sr.read();
         let crce = sr.crcerr().bit_is_set();
 
@@ -576,46 +774,133 @@ where
             }
         }
 
-        unsafe { ptr::write_volatile(&self.regs.dr as *const _ as *mut u8, byte) };
+        unsafe { ptr::write_volatile(&self.regs.dr as *const _ as *mut W, word) };
 
         Ok(())
     }
 
     /// Write multiple bytes on the SPI line, blocking until complete.
     /// See L44 RM, section 40.4.9: Data transmission and reception procedures.
-    #[cfg(not(any(feature = "h5", feature = "h7")))]
     pub fn write(&mut self, words: &[u8]) -> Result<(), SpiError> {
-        for word in words {
-            self.write_one(*word)?;
-            self.read()?;
-        }
-
-        Ok(())
+        self.write_words(words)
     }
 
     /// Read multiple bytes to a buffer, blocking until complete.
     /// See L44 RM, section 40.4.9: Data transmission and reception procedures.
+    pub fn transfer(&mut self, words: &mut [u8]) -> Result<(), SpiError> {
+        self.transfer_words(words)
+    }
+
+    /// Write multiple words on the SPI line, blocking until complete. Use this for 16-bit words,
+    /// or on H7, 32-bit ones.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    pub fn write_words<W: SpiWord>(&mut self, words: &[W]) -> Result<(), SpiError> {
+        // Nothing is sent, so there's no CRC to check.
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        self.crc_reset();
+
+        for (i, word) in words.iter().enumerate() {
+            self.write_word(*word)?;
+            if i == words.len() - 1 {
+                self.crc_next();
+                // The CRC may already have arrived, setting CRCERR; `crc_check` handles that.
+                self.read_dr::<W>()?;
+            } else {
+                self.read_word::<W>()?;
+            }
+        }
+
+        self.crc_check()
+    }
+
+    /// Read multiple words to a buffer, blocking until complete. Use this for 16-bit words,
+    /// or on H7, 32-bit ones.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    pub fn transfer_words<W: SpiWord>(&mut self, words: &mut [W]) -> Result<(), SpiError> {
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        self.crc_reset();
+
+        let len = words.len();
+        for (i, word) in words.iter_mut().enumerate() {
+            self.write_word(*word)?;
+            if i == len - 1 {
+                self.crc_next();
+                // The CRC may already have arrived, setting CRCERR; `crc_check` handles that.
+                *word = self.read_dr()?;
+            } else {
+                *word = self.read_word()?;
+            }
+        }
+
+        self.crc_check()
+    }
+
+    /// If CRC is enabled, reset its calculation before a transfer. "The CRC values are reset by
+    /// clearing and setting CRCEN"; this must be done while the SPI is disabled.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    fn crc_reset(&mut self) {
+        if self.cfg.crc.is_some() {
+            self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+            self.regs.cr1.modify(|_, w| w.crcen().clear_bit());
+            self.regs.cr1.modify(|_, w| w.crcen().set_bit());
+            // Clear an error left from a previous transfer, so it doesn't fail this one.
+            self.regs.sr.modify(|_, w| w.crcerr().clear_bit());
+            self.regs.cr1.modify(|_, w| w.spe().set_bit());
+        }
+    }
+
+    /// If CRC is enabled, set CRCNEXT after writing the last word of a transfer, so the CRC
+    /// is transmitted after it.
+    #[cfg(not(any(feature = "h5", feature = "h7")))]
+    fn crc_next(&mut self) {
+        if self.cfg.crc.is_some() {
+            self.regs.cr1.modify(|_, w| w.crcnext().set_bit());
+        }
+    }
+
+    /// If CRC is enabled, read the CRC received after the last word of a transfer, and check
+    /// it against the one calculated.
     #[cfg(not(any(feature = "h5", feature = "h7")))]
-    pub fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<(), SpiError> {
-        for word in words.iter_mut() {
-            self.write_one(*word)?;
-            *word = self.read()?;
+    fn crc_check(&mut self) -> Result<(), SpiError> {
+        if let Some(crc) = self.cfg.crc {
+            // The CRC received is placed in the RX FIFO; discard it. Don't check for errors first,
+            // so CRCERR is cleared below, and the FIFO drained, even if it's already set.
+            match crc.len {
+                CrcLen::L8 => {
+                    self.read_dr::<u8>()?;
+                }
+                CrcLen::L16 => {
+                    self.read_dr::<u16>()?;
+                }
+            }
+
+            if self.regs.sr.read().crcerr().bit_is_set() {
+                // "This flag is set by hardware and cleared by software writing 0."
+                self.regs.sr.modify(|_, w| w.crcerr().clear_bit());
+                return Err(SpiError::Crc);
+            }
         }
 
         Ok(())
     }
 
     #[cfg(any(feature = "h5", feature = "h7"))]
-    fn read_one(&mut self) -> Result<u8, SpiError> {
+    fn read_one<W: SpiWord>(&mut self) -> Result<W, SpiError> {
         // NOTE(read_volatile) read only 1 word
-        unsafe { Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const u8)) }
+        unsafe { Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const W)) }
     }
 
     #[cfg(any(feature = "h5", feature = "h7"))]
-    fn send(&mut self, word: u8) -> Result<(), SpiError> {
+    fn send<W: SpiWord>(&mut self, word: W) -> Result<(), SpiError> {
         // NOTE(write_volatile) see note above
         unsafe {
-            ptr::write_volatile(&self.regs.txdr as *const _ as *mut u8, word);
+            ptr::write_volatile(&self.regs.txdr as *const _ as *mut W, word);
         }
         // write CSTART to start a transaction in
         // master mode
@@ -625,7 +910,7 @@ where
     }
 
     #[cfg(any(feature = "h5", feature = "h7"))]
-    fn exchange_duplex(&mut self, word: u8) -> Result<u8, SpiError> {
+    fn exchange_duplex<W: SpiWord>(&mut self, word: W) -> Result<W, SpiError> {
         // todo DRY
         let sr = self.regs.sr.read();
 
@@ -640,8 +925,8 @@ where
         }
 
         unsafe {
-            ptr::write_volatile(&self.regs.txdr as *const _ as *mut _, word);
-            Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const u8))
+            ptr::write_volatile(&self.regs.txdr as *const _ as *mut W, word);
+            Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const W))
         }
         //
         // { // else if sr.txc().is_completed() {
@@ -667,7 +952,7 @@ where
     /// * Assumes the transaction has started (CSTART handled externally)
     /// * Assumes at least one word has already been written to the Tx FIFO
     #[cfg(any(feature = "h5", feature = "h7"))]
-    fn read_duplex(&mut self) -> Result<u8, SpiError> {
+    fn read_duplex<W: SpiWord>(&mut self) -> Result<W, SpiError> {
         // NOTE(read_volatile) read only 1 word
         // todo DRY
         let sr = self.regs.sr.read();
@@ -682,7 +967,7 @@ where
             return Err(SpiError::Crc);
         }
 
-        unsafe { Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const u8)) }
+        unsafe { Ok(ptr::read_volatile(&self.regs.rxdr as *const _ as *const W)) }
         // , { // else if sr.txc().is_completed()
         //         txc, is_completed,
         //         {
@@ -699,7 +984,7 @@ where
     }
 
     #[cfg(any(feature = "h5", feature = "h7"))]
-    pub fn write<'w>(&mut self, write_words: &'w [u8]) -> Result<(), SpiError> {
+    pub fn write_words<W: SpiWord>(&mut self, write_words: &[W]) -> Result<(), SpiError> {
         // Depth of FIFO to use. All current SPI implementations
         // have a FIFO depth of at least 8 (see RM0433 Rev 7
         // Table 409.) but pick 4 as a conservative value.
@@ -707,6 +992,7 @@ where
 
         // Fill the first half of the write FIFO
         let len = write_words.len();
+        self.start_transfer::<W>(len);
         let mut write = write_words.iter();
         for _ in 0..core::cmp::min(FIFO_WORDS, len) {
             self.send(*write.next().unwrap())?;
@@ -719,14 +1005,14 @@ where
 
         // Dummy read from the read FIFO
         for _ in 0..core::cmp::min(FIFO_WORDS, len) {
-            let _ = self.read_duplex()?;
+            let _ = self.read_duplex::<W>()?;
         }
 
-        Ok(())
+        self.end_transfer()
     }
 
     #[cfg(any(feature = "h5", feature = "h7"))]
-    pub fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<(), SpiError> {
+    pub fn transfer_words<W: SpiWord>(&mut self, words: &mut [W]) -> Result<(), SpiError> {
         // Depth of FIFO to use. All current SPI implementations
         // have a FIFO depth of at least 8 (see RM0433 Rev 7
         // Table 409.) but pick 4 as a conservative value.
@@ -734,6 +1020,7 @@ where
 
         // Fill the first half of the write FIFO
         let len = words.len();
+        self.start_transfer::<W>(len);
         for i in 0..core::cmp::min(FIFO_WORDS, len) {
             self.send(words[i])?;
         }
@@ -750,30 +1037,235 @@ where
             }
         }
 
+        self.end_transfer()
+    }
+
+    /// Set TSIZE to the length of a transfer, so the transaction ends (and the CRC is sent, if enabled)
+    /// after it. "When these bits are changed by software, the SPI has to be disabled." TSIZE counts
+    /// frames, not words; transfers longer than 65,535 frames use a TSIZE of 0; ie no EOT, or CRC.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn start_transfer<W: SpiWord>(&mut self, len: usize) {
+        let tsize = self.tsize::<W>(len);
+
+        self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+        self.set_fifo_thresh::<W>();
+        self.regs.cr2.modify(|_, w| w.tsize().bits(tsize));
+        self.regs.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Set the FIFO threshold to the number of frames packed into each word, so a word is only read once
+    /// all of its frames are received. "When the data packing mode is used, the FTHLV setting must be
+    /// a multiple of the packed frames." Sets CFG1 register, FTHLV field. The SPI must be disabled.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn set_fifo_thresh<W: SpiWord>(&mut self) {
+        let fthlv = self.frames_per_word::<W>() - 1;
+        self.regs.cfg1.modify(|_, w| w.fthlv().bits(fthlv));
+    }
+
+    /// The number of data frames packed into each word.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn frames_per_word<W: SpiWord>(&self) -> u8 {
+        let frame_bits = self.cfg.data_size as u8 + 1;
+        (W::BITS / frame_bits).max(1)
+    }
+
+    /// The TSIZE value for a transfer of `len` words, or 0 if it's too long.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn tsize<W: SpiWord>(&self, len: usize) -> u16 {
+        let frames = len * self.frames_per_word::<W>() as usize;
+        if frames > u16::MAX as usize {
+            0
+        } else {
+            frames as u16
+        }
+    }
+
+    /// If CRC is enabled, set TSIZE to the length of a DMA transfer, so the CRC is sent after it. The
+    /// SPI must be disabled.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn set_dma_tsize<W: SpiWord>(&mut self, len: usize) {
+        if self.cfg.crc.is_some() {
+            let tsize = self.tsize::<W>(len);
+            self.regs.cr2.modify(|_, w| w.tsize().bits(tsize));
+        }
+    }
+
+    /// Wait for the end of a transfer started with `start_transfer()`, check its CRC if enabled, and
+    /// reset TSIZE to 0, so operations that don't set it aren't ended early.
+    #[cfg(any(feature = "h5", feature = "h7"))]
+    fn end_transfer(&mut self) -> Result<(), SpiError> {
+        if self.regs.cr2.read().tsize().bits() == 0 {
+            return Ok(());
+        }
+
+        let mut i = 0;
+        while self.regs.sr.read().eot().bit_is_clear() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(SpiError::Hardware);
+            }
+        }
+
+        // The CRC received isn't placed in the RX FIFO; it's compared by hardware.
+        let crce = self.regs.sr.read().crce().bit_is_set();
+
+        self.regs.ifcr.write(|w| {
+            w.eotc().set_bit();
+            w.txtfc().set_bit();
+            w.crcec().set_bit()
+        });
+
+        self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+        self.regs.cr2.modify(|_, w| w.tsize().bits(0));
+        self.regs.cr1.modify(|_, w| w.spe().set_bit());
+
+        if crce {
+            return Err(SpiError::Crc);
+        }
+
         Ok(())
     }
 
     // todo: End H7xx HAL C+Ps
 
+    /// In half-duplex (3-wire) master mode, write words, then switch the data line's direction, and read
+    /// words into a buffer; eg to read a sensor's registers. Blocks until complete. Requires
+    /// `SpiCommMode::HalfDuplex`. Sets CR1 register, BIDIOE field. On H7, sets CR1 register, HDDIR field.
+    /// On H7, `write` and `read` must be 65,535 words or fewer.
+    pub fn write_then_read_half_duplex<W: SpiWord>(
+        &mut self,
+        write: &[W],
+        read: &mut [W],
+    ) -> Result<(), SpiError> {
+        cfg_if! {
+            if #[cfg(any(feature = "h5", feature = "h7"))] {
+                // "HDDIR: Rx/Tx direction at half-duplex mode. 0: SPI is receiver, 1: SPI is transmitter."
+                // The transaction ends once TSIZE words are transferred, so the clock stops
+                // without intervention.
+                if !write.is_empty() {
+                    self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+                    self.regs.cr1.modify(|_, w| w.hddir().set_bit());
+                    self.start_transfer::<W>(write.len());
+
+                    for word in write {
+                        let mut i = 0;
+                        while self.regs.sr.read().txp().bit_is_clear() {
+                            i += 1;
+                            if i >= MAX_ITERS {
+                                return Err(SpiError::Hardware);
+                            }
+                        }
+                        self.send(*word)?;
+                    }
+
+                    self.end_transfer()?;
+                }
+
+                if !read.is_empty() {
+                    self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+                    self.regs.cr1.modify(|_, w| w.hddir().clear_bit());
+                    self.start_transfer::<W>(read.len());
+                    self.regs.cr1.modify(|_, w| w.cstart().set_bit());
+
+                    for word in read.iter_mut() {
+                        let mut i = 0;
+                        while self.regs.sr.read().rxp().bit_is_clear() {
+                            i += 1;
+                            if i >= MAX_ITERS {
+                                return Err(SpiError::Hardware);
+                            }
+                        }
+                        *word = self.read_duplex()?;
+                    }
+
+                    self.end_transfer()?;
+                }
+
+                Ok(())
+            } else {
+                // "BIDIOE: Output enable in bidirectional mode. 0: Output disabled (receive-only mode)
+                // 1: Output enabled (transmit-only mode)"
+                self.regs.cr1.modify(|_, w| w.bidioe().set_bit());
+
+                for word in write {
+                    self.write_word(*word)?;
+                }
+
+                // Wait for the last frame to be sent before changing direction.
+                let mut i = 0;
+                #[cfg(not(feature = "f4"))]
+                while self.regs.sr.read().ftlvl().bits() != 0 {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(SpiError::Hardware);
+                    }
+                }
+                while self.regs.sr.read().bsy().bit_is_set() {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(SpiError::Hardware);
+                    }
+                }
+
+                if read.is_empty() {
+                    return Ok(());
+                }
+
+                // In master receive mode, the clock runs continuously while the SPI is enabled.
+                // L44 RM, section 40.4.9: "Procedure for disabling the SPI": "Interrupt the receive flow
+                // by disabling SPI (SPE=0) in the specific time window while the last data frame is
+                // ongoing." F4 RM, section 28.3.8: "Wait for the second to last occurrence of RXNE=1
+                // (n-1), then wait for one SPI clock cycle (using a software loop) before disabling the SPI
+                // (SPE=0). Then wait for the last RXNE=1 before entering the Halt mode."
+                self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+                self.set_reception_thresh::<W>();
+                self.regs.cr1.modify(|_, w| w.bidioe().clear_bit());
+                self.regs.cr1.modify(|_, w| w.spe().set_bit());
+
+                // One SPI clock, in PCLK cycles. We don't know the ratio of the core clock to PCLK here;
+                // this is padded to cover an APB prescaler up to 4, while staying inside the last frame.
+                let spi_clock_cycles = 2 << self.regs.cr1.read().br().bits();
+
+                let len = read.len();
+                for (i, word) in read.iter_mut().enumerate() {
+                    if i == len - 1 {
+                        cortex_m::asm::delay(spi_clock_cycles * 4);
+                        self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+                    }
+                    *word = self.read_word()?;
+                }
+
+                // Return to transmit mode before re-enabling, so the clock doesn't start.
+                self.regs.cr1.modify(|_, w| w.bidioe().set_bit());
+                self.regs.cr1.modify(|_, w| w.spe().set_bit());
+
+                Ok(())
+            }
+        }
+    }
+
     /// Transmit data using DMA. See L44 RM, section 40.4.9: Communication using DMA.
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
+    /// `dma_periph` selects the DMA controller to use; it replaces the `dma: &mut Dma<D>` argument
+    /// this took previously.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
-    pub unsafe fn write_dma<D>(
+    pub unsafe fn write_dma<W: SpiWord>(
         &mut self,
-        buf: &[u8],
+        buf: &[W],
         channel: DmaChannel,
         channel_cfg: ChannelCfg,
-        dma: &mut Dma<D>,
-    ) where
-        D: Deref<Target = dma_p::RegisterBlock>,
-    {
+        dma_periph: dma::DmaPeriph,
+    ) {
         // Static write and read buffers?
         let (ptr, len) = (buf.as_ptr(), buf.len());
 
         self.regs.cr1.modify(|_, w| w.spe().clear_bit());
 
-        // todo: Accept u16 words too.
+        #[cfg(not(any(feature = "h5", feature = "h7")))]
+        self.set_reception_thresh::<W>();
+
         // todo: Pri and Circular as args?
 
         // A DMA access is requested when the TXE or RXNE enable bit in the SPIx_CR2 register is
@@ -790,11 +1282,9 @@ where
 
         // 2. Enable DMA streams for Tx and Rx in DMA registers, if the streams are used.
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::write_chan();
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
-        #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::write_sel(dma_periph);
 
         #[cfg(any(feature = "h5", feature = "h7"))]
         let periph_addr = &self.regs.txdr as *const _ as u32;
@@ -806,16 +1296,47 @@ where
         #[cfg(not(any(feature = "h5", feature = "h7")))]
         let num_data = len as u16;
 
-        dma.cfg_channel(
-            channel,
-            periph_addr,
-            ptr as u32,
-            num_data,
-            dma::Direction::ReadFromMem,
-            dma::DataSize::S8,
-            dma::DataSize::S8,
-            channel_cfg,
-        );
+        #[cfg(any(feature = "h5", feature = "h7"))]
+        {
+            self.set_fifo_thresh::<W>();
+            self.set_dma_tsize::<W>(len);
+        }
+
+        match dma_periph {
+            dma::DmaPeriph::Dma1 => {
+                let mut regs = unsafe { &(*DMA1::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    channel,
+                    periph_addr,
+                    ptr as u32,
+                    num_data,
+                    dma::Direction::ReadFromMem,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
+                    channel_cfg,
+                );
+            }
+            #[cfg(not(any(
+                feature = "f3x4",
+                all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+                feature = "wb"
+            )))]
+            dma::DmaPeriph::Dma2 => {
+                let mut regs = unsafe { &(*pac::DMA2::ptr()) };
+                dma::cfg_channel(
+                    &mut regs,
+                    channel,
+                    periph_addr,
+                    ptr as u32,
+                    num_data,
+                    dma::Direction::ReadFromMem,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
+                    channel_cfg,
+                );
+            }
+        }
 
         // 3. Enable DMA Tx buffer in the TXDMAEN bit in the SPI_CR2 register, if DMA Tx is used.
         #[cfg(not(feature = "h7"))]
@@ -830,30 +1351,31 @@ where
     /// Receive data using DMA. See L44 RM, section 40.4.9: Communication using DMA.
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
-    pub unsafe fn read_dma(
+    pub unsafe fn read_dma<W: SpiWord>(
         &mut self,
-        buf: &mut [u8],
+        buf: &mut [W],
         channel: DmaChannel,
         channel_cfg: ChannelCfg,
         dma_periph: dma::DmaPeriph,
     ) {
-        // todo: Accept u16 words too.
         let (ptr, len) = (buf.as_mut_ptr(), buf.len());
 
         self.regs.cr1.modify(|_, w| w.spe().clear_bit());
 
+        #[cfg(not(any(feature = "h5", feature = "h7")))]
+        self.set_reception_thresh::<W>();
+
         #[cfg(not(any(feature = "h5", feature = "h7")))]
         self.regs.cr2.modify(|_, w| w.rxdmaen().set_bit());
         #[cfg(any(feature = "h5", feature = "h7"))]
         self.regs.cfg1.modify(|_, w| w.rxdmaen().set_bit());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::read_chan();
-        #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::read_sel(dma_periph);
 
         #[cfg(any(feature = "h5", feature = "h7"))]
         let periph_addr = &self.regs.rxdr as *const _ as u32;
@@ -865,6 +1387,12 @@ where
         #[cfg(not(any(feature = "h5", feature = "h7")))]
         let num_data = len as u16;
 
+        #[cfg(any(feature = "h5", feature = "h7"))]
+        {
+            self.set_fifo_thresh::<W>();
+            self.set_dma_tsize::<W>(len);
+        }
+
         match dma_periph {
             dma::DmaPeriph::Dma1 => {
                 let mut regs = unsafe { &(*DMA1::ptr()) };
@@ -875,12 +1403,16 @@ where
                     ptr as u32,
                     num_data,
                     dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg,
                 );
             }
-            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
+            #[cfg(not(any(
+                feature = "f3x4",
+                all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+                feature = "wb"
+            )))]
             dma::DmaPeriph::Dma2 => {
                 let mut regs = unsafe { &(*pac::DMA2::ptr()) };
                 dma::cfg_channel(
@@ -890,8 +1422,8 @@ where
                     ptr as u32,
                     num_data,
                     dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg,
                 );
             }
@@ -903,10 +1435,10 @@ where
     /// Transfer data from DMA; this is the basic reading API, using both write and read transfers:
     /// It performs a write with register data, and reads to a buffer.
     #[cfg(not(any(feature = "f4", feature = "l552")))]
-    pub unsafe fn transfer_dma(
+    pub unsafe fn transfer_dma<W: SpiWord>(
         &mut self,
-        buf_write: &[u8],
-        buf_read: &mut [u8],
+        buf_write: &[W],
+        buf_read: &mut [W],
         channel_write: DmaChannel,
         channel_read: DmaChannel,
         channel_cfg_write: ChannelCfg,
@@ -917,12 +1449,14 @@ where
         // where
         // D: Deref<Target = dma_p::RegisterBlock>,
         // {
-        // todo: Accept u16 words too.
         let (ptr_write, len_write) = (buf_write.as_ptr(), buf_write.len());
         let (ptr_read, len_read) = (buf_read.as_mut_ptr(), buf_read.len());
 
         self.regs.cr1.modify(|_, w| w.spe().clear_bit());
 
+        #[cfg(not(any(feature = "h5", feature = "h7")))]
+        self.set_reception_thresh::<W>();
+
         // todo: DRY here, with `write_dma`, and `read_dma`.
 
         #[cfg(any(feature = "h5", feature = "h7"))]
@@ -945,6 +1479,12 @@ where
         #[cfg(any(feature = "h5", not(feature = "h7")))]
         let num_data_read = len_read as u16;
 
+        #[cfg(any(feature = "h5", feature = "h7"))]
+        {
+            self.set_fifo_thresh::<W>();
+            self.set_dma_tsize::<W>(len_read);
+        }
+
         // Be careful - order of enabling Rx and Tx may matter, along with other things like when we
         // enable the channels, and the SPI periph.
         #[cfg(not(any(feature = "h5", feature = "h7")))]
@@ -953,18 +1493,14 @@ where
         self.regs.cfg1.modify(|_, w| w.rxdmaen().set_bit());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel_write = R::write_chan();
-        #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
+        let channel_write = R::write_chan(dma_periph).unwrap_or(channel_write);
         #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::write_sel(dma_periph);
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel_read = R::read_chan();
+        let channel_read = R::read_chan(dma_periph).unwrap_or(channel_read);
         #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
-        #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::read_sel(dma_periph);
         match dma_periph {
             dma::DmaPeriph::Dma1 => {
                 let mut regs = unsafe { &(*DMA1::ptr()) };
@@ -975,8 +1511,8 @@ where
                     ptr_write as u32,
                     num_data_write,
                     dma::Direction::ReadFromMem,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg_write,
                 );
 
@@ -987,12 +1523,16 @@ where
                     ptr_read as u32,
                     num_data_read,
                     dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg_read,
                 );
             }
-            #[cfg(not(any(feature = "f3x4", feature = "g0", feature = "wb")))]
+            #[cfg(not(any(
+                feature = "f3x4",
+                all(feature = "g0", not(any(feature = "g0b1", feature = "g0c1"))),
+                feature = "wb"
+            )))]
             dma::DmaPeriph::Dma2 => {
                 let mut regs = unsafe { &(*pac::DMA2::ptr()) };
                 dma::cfg_channel(
@@ -1002,8 +1542,8 @@ where
                     ptr_write as u32,
                     num_data_write,
                     dma::Direction::ReadFromMem,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg_write,
                 );
 
@@ -1014,8 +1554,8 @@ where
                     ptr_read as u32,
                     num_data_read,
                     dma::Direction::ReadFromPeriph,
-                    dma::DataSize::S8,
-                    dma::DataSize::S8,
+                    dma_size::<W>(),
+                    dma_size::<W>(),
                     channel_cfg_read,
                 );
             }
@@ -1029,6 +1569,206 @@ where
         self.regs.cr1.modify(|_, w| w.spe().set_bit());
     }
 
+    /// In slave mode, arm a transaction using DMA: Data from `buf_write` is sent, and data received into
+    /// `buf_read`, while the master selects this device using NSS. When the master releases NSS, call
+    /// `slave_dma_stop()`, then call this again to re-arm for the next transaction; or call
+    /// `slave_dma_on_nss_edge()`, which does both. Detect this using an EXTI interrupt on the rising
+    /// edge of the NSS pin. On H7, the EOT interrupt is also enabled; it fires when `buf_read` is full.
+    /// Note that the `channel` arguments are unused on F3 and L4, since they're hard-coded,
+    /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// They're only used there if this peripheral isn't connected to `dma_periph`.
+    #[cfg(not(any(feature = "f4", feature = "l552")))]
+    pub unsafe fn slave_dma_start<W: SpiWord>(
+        &mut self,
+        buf_write: &[W],
+        buf_read: &mut [W],
+        channel_write: DmaChannel,
+        channel_read: DmaChannel,
+        channel_cfg_write: ChannelCfg,
+        channel_cfg_read: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        #[cfg(feature = "h7")]
+        {
+            self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+            // "TSIZE[15:0]: Number of data at current transfer. When these bits are changed by software,
+            // the SPI has to be disabled." EOT is set once this many frames are transferred.
+            let tsize = self.tsize::<W>(buf_read.len());
+            self.regs.cr2.modify(|_, w| w.tsize().bits(tsize));
+
+            self.clear_interrupt(SpiInterrupt::EotSuspTxc);
+            self.enable_interrupt(SpiInterrupt::EotSuspTxc);
+        }
+
+        self.transfer_dma(
+            buf_write,
+            buf_read,
+            channel_write,
+            channel_read,
+            channel_cfg_write,
+            channel_cfg_read,
+            dma_periph,
+        );
+    }
+
+    /// In slave mode, end a transaction started with `slave_dma_start()`; call this when the master
+    /// releases NSS, or on H7, from the EOT interrupt. This stops DMA, disables the SPI, and discards
+    /// data remaining in its FIFOs. Other than on H7, this resets the peripheral using RCC to flush
+    /// the TX FIFO, then restores its configuration. `buf_read_len` is the length of the read buffer
+    /// passed to `slave_dma_start()`. Returns the number of words received into it.
+    #[cfg(not(any(feature = "f4", feature = "l552")))]
+    pub fn slave_dma_stop(
+        &mut self,
+        buf_read_len: usize,
+        channel_write: DmaChannel,
+        channel_read: DmaChannel,
+        dma_periph: dma::DmaPeriph,
+    ) -> usize {
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel_read = R::read_chan(dma_periph).unwrap_or(channel_read);
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel_write = R::write_chan(dma_periph).unwrap_or(channel_write);
+
+        let remaining = dma::transfers_remaining(dma_periph, channel_read) as usize;
+
+        self.stop_dma(channel_write, Some(channel_read), dma_periph);
+
+        // We don't use `disable()` here, since it waits for the TX FIFO to empty; in slave mode,
+        // this doesn't happen if the master ends the transaction early.
+        self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+
+        cfg_if! {
+            if #[cfg(feature = "h7")] {
+                // Disabling the SPI flushes its FIFOs.
+                self.clear_interrupt(SpiInterrupt::EotSuspTxc);
+            } else {
+                // Disabling the SPI doesn't flush its TX FIFO; data left there would be sent at the
+                // start of the next transaction. The RM's procedure for disabling the SPI notes the
+                // FIFOs can only be flushed by resetting the peripheral through RCC, so do that, and
+                // restore its configuration. This also discards the RX FIFO's contents.
+                let cr1 = self.regs.cr1.read().bits();
+                let cr2 = self.regs.cr2.read().bits();
+                let crcpr = self.regs.crcpr.read().bits();
+
+                cortex_m::interrupt::free(|_| {
+                    let rcc = unsafe { &(*RCC::ptr()) };
+                    R::en_reset(rcc);
+                });
+
+                self.regs.crcpr.write(|w| unsafe { w.bits(crcpr) });
+                self.regs.cr2.write(|w| unsafe { w.bits(cr2) });
+                self.regs.cr1.write(|w| unsafe { w.bits(cr1) });
+            }
+        }
+
+        buf_read_len.saturating_sub(remaining)
+    }
+
+    /// In slave mode, re-arm DMA at the end of each transaction. Call this from the EXTI interrupt for
+    /// the rising edge of the NSS pin; eg set up with `nss_pin.enable_interrupt(Edge::Rising)`. This
+    /// ends the transaction started with `slave_dma_start()` or a previous call to this, using
+    /// `slave_dma_stop()`, then arms the next one with `slave_dma_start()`. `prev_read_len` is the
+    /// length of the previous read buffer. Returns the number of words received into it.
+    ///
+    /// To process received data while the next transaction is in progress, alternate between two
+    /// read buffers. Otherwise, process the data before the master selects this device again.
+    #[cfg(not(any(feature = "f4", feature = "l552")))]
+    pub unsafe fn slave_dma_on_nss_edge<W: SpiWord>(
+        &mut self,
+        prev_read_len: usize,
+        buf_write: &[W],
+        buf_read: &mut [W],
+        channel_write: DmaChannel,
+        channel_read: DmaChannel,
+        channel_cfg_write: ChannelCfg,
+        channel_cfg_read: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> usize {
+        let received = self.slave_dma_stop(prev_read_len, channel_write, channel_read, dma_periph);
+
+        self.slave_dma_start(
+            buf_write,
+            buf_read,
+            channel_write,
+            channel_read,
+            channel_cfg_write,
+            channel_cfg_read,
+            dma_periph,
+        );
+
+        received
+    }
+
+    /// Transmit data using DMA, without `unsafe`: The returned `Transfer` owns this peripheral and
+    /// the buffer until the transfer completes or is aborted. Run `stop_dma()` on the returned
+    /// peripheral after that. Otherwise, the same as `write_dma()`.
+    #[cfg(not(any(feature = "f4", feature = "l552")))]
+    pub fn write_dma_transfer<W: SpiWord, B: ReadBuffer<Word = W>>(
+        mut self,
+        buf: B,
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> Transfer<Self, B> {
+        let (ptr, len) = buf.read_buffer();
+
+        // A circular transfer never completes, so `Transfer::wait()` would block forever.
+        let channel_cfg = ChannelCfg {
+            circular: Circular::Disabled,
+            ..channel_cfg
+        };
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
+
+        // The buffer is owned by the transfer, and `'static` or pinned, so stays valid while DMA
+        // uses it.
+        unsafe {
+            self.write_dma(
+                core::slice::from_raw_parts(ptr, len),
+                channel,
+                channel_cfg,
+                dma_periph,
+            );
+        }
+
+        Transfer::new(self, buf, channel, dma_periph)
+    }
+
+    /// Receive data using DMA, without `unsafe`: The returned `Transfer` owns this peripheral and
+    /// the buffer until the transfer completes or is aborted. Run `stop_dma()` on the returned
+    /// peripheral after that. Otherwise, the same as `read_dma()`.
+    #[cfg(not(any(feature = "f4", feature = "l552")))]
+    pub fn read_dma_transfer<W: SpiWord, B: WriteBuffer<Word = W>>(
+        mut self,
+        mut buf: B,
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> Transfer<Self, B> {
+        let (ptr, len) = buf.write_buffer();
+
+        // A circular transfer never completes, so `Transfer::wait()` would block forever.
+        let channel_cfg = ChannelCfg {
+            circular: Circular::Disabled,
+            ..channel_cfg
+        };
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+
+        unsafe {
+            self.read_dma(
+                core::slice::from_raw_parts_mut(ptr, len),
+                channel,
+                channel_cfg,
+                dma_periph,
+            );
+        }
+
+        Transfer::new(self, buf, channel, dma_periph)
+    }
+
     /// Stop a DMA transfer. Stops the channel, and disables the `txdmaen` and `rxdmaen` bits.
     /// Run this after each transfer completes - you may wish to do this in an interrupt
     /// (eg DMA transfer complete) instead of blocking. `channel2` is an optional second channel
@@ -1068,6 +1808,18 @@ where
             w.txdmaen().clear_bit();
             w.rxdmaen().clear_bit()
         });
+
+        // If TSIZE was set for CRC, reset it, so operations that don't set it aren't ended early.
+        #[cfg(feature = "h7")]
+        if self.regs.cr2.read().tsize().bits() != 0 {
+            self.regs.cr1.modify(|_, w| w.spe().clear_bit());
+            self.regs.ifcr.write(|w| {
+                w.eotc().set_bit();
+                w.txtfc().set_bit()
+            });
+            self.regs.cr2.modify(|_, w| w.tsize().bits(0));
+            self.regs.cr1.modify(|_, w| w.spe().set_bit());
+        }
     }
 
     #[cfg(not(any(feature = "h5", feature = "h7")))]
@@ -1126,6 +1878,124 @@ where
     }
 }
 
+#[cfg(not(any(feature = "f4", feature = "l552")))]
+/// The DMA transfer size matching an SPI word.
+fn dma_size<W: SpiWord>() -> dma::DataSize {
+    match W::BITS {
+        8 => dma::DataSize::S8,
+        16 => dma::DataSize::S16,
+        _ => dma::DataSize::S32,
+    }
+}
+
+/// An SPI bus shared by multiple devices, each with its own CS pin, mode, and baud rate. Perform
+/// transactions using `SpiDevice`, passing this bus to its methods. To share the bus between tasks,
+/// store it in a `Mutex<RefCell<Option<>>>` global (see `make_globals!`), and access it in a critical
+/// section, or use an RTIC shared resource, and access it with `lock`.
+pub struct SpiBus<R> {
+    pub spi: Spi<R>,
+    /// The baud rate currently configured on the peripheral.
+    baud_rate: BaudRate,
+}
+
+impl<R> SpiBus<R>
+where
+    R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+{
+    /// Create a shared bus from an initialized SPI peripheral. `baud_rate` is the one it was
+    /// initialized with.
+    pub fn new(spi: Spi<R>, baud_rate: BaudRate) -> Self {
+        Self { spi, baud_rate }
+    }
+
+    /// Create a handle for a device on this bus. `cs` must be configured as an output; it's set
+    /// high (deasserted) here.
+    pub fn new_device(&self, cs: Pin, mode: SpiModeType, baud_rate: BaudRate) -> SpiDevice {
+        SpiDevice::new(cs, mode, baud_rate)
+    }
+
+    /// Reconfigure the peripheral for a device's mode and baud rate, if they differ from the
+    /// current ones.
+    fn configure(&mut self, mode: SpiModeType, baud_rate: BaudRate) {
+        if mode.polarity as u8 != self.spi.cfg.mode.polarity as u8
+            || mode.phase as u8 != self.spi.cfg.mode.phase as u8
+        {
+            self.spi.set_mode(mode);
+        }
+
+        if baud_rate as u8 != self.baud_rate as u8 {
+            self.spi.reclock(baud_rate);
+            self.baud_rate = baud_rate;
+        }
+    }
+
+    /// Release the SPI peripheral.
+    pub fn free(self) -> Spi<R> {
+        self.spi
+    }
+}
+
+/// A device on a shared SPI bus. Owns the device's CS pin, and the mode and baud rate it's used
+/// with. (See `SpiBus`)
+pub struct SpiDevice {
+    pub cs: Pin,
+    pub mode: SpiModeType,
+    pub baud_rate: BaudRate,
+}
+
+impl SpiDevice {
+    /// Create a handle for a device. `cs` must be configured as an output; it's set high (deasserted) here.
+    pub fn new(mut cs: Pin, mode: SpiModeType, baud_rate: BaudRate) -> Self {
+        cs.set_high();
+
+        Self {
+            cs,
+            mode,
+            baud_rate,
+        }
+    }
+
+    /// Perform a transaction with this device: Configure the bus for its mode and baud rate, assert CS,
+    /// run `f`, then deassert CS. CS is deasserted even if `f` returns an error. Blocking reads and
+    /// writes in `f` complete before returning, so CS isn't deasserted while a word is being clocked.
+    pub fn transaction<R, T>(
+        &mut self,
+        bus: &mut SpiBus<R>,
+        f: impl FnOnce(&mut Spi<R>) -> Result<T, SpiError>,
+    ) -> Result<T, SpiError>
+    where
+        R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+    {
+        bus.configure(self.mode, self.baud_rate);
+
+        self.cs.set_low();
+        let result = f(&mut bus.spi);
+        self.cs.set_high();
+
+        result
+    }
+
+    /// Write multiple words to this device, blocking until complete.
+    pub fn write<R, W: SpiWord>(&mut self, bus: &mut SpiBus<R>, words: &[W]) -> Result<(), SpiError>
+    where
+        R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+    {
+        self.transaction(bus, |spi| spi.write_words(words))
+    }
+
+    /// Write multiple words to this device, replacing them with the words read, blocking until complete.
+    pub fn transfer<R, W: SpiWord>(
+        &mut self,
+        bus: &mut SpiBus<R>,
+        words: &mut [W],
+    ) -> Result<(), SpiError>
+    where
+        R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+    {
+        self.transaction(bus, |spi| spi.transfer_words(words))
+    }
+}
+
 #[cfg(feature = "embedded_hal")]
 impl<R> FullDuplex<u8> for Spi<R>
 where
@@ -1159,3 +2029,38 @@ impl<R> embedded_hal::blocking::spi::write::Default<u8> for Spi<R> where
     R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph
 {
 }
+
+// H7 doesn't have the single-word `read` and `write_one` used by these.
+#[cfg(all(feature = "embedded_hal", not(any(feature = "h5", feature = "h7"))))]
+impl<R> FullDuplex<u16> for Spi<R>
+where
+    R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph,
+{
+    type Error = SpiError;
+
+    fn read(&mut self) -> nb::Result<u16, SpiError> {
+        match Spi::read_word(self) {
+            Ok(r) => Ok(r),
+            Err(e) => Err(nb::Error::Other(e)),
+        }
+    }
+
+    fn send(&mut self, word: u16) -> nb::Result<(), SpiError> {
+        match Spi::write_word(self, word) {
+            Ok(r) => Ok(r),
+            Err(e) => Err(nb::Error::Other(e)),
+        }
+    }
+}
+
+#[cfg(all(feature = "embedded_hal", not(any(feature = "h5", feature = "h7"))))]
+impl<R> embedded_hal::blocking::spi::transfer::Default<u16> for Spi<R> where
+    R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph
+{
+}
+
+#[cfg(all(feature = "embedded_hal", not(any(feature = "h5", feature = "h7"))))]
+impl<R> embedded_hal::blocking::spi::write::Default<u16> for Spi<R> where
+    R: Deref<Target = pac::spi1::RegisterBlock> + RccPeriph
+{
+}
@@ -9,6 +9,9 @@
 
 use crate::{
     clocks::Clocks,
+    iso7816,
+    lin::{self, ChecksumModel},
+    modbus,
     pac::{self, RCC},
     util::{BaudPeriph, RccPeriph},
     MAX_ITERS,
@@ -19,7 +22,9 @@ use core::ops::Deref;
 use cortex_m::interrupt::free;
 
 #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
-use crate::dma::{self, ChannelCfg, DmaChannel};
+use crate::dma::{
+    self, ChannelCfg, Circular, DmaChannel, DmaInterrupt, ReadBuffer, Transfer, WriteBuffer,
+};
 
 #[cfg(any(feature = "f3", feature = "l4"))]
 use crate::dma::DmaInput;
@@ -39,6 +44,12 @@ use nb;
 
 use cfg_if::cfg_if;
 
+/// The depth of the TX and RX FIFOs, in words.
+#[cfg(feature = "h7")]
+const FIFO_DEPTH: usize = 16;
+#[cfg(all(feature = "usart_fifo", not(feature = "h7")))]
+const FIFO_DEPTH: usize = 8;
+
 // todo: Prescaler (USART_PRESC) register on v3 (L5, G, H etc)
 
 #[derive(Clone, Copy)]
@@ -97,6 +108,125 @@ pub enum IrdaMode {
     LowPower,
 }
 
+#[cfg(feature = "usart_fifo")]
+#[derive(Clone, Copy)]
+#[repr(u8)]
+/// The FIFO level at which the TXFT or RXFT flag is set, and its interrupt fired if enabled.
+/// (USART_CR3, TXFTCFG and RXFTCFG)
+pub enum FifoThresh {
+    /// 1/8 of the FIFO depth.
+    Eighth = 0b000,
+    /// 1/4 of the FIFO depth.
+    Quarter = 0b001,
+    /// 1/2 of the FIFO depth.
+    Half = 0b010,
+    /// 3/4 of the FIFO depth.
+    ThreeQuarters = 0b011,
+    /// 7/8 of the FIFO depth.
+    SevenEighths = 0b100,
+    /// The FIFO is full (RX), or empty (TX).
+    Full = 0b101,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+/// Hardware flow control. (USART_CR3, RTSE and CTSE). Configure the RTS and CTS pins in their U[S]ART
+/// alternate function; eg AF7 for USART1-3 on most families. (On USART1, this is usually PA11 for CTS,
+/// and PA12 for RTS). Note that not all U[S]ARTs support this; see the RM. See G4 RM, section 37.5.21:
+/// RS232 hardware flow control and RS485 Driver Enable.
+pub enum FlowControl {
+    /// No flow control.
+    None,
+    /// RTS is asserted (low) when there's space to receive data, and de-asserted at the end of the
+    /// current character when the receive register (or FIFO) is full.
+    Rts,
+    /// Transmission only starts, or continues to the next character, when CTS is asserted (low).
+    Cts,
+    /// Both RTS and CTS flow control.
+    RtsCts,
+}
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+/// The length of break to detect in LIN mode. (USART_CR2, LBDL)
+pub enum LinBreakLen {
+    /// 10-bit break detection
+    B10 = 0,
+    /// 11-bit break detection
+    B11 = 1,
+}
+
+#[cfg(not(feature = "f4"))]
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+/// Polarity of the RS-485 Driver Enable (DE) signal. (USART_CR3, DEP)
+pub enum DePolarity {
+    /// DE signal is active high.
+    ActiveHigh = 0,
+    /// DE signal is active low.
+    ActiveLow = 1,
+}
+
+#[cfg(not(feature = "f4"))]
+#[derive(Clone, Copy)]
+/// Configuration for hardware RS-485 Driver Enable (DE) control. When set, the DE signal
+/// is output on the RTS pin; configure that pin's alternate function accordingly.
+/// See G4 RM, section 37.5.21: RS232 hardware flow control and RS485 Driver Enable.
+pub struct Rs485Config {
+    /// Polarity of the DE signal. Defaults to active high.
+    pub polarity: DePolarity,
+    /// Time between the activation of the DE signal, and the beginning of the start bit, in bit-times.
+    /// (USART_CR1, DEAT). Limited to 31 sample times; ie 1.9375 bit-times with 16x oversampling, or
+    /// 3.875 bit-times with 8x oversampling. Defaults to 1.
+    pub assertion_time: f32,
+    /// Time between the end of the last stop bit of the last frame transmitted, and the
+    /// de-activation of the DE signal, in bit-times. (USART_CR1, DEDT). Same limits as
+    /// `assertion_time`. Defaults to 1.
+    pub deassertion_time: f32,
+}
+
+#[cfg(not(feature = "f4"))]
+impl Default for Rs485Config {
+    fn default() -> Self {
+        Self {
+            polarity: DePolarity::ActiveHigh,
+            assertion_time: 1.,
+            deassertion_time: 1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+/// Configuration for Smartcard (ISO 7816) mode. The card's clock is output on the CK pin, and
+/// data is exchanged on the TX pin, which must be configured as open-drain, with a pull-up.
+/// See G4 RM, section 37.5.16: USART Smartcard mode.
+pub struct SmartcardConfig {
+    /// The clock frequency to output to the card, in Hz. It's derived from the USART kernel clock,
+    /// divided by an even number between 2 and 62. Defaults to 4Mhz.
+    pub card_clock: u32,
+    /// Guard time, in bit durations (ETUs). (USART_GTPR, GT). The TC flag is set this long after the
+    /// end of transmission. Add the extra guard time indicated in the card's ATR. Defaults to 16.
+    pub guard_time: u8,
+    /// Send a NACK on the I/O line when a parity error is received, requesting the card repeat
+    /// the character. (USART_CR3, NACK). Defaults to `true`.
+    pub nack: bool,
+    #[cfg(not(feature = "f4"))]
+    /// The number of times to automatically retransmit a character the card has NACKed, from
+    /// 0 to 7. (USART_CR3, SCARCNT). Defaults to 3.
+    pub auto_retry_count: u8,
+}
+
+impl Default for SmartcardConfig {
+    fn default() -> Self {
+        Self {
+            card_clock: 4_000_000,
+            guard_time: 16,
+            nack: true,
+            #[cfg(not(feature = "f4"))]
+            auto_retry_count: 3,
+        }
+    }
+}
+
 #[cfg(not(feature = "f4"))]
 #[derive(Clone, Copy)]
 /// The type of USART interrupt to configure. Reference the USART_ISR register.
@@ -105,6 +235,7 @@ pub enum UsartInterrupt {
     /// the character to match on `enable_interrupt`. The option's value doesn't
     /// affect anything when stopping or clearing interrupts.
     CharDetect(Option<u8>),
+    /// The CTS input changed state. Read its state with `Usart::cts_asserted()`.
     Cts,
     EndOfBlock,
     Idle,
@@ -114,6 +245,12 @@ pub enum UsartInterrupt {
     ParityError,
     ReadNotEmpty,
     ReceiverTimeout,
+    #[cfg(feature = "usart_fifo")]
+    /// The RX FIFO reached the threshold set by `rx_fifo_thresh` in the config. (RXFT)
+    RxFifoThreshold,
+    #[cfg(feature = "usart_fifo")]
+    /// The TX FIFO reached the threshold set by `tx_fifo_thresh` in the config. (TXFT)
+    TxFifoThreshold,
     #[cfg(not(any(feature = "f3", feature = "l4")))] // todo: PAC ommission?
     Tcbgt,
     TransmissionComplete,
@@ -132,12 +269,30 @@ pub struct UsartConfig {
     pub parity: Parity,
     /// IrDA mode: Enables this protocol, which is used to communicate with IR devices.
     pub irda_mode: IrdaMode,
-    #[cfg(any(feature = "g4", feature = "h7"))] // todo: Which others have FIFO?
-    /// The first-in, first-out buffer is enabled. Defaults to enabled.
+    #[cfg(feature = "usart_fifo")]
+    /// The first-in, first-out buffer is enabled. When enabled, `read()` and `write()` transfer
+    /// data in FIFO-sized bursts. Defaults to enabled. Instances without a FIFO, eg USART3 and USART4
+    /// on G0, don't implement FIFOEN; on these, `Usart::new()` clears this.
     pub fifo_enabled: bool,
+    #[cfg(feature = "usart_fifo")]
+    /// The TX FIFO level that sets the TXFT flag. Defaults to 1/8.
+    pub tx_fifo_thresh: FifoThresh,
+    #[cfg(feature = "usart_fifo")]
+    /// The RX FIFO level that sets the RXFT flag. Defaults to 1/8.
+    pub rx_fifo_thresh: FifoThresh,
     #[cfg(not(feature = "f4"))]
     /// Optionally, disable the overrun functionality. Defaults to `false`.
     pub overrun_disabled: bool,
+    #[cfg(not(feature = "f4"))]
+    /// Optionally, enable hardware RS-485 Driver Enable on the RTS pin. Defaults to `None`.
+    pub rs485: Option<Rs485Config>,
+    /// Hardware flow control, using the RTS and CTS pins. This can't be used with RTS
+    /// if RS-485 Driver Enable is used. Defaults to none.
+    pub flow_control: FlowControl,
+    /// The maximum number of status register reads blocking transmit functions wait for while CTS
+    /// holds off transmission, before returning `Error::CtsTimeout`. Defaults to 100 times the
+    /// limit used for other waits.
+    pub cts_timeout: u32,
 }
 
 impl Default for UsartConfig {
@@ -148,10 +303,18 @@ impl Default for UsartConfig {
             oversampling: OverSampling::O16,
             parity: Parity::Disabled,
             irda_mode: IrdaMode::None,
-            #[cfg(any(feature = "g4", feature = "h7"))]
+            #[cfg(feature = "usart_fifo")]
             fifo_enabled: true,
+            #[cfg(feature = "usart_fifo")]
+            tx_fifo_thresh: FifoThresh::Eighth,
+            #[cfg(feature = "usart_fifo")]
+            rx_fifo_thresh: FifoThresh::Eighth,
             #[cfg(not(feature = "f4"))]
             overrun_disabled: false,
+            #[cfg(not(feature = "f4"))]
+            rs485: None,
+            flow_control: FlowControl::None,
+            cts_timeout: MAX_ITERS * 100,
         }
     }
 }
@@ -192,6 +355,83 @@ macro_rules! isr {
     };
 }
 
+// The LPUART's register block is a separate type on most PACs, but its fields match the USART's.
+// These macros are shared with the `lpuart` module.
+
+/// Block until a status flag is set (or cleared), returning `Error::Hardware` if this takes too long.
+macro_rules! wait_flag {
+    ($regs:expr, $flag:ident) => {
+        let mut i = 0;
+        while isr!($regs).read().$flag().bit_is_clear() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::Hardware);
+            }
+        }
+    };
+    ($regs:expr, $flag:ident, clear) => {
+        let mut i = 0;
+        while isr!($regs).read().$flag().bit_is_set() {
+            i += 1;
+            if i >= MAX_ITERS {
+                return Err(Error::Hardware);
+            }
+        }
+    };
+}
+
+/// Run a block with the peripheral disabled, since some fields can only be written while UE is
+/// clear, then re-enable it if it was originally enabled. Evaluates to the block's value.
+macro_rules! while_disabled {
+    ($regs:expr, $body:block) => {{
+        let originally_enabled = cr1!($regs).read().ue().bit_is_set();
+
+        if originally_enabled {
+            cr1!($regs).modify(|_, w| w.ue().clear_bit());
+            let mut i = 0;
+            while cr1!($regs).read().ue().bit_is_set() {
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(Error::Hardware);
+                }
+            }
+        }
+
+        let result = $body;
+
+        if originally_enabled {
+            cr1!($regs).modify(|_, w| w.ue().set_bit());
+        }
+
+        result
+    }};
+}
+
+/// Block until a transmit status flag is set. Returns `Error::Hardware` if this takes too long,
+/// not counting time spent with transmission held off by CTS flow control; that's limited to
+/// `cts_timeout` status reads separately, after which this returns `Error::CtsTimeout`.
+macro_rules! wait_tx_flag {
+    ($self:ident, $flag:ident) => {
+        let mut i = 0;
+        let mut i_cts = 0;
+        while isr!($self.regs).read().$flag().bit_is_clear() {
+            if $self.cts_blocked() {
+                i_cts += 1;
+                if i_cts >= $self.config.cts_timeout {
+                    return Err(Error::CtsTimeout);
+                }
+            } else {
+                i += 1;
+                if i >= MAX_ITERS {
+                    return Err(Error::Hardware);
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use {cr1, isr, wait_flag, while_disabled};
+
 /// Represents the USART peripheral, for serial communications.
 pub struct Usart<R> {
     pub regs: R,
@@ -252,12 +492,36 @@ where
             .cr3
             .modify(|_, w| w.ovrdis().bit(result.config.overrun_disabled));
 
+        // Driver Enable fields can only be written when the USART is disabled.
+        #[cfg(not(feature = "f4"))]
+        if let Some(rs485) = result.config.rs485 {
+            result.set_rs485(&rs485);
+        }
+
+        // RTSE and CTSE can only be written when the USART is disabled.
+        let flow_control = result.config.flow_control;
+        result.regs.cr3.modify(|_, w| {
+            w.rtse()
+                .bit(flow_control == FlowControl::Rts || flow_control == FlowControl::RtsCts);
+            w.ctse()
+                .bit(flow_control == FlowControl::Cts || flow_control == FlowControl::RtsCts)
+        });
+
         // Must be done before enabling.
-        #[cfg(any(feature = "g4", feature = "h7"))]
-        result
-            .regs
-            .cr1
-            .modify(|_, w| w.fifoen().bit(result.config.fifo_enabled));
+        #[cfg(feature = "usart_fifo")]
+        {
+            result
+                .regs
+                .cr1
+                .modify(|_, w| w.fifoen().bit(result.config.fifo_enabled));
+            // FIFOEN reads back as 0 on instances without a FIFO, where TXFE is never set.
+            result.config.fifo_enabled = result.regs.cr1.read().fifoen().bit_is_set();
+
+            result.regs.cr3.modify(|_, w| unsafe {
+                w.txftcfg().bits(result.config.tx_fifo_thresh as u8);
+                w.rxftcfg().bits(result.config.rx_fifo_thresh as u8)
+            });
+        }
 
         // 2. Select the desired baud rate using the USART_BRR register.
         result.set_baud(baud, clock_cfg);
@@ -266,21 +530,8 @@ where
             .regs
             .cr2
             .modify(|_, w| unsafe { w.stop().bits(result.config.stop_bits as u8) });
-        // 4. Enable the USART by writing the UE bit in USART_CR1 register to 1.
-        result.enable();
-
-        // 5. Select DMA enable (DMAT[R]] in USART_CR3 if multibuffer communication is to take
-        // place. Configure the DMA register as explained in multibuffer communication.
-        // (Handled in `read_dma()` and `write_dma()`)
-        // 6. Set the TE bit in USART_CR1 to send an idle frame as first transmission.
-        // 6. Set the RE bit USART_CR1. This enables the receiver which begins searching for a
-        // start bit.
-
-        cr1!(result.regs).modify(|_, w| {
-            w.te().set_bit();
-            w.re().set_bit()
-        });
 
+        // IrDA bits, and the prescaler, can only be written when the USART is disabled.
         match result.config.irda_mode {
             // See G4 RM, section 37.5.18: USART IrDA SIR ENDEC block
             // " IrDA mode is selected by setting the IREN bit in the USART_CR3 register. In IrDA mode,
@@ -301,9 +552,34 @@ where
                     w.irlp().bit(result.config.irda_mode == IrdaMode::LowPower);
                     w.iren().set_bit()
                 });
+
+                // "PSC[7:0]: In IrDA Low-power mode, PSC[7:0] bits are used to program the prescaler for
+                // dividing the USART source clock to achieve the low-power frequency. [...]
+                // In normal IrDA mode, PSC must be set to 00000001." The low-power frequency must be
+                // between 1.42 and 2.12Mhz; target 1.8432Mhz.
+                let psc = match result.config.irda_mode {
+                    IrdaMode::LowPower => (R::baud(clock_cfg) / 1_843_200).clamp(1, 255) as u8,
+                    _ => 1,
+                };
+                result.regs.gtpr.modify(|_, w| unsafe { w.psc().bits(psc) });
             }
         }
 
+        // 4. Enable the USART by writing the UE bit in USART_CR1 register to 1.
+        result.enable();
+
+        // 5. Select DMA enable (DMAT[R]] in USART_CR3 if multibuffer communication is to take
+        // place. Configure the DMA register as explained in multibuffer communication.
+        // (Handled in `read_dma()` and `write_dma()`)
+        // 6. Set the TE bit in USART_CR1 to send an idle frame as first transmission.
+        // 6. Set the RE bit USART_CR1. This enables the receiver which begins searching for a
+        // start bit.
+
+        cr1!(result.regs).modify(|_, w| {
+            w.te().set_bit();
+            w.re().set_bit()
+        });
+
         result
     }
 
@@ -322,19 +598,6 @@ where
     /// Set the BAUD rate. Called during init, and can be called later to change BAUD
     /// during program execution.
     pub fn set_baud(&mut self, baud: u32, clock_cfg: &Clocks) -> Result<(), Error> {
-        let originally_enabled = cr1!(self.regs).read().ue().bit_is_set();
-
-        if originally_enabled {
-            cr1!(self.regs).modify(|_, w| w.ue().clear_bit());
-            let mut i = 0;
-            while cr1!(self.regs).read().ue().bit_is_set() {
-                i += 1;
-                if i >= MAX_ITERS {
-                    return Err(Error::Hardware);
-                }
-            }
-        }
-
         // To set BAUD rate, see L4 RM section 38.5.4: "USART baud rate generation".
         // todo: This assumes the USART clock is APB1 or 2 depending on which USART.
         // todo: Take into account the selectable USART clock in both
@@ -354,15 +617,293 @@ where
         // – BRR[15:4] = USARTDIV[15:4]
         // todo: BRR needs to be modified per the above if on oversampling 8.
 
-        self.regs.brr.write(|w| unsafe { w.bits(usart_div as u32) });
+        while_disabled!(self.regs, {
+            self.regs.brr.write(|w| unsafe { w.bits(usart_div as u32) });
+        });
 
         self.baud = baud;
 
-        if originally_enabled {
-            cr1!(self.regs).modify(|_, w| w.ue().set_bit());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "f4"))]
+    /// Configure hardware RS-485 Driver Enable. See G4 RM, section 37.5.21. This must be called while
+    /// the USART is disabled; this is handled by `new()`, if `rs485` is set in the config.
+    fn set_rs485(&mut self, cfg: &Rs485Config) {
+        // DEAT and DEDT are expressed in sample time units: 1/8 or 1/16 of a bit time, depending on
+        // the oversampling rate.
+        let samples_per_bit = match self.config.oversampling {
+            OverSampling::O16 => 16.,
+            OverSampling::O8 => 8.,
+        };
+        let deat = de_time_to_samples(cfg.assertion_time, samples_per_bit);
+        let dedt = de_time_to_samples(cfg.deassertion_time, samples_per_bit);
+
+        // We write DEAT (CR1 bits 25:21) and DEDT (CR1 bits 20:16) directly, since some PACs split
+        // these fields into individual bits.
+        cr1!(self.regs).modify(|r, w| unsafe {
+            w.bits((r.bits() & !(0x3ff << 16)) | ((deat as u32) << 21) | ((dedt as u32) << 16))
+        });
+
+        self.regs.cr3.modify(|_, w| {
+            w.dep().bit(cfg.polarity == DePolarity::ActiveLow);
+            // "The DEM bit allows the user to activate the external transceiver control, through the
+            // DE (Driver Enable) signal."
+            w.dem().set_bit()
+        });
+    }
+
+    /// Enable LIN mode. See G4 RM, section 37.5.12: LIN (local interconnection network) mode.
+    /// The line break interrupt (`UsartInterrupt::LineBreak`) may be used to detect the start of a
+    /// frame as a slave.
+    pub fn enable_lin(&mut self, break_len: LinBreakLen) {
+        // LINEN, and the other CR2 and CR3 bits below can only be written when the USART is disabled.
+        self.disable();
+
+        // "The LIN mode is selected by setting the LINEN bit in the USART_CR2 register. In LIN mode,
+        // the following bits must be kept cleared:
+        // • STOP[1:0] and CLKEN in the USART_CR2 register,
+        self.regs.cr2.modify(|_, w| unsafe {
+            w.stop().bits(0);
+            w.clken().clear_bit();
+            w.lbdl().bit(break_len as u8 != 0);
+            w.linen().set_bit()
+        });
+
+        // • SCEN, HDSEL and IREN in the USART_CR3 register."
+        self.regs.cr3.modify(|_, w| {
+            w.scen().clear_bit();
+            w.hdsel().clear_bit();
+            w.iren().clear_bit()
+        });
+
+        self.enable();
+    }
+
+    /// Disable LIN mode.
+    pub fn disable_lin(&mut self) {
+        self.disable();
+        self.regs.cr2.modify(|_, w| w.linen().clear_bit());
+        self.enable();
+    }
+
+    /// Send a break character: 13 low bits in LIN mode. The break is sent after completion of
+    /// the current character transmission.
+    pub fn send_break(&mut self) {
+        #[cfg(not(feature = "f4"))]
+        self.regs.rqr.write(|w| w.sbkrq().set_bit());
+        #[cfg(feature = "f4")]
+        self.regs.cr1.modify(|_, w| w.sbk().set_bit());
+    }
+
+    /// As the LIN master, send a frame header: A break, the synch byte, and the protected identifier.
+    pub fn lin_write_header(&mut self, id: u8) -> Result<(), Error> {
+        if id > 0x3f {
+            return Err(Error::Lin(lin::Error::Length));
         }
 
-        Ok(())
+        self.send_break();
+        self.write(&[lin::SYNC, lin::protected_id(id)])
+    }
+
+    /// Send a frame's response: its data, and checksum. This may be called by the master or slave
+    /// task publishing the frame, after its header is sent.
+    pub fn lin_write_response(
+        &mut self,
+        frame: &lin::Frame,
+        model: ChecksumModel,
+    ) -> Result<(), Error> {
+        let mut buf = [0; lin::MAX_DATA_LEN + 1];
+        let len = frame.encode_response(model, &mut buf)?;
+
+        self.write(&buf[..len])
+    }
+
+    /// As the LIN master, send a complete frame: A header, followed by its response.
+    pub fn lin_write_frame(
+        &mut self,
+        frame: &lin::Frame,
+        model: ChecksumModel,
+    ) -> Result<(), Error> {
+        self.lin_write_header(frame.id)?;
+        self.lin_write_response(frame, model)
+    }
+
+    /// As a LIN slave, block until a break is detected, then read the frame header. Returns the
+    /// frame identifier.
+    pub fn lin_read_header(&mut self) -> Result<u8, Error> {
+        let mut i = 0;
+        cfg_if! {
+            if #[cfg(feature = "f4")] {
+                while self.regs.sr.read().lbd().bit_is_clear() {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(Error::Hardware);
+                    }
+                }
+                self.regs.sr.modify(|_, w| w.lbd().clear_bit());
+                // The break is also received as a 0 character with a framing error.
+                self.read_one();
+            } else {
+                while isr!(self.regs).read().lbdf().bit_is_clear() {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(Error::Hardware);
+                    }
+                }
+                self.regs.icr.write(|w| w.lbdcf().set_bit());
+                // The break is also received as a 0 character with a framing error. Discard it.
+                self.regs.rqr.write(|w| w.rxfrq().set_bit());
+                self.regs.icr.write(|w| w.fecf().set_bit());
+            }
+        }
+
+        let mut header = [0; 2];
+        self.read(&mut header)?;
+
+        Ok(lin::decode_header(&header)?)
+    }
+
+    /// Read a frame's response (data, and checksum) of a known data length, after its header, and
+    /// validate it.
+    pub fn lin_read_response(
+        &mut self,
+        id: u8,
+        data_len: usize,
+        model: ChecksumModel,
+    ) -> Result<lin::Frame, Error> {
+        if data_len > lin::MAX_DATA_LEN {
+            return Err(Error::Lin(lin::Error::Length));
+        }
+
+        let mut buf = [0; lin::MAX_DATA_LEN + 1];
+        self.read(&mut buf[..data_len + 1])?;
+
+        Ok(lin::Frame::decode(
+            lin::protected_id(id),
+            &buf[..data_len + 1],
+            model,
+        )?)
+    }
+
+    /// Enable Smartcard (ISO 7816) mode, using 8 data bits with even parity, and 1.5 stop bits. This
+    /// sets the baud rate to the default of the card clock / 372; after reading the ATR, it may be changed
+    /// with `set_baud()`, eg using `iso7816::baud()`. Returns the card clock frequency set, in Hz, or
+    /// `Error::BaudRate` if `cfg.card_clock` is 0. See G4 RM, section 37.5.16: USART Smartcard mode.
+    pub fn enable_smartcard(
+        &mut self,
+        cfg: &SmartcardConfig,
+        clock_cfg: &Clocks,
+    ) -> Result<u32, Error> {
+        if cfg.card_clock == 0 {
+            return Err(Error::BaudRate);
+        }
+
+        // SCEN, and the other CR2 and CR3 bits below can only be written when the USART is disabled.
+        self.disable();
+
+        // The card clock is the kernel clock divided by 2 x PSC. PSC is 5 bits in Smartcard mode.
+        let fclk = R::baud(clock_cfg);
+        let psc = fclk.div_ceil(2 * cfg.card_clock).clamp(1, 31);
+        let card_clock = fclk / (2 * psc);
+
+        self.regs.gtpr.modify(|_, w| unsafe {
+            w.gt().bits(cfg.guard_time);
+            w.psc().bits(psc as u8)
+        });
+
+        // "The Smartcard mode is selected by setting the SCEN bit in the USART_CR3 register. In
+        // Smartcard mode, the following bits must be kept cleared:
+        // • LINEN bit in the USART_CR2 register,
+        // • HDSEL and IREN bits in the USART_CR3 register.
+        // Moreover, the CLKEN bit may be set in order to provide a clock to the smartcard."
+        self.regs.cr2.modify(|_, w| unsafe {
+            w.linen().clear_bit();
+            // "1.5 stop bits should be used for both transmitting and receiving"
+            w.stop().bits(StopBits::S1_5 as u8);
+            w.clken().set_bit()
+        });
+
+        // "The USART should be configured as: 8 bits plus parity: where word length is set to
+        // 8 bits and PCE=1 in the USART_CR1 register". We write M0 (bit 12) and M1 (bit 28)
+        // directly, since M0 is missing from some PACs. Even parity: PS (bit 9) cleared.
+        cr1!(self.regs).modify(|r, w| unsafe {
+            w.bits((r.bits() & !((1 << 28) | (1 << 9))) | (1 << 12) | (1 << 10))
+        });
+
+        self.regs.cr3.modify(|_, w| {
+            #[cfg(not(feature = "f4"))]
+            unsafe {
+                w.scarcnt().bits(cfg.auto_retry_count.min(7));
+            }
+            w.hdsel().clear_bit();
+            w.iren().clear_bit();
+            w.nack().bit(cfg.nack);
+            w.scen().set_bit()
+        });
+
+        self.set_baud(iso7816::baud(card_clock, 372, 1), clock_cfg)?;
+
+        self.enable();
+
+        Ok(card_clock)
+    }
+
+    /// Disable Smartcard mode, and stop the card clock.
+    pub fn disable_smartcard(&mut self) {
+        self.disable();
+        self.regs.cr3.modify(|_, w| w.scen().clear_bit());
+        self.regs.cr2.modify(|_, w| w.clken().clear_bit());
+        self.enable();
+    }
+
+    /// Read the card's Answer-To-Reset. Call this after releasing the card's reset line, in Smartcard
+    /// mode. Note that only the direct convention is currently supported.
+    pub fn smartcard_read_atr(&mut self) -> Result<iso7816::Atr, Error> {
+        // todo: Inverse convention; this requires MSBFIRST, DATAINV, and odd parity.
+        iso7816::Atr::read(|| {
+            let mut byte = [0];
+            self.read(&mut byte)?;
+            Ok(byte[0])
+        })
+    }
+
+    /// Exchange a command APDU with the card using the T=0 protocol, writing response data
+    /// into `resp`. Returns the response length, and status word.
+    pub fn smartcard_transmit(
+        &mut self,
+        cmd: &iso7816::Command,
+        resp: &mut [u8],
+    ) -> Result<iso7816::Response, Error> {
+        iso7816::transmit_t0(self, cmd, resp)
+    }
+
+    #[cfg(not(feature = "f4"))]
+    /// Enable the receiver timeout: The `ReceiverTimeout` flag is set, and its interrupt fired if enabled,
+    /// when no new character is received for `bit_times` bit durations after the last stop bit. This
+    /// may be used to detect the end of a frame, eg for Modbus RTU. The timeout is 24 bits wide. Note
+    /// that not all U[S]ARTs support this; see the RM. See G4 RM, section 37.5.10: "Receiver timeout".
+    pub fn enable_receiver_timeout(&mut self, bit_times: u32) {
+        self.regs
+            .rtor
+            .modify(|_, w| unsafe { w.rto().bits(bit_times.min(0xff_ffff)) });
+        self.regs.cr2.modify(|_, w| w.rtoen().set_bit());
+    }
+
+    #[cfg(not(feature = "f4"))]
+    /// Disable the receiver timeout.
+    pub fn disable_receiver_timeout(&mut self) {
+        self.regs.cr2.modify(|_, w| w.rtoen().clear_bit());
+    }
+
+    /// Send a Modbus RTU frame to a slave address. `pdu` is the function code, followed by its data.
+    /// This blocks until transmission is complete, so the line is released for the t3.5 interval
+    /// that follows.
+    pub fn modbus_write(&mut self, address: u8, pdu: &[u8]) -> Result<(), Error> {
+        let mut buf = [0; modbus::MAX_ADU_LEN];
+        let len = modbus::Adu::new(address, pdu)?.encode(&mut buf)?;
+
+        self.write(&buf[..len])
     }
 
     /// Transmit data, as a sequence of u8. See L44 RM, section 38.5.2: "Character transmission procedure"
@@ -370,32 +911,33 @@ where
         // todo: how does this work with a 9 bit words? Presumably you'd need to make `data`
         // todo take `&u16`.
 
+        #[cfg(feature = "usart_fifo")]
+        if self.config.fifo_enabled {
+            return self.write_fifo(data);
+        }
+
         // 7. Write the data to send in the USART_TDR register (this clears the TXE bit). Repeat this
         // for each data to be transmitted in case of single buffer.
 
         cfg_if! {
             if #[cfg(not(feature = "f4"))] {
                 for word in data {
-                    let mut i = 0;
-
                     #[cfg(feature = "h5")]
-                    // todo: Fifo vs non-fifo for f5.
-                    while isr!(self.regs).read().txfe().bit_is_clear() {
-                        i += 1;
-                        if i >= MAX_ITERS {
-                            // return Err(Error::Hardware);
+                    {
+                        let mut i = 0;
+                        // todo: Fifo vs non-fifo for f5.
+                        while isr!(self.regs).read().txfe().bit_is_clear() {
+                            i += 1;
+                            if i >= MAX_ITERS {
+                                // return Err(Error::Hardware);
+                            }
                         }
                     }
 
                     #[cfg(not(feature = "h5"))]
                     // Note: Per these PACs, TXFNF and TXE are on the same field, so this is actually
                     // checking txfnf if the fifo is enabled.
-                    while isr!(self.regs).read().txe().bit_is_clear() {
-                        i += 1;
-                        if i >= MAX_ITERS {
-                            return Err(Error::Hardware);
-                        }
-                    }
+                    wait_tx_flag!(self, txe);
 
                     self.regs
                         .tdr
@@ -405,13 +947,7 @@ where
                 // that the transmission of the last frame is complete. This is required for instance when
                 // the USART is disabled or enters the Halt mode to avoid corrupting the last
                 // transmission
-                let mut i = 0;
-                while isr!(self.regs).read().tc().bit_is_clear() {
-                        i += 1;
-                        if i >= MAX_ITERS {
-                            return Err(Error::Hardware);
-                        }
-                }
+                wait_tx_flag!(self, tc);
             } else {
                 for word in data {
                     let mut i = 0;
@@ -459,6 +995,11 @@ where
 
     /// Receive data into a u8 buffer. See L44 RM, section 38.5.3: "Character reception procedure"
     pub fn read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        #[cfg(feature = "usart_fifo")]
+        if self.config.fifo_enabled {
+            return self.read_fifo(buf);
+        }
+
         for i in 0..buf.len() {
             let mut i_ = 0;
             cfg_if! {
@@ -512,6 +1053,53 @@ where
         Ok(())
     }
 
+    #[cfg(feature = "usart_fifo")]
+    /// Transmit data with the FIFO enabled: Wait until the TX FIFO is empty, then fill it. The shift
+    /// register is still sending the previous character when TXFE is set, so there are no gaps
+    /// between bursts. See G4 RM, section 37.5.5: "USART FIFOs and thresholds".
+    fn write_fifo(&mut self, data: &[u8]) -> Result<(), Error> {
+        for burst in data.chunks(FIFO_DEPTH) {
+            wait_tx_flag!(self, txfe);
+
+            for word in burst {
+                self.regs
+                    .tdr
+                    .modify(|_, w| unsafe { w.tdr().bits(*word as u16) });
+            }
+        }
+
+        wait_tx_flag!(self, tc);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "usart_fifo")]
+    /// Receive data with the FIFO enabled: Wait until the RX FIFO isn't empty, then drain it. If the FIFO
+    /// is full, read its whole depth without checking flags between words.
+    fn read_fifo(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            // Note: Per these PACs, RXFNE and RXNE are on the same field.
+            wait_flag!(self.regs, rxne);
+
+            if isr!(self.regs).read().rxff().bit_is_set() {
+                let burst = FIFO_DEPTH.min(buf.len() - pos);
+                for word in &mut buf[pos..pos + burst] {
+                    *word = self.regs.rdr.read().rdr().bits() as u8;
+                }
+                pos += burst;
+            } else {
+                while pos < buf.len() && isr!(self.regs).read().rxne().bit_is_set() {
+                    buf[pos] = self.regs.rdr.read().rdr().bits() as u8;
+                    pos += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Read a single word, without waiting  until ready for the next. Compared to the `read()` function, this
     /// does not block.
     pub fn read_one(&mut self) -> u8 {
@@ -525,9 +1113,11 @@ where
     }
 
     #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
-    /// Transmit data using DMA. (L44 RM, section 38.5.15)
+    /// Transmit data using DMA. (L44 RM, section 38.5.15). If CTS flow control is enabled, the
+    /// transfer pauses while the receiver holds CTS de-asserted.
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
     pub unsafe fn write_dma(
         &mut self,
         buf: &[u8],
@@ -541,11 +1131,9 @@ where
         // the following procedure (x denotes the channel number):
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::write_chan();
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
-        #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::write_sel(dma_periph);
 
         #[cfg(feature = "h7")]
         let num_data = len as u32;
@@ -624,6 +1212,7 @@ where
     /// Receive data using DMA. (L44 RM, section 38.5.15; G4 RM section 37.5.19.
     /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
     /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
     pub unsafe fn read_dma(
         &mut self,
         buf: &mut [u8],
@@ -634,11 +1223,9 @@ where
         let (ptr, len) = (buf.as_mut_ptr(), buf.len());
 
         #[cfg(any(feature = "f3", feature = "l4"))]
-        let channel = R::read_chan();
-        #[cfg(feature = "l4")]
-        let mut dma_regs = unsafe { &(*DMA1::ptr()) }; // todo: Hardcoded DMA1
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
         #[cfg(feature = "l4")]
-        R::write_sel(&mut dma_regs);
+        R::read_sel(dma_periph);
 
         #[cfg(feature = "h7")]
         let num_data = len as u32;
@@ -702,12 +1289,222 @@ where
         // controller generates an interrupt on the DMA channel interrupt vector.
     }
 
-    /// Flush the transmit buffer.
-    pub fn flush(&self) {
-        #[cfg(not(feature = "f4"))]
-        while isr!(self.regs).read().tc().bit_is_clear() {}
-        #[cfg(feature = "f4")]
-        while self.regs.sr.read().tc().bit_is_clear() {}
+    #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+    /// Transmit data using DMA, without `unsafe`: The returned `Transfer` owns this peripheral and
+    /// the buffer until the transfer completes or is aborted. Otherwise, the same as `write_dma()`.
+    /// After the transfer completes, wait for the Transmission Complete flag before disabling the
+    /// USART.
+    pub fn write_dma_transfer<B: ReadBuffer<Word = u8>>(
+        mut self,
+        buf: B,
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> Transfer<Self, B> {
+        let (ptr, len) = buf.read_buffer();
+
+        // A circular transfer never completes, so `Transfer::wait()` would block forever.
+        let channel_cfg = ChannelCfg {
+            circular: Circular::Disabled,
+            ..channel_cfg
+        };
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::write_chan(dma_periph).unwrap_or(channel);
+
+        // The buffer is owned by the transfer, and `'static` or pinned, so stays valid while DMA
+        // uses it.
+        unsafe {
+            self.write_dma(
+                core::slice::from_raw_parts(ptr, len),
+                channel,
+                channel_cfg,
+                dma_periph,
+            );
+        }
+
+        Transfer::new(self, buf, channel, dma_periph)
+    }
+
+    #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+    /// Receive data using DMA, without `unsafe`: The returned `Transfer` owns this peripheral and
+    /// the buffer until the transfer completes or is aborted. Otherwise, the same as `read_dma()`.
+    pub fn read_dma_transfer<B: WriteBuffer<Word = u8>>(
+        mut self,
+        mut buf: B,
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> Transfer<Self, B> {
+        let (ptr, len) = buf.write_buffer();
+
+        // A circular transfer never completes, so `Transfer::wait()` would block forever.
+        let channel_cfg = ChannelCfg {
+            circular: Circular::Disabled,
+            ..channel_cfg
+        };
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+
+        unsafe {
+            self.read_dma(
+                core::slice::from_raw_parts_mut(ptr, len),
+                channel,
+                channel_cfg,
+                dma_periph,
+            );
+        }
+
+        Transfer::new(self, buf, channel, dma_periph)
+    }
+
+    #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+    /// Receive data of unknown length continuously, using circular DMA into a ring buffer.
+    /// This enables the Idle line interrupt, and the DMA half and full-transfer interrupts; on
+    /// any of these events, consume received data using the returned reader. The reader must be serviced
+    /// at least once per half buffer, or unread data will be overwritten.
+    /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
+    /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
+    /// Returns `Error::EmptyBuffer` if `buf` is empty.
+    pub unsafe fn read_dma_circular(
+        &mut self,
+        buf: &'static mut [u8],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) -> Result<UsartDmaReader, Error> {
+        if buf.is_empty() {
+            return Err(Error::EmptyBuffer);
+        }
+
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+
+        let (ptr, len) = (buf.as_ptr(), buf.len());
+
+        let channel_cfg = ChannelCfg {
+            circular: Circular::Enabled,
+            ..channel_cfg
+        };
+
+        self.read_dma(buf, channel, channel_cfg, dma_periph);
+
+        // The transfer-complete interrupt is enabled by `cfg_channel`.
+        dma::enable_interrupt(dma_periph, channel, DmaInterrupt::HalfTransfer);
+        self.clear_interrupt(UsartInterrupt::Idle);
+        self.enable_interrupt(UsartInterrupt::Idle);
+
+        Ok(UsartDmaReader {
+            buf: ptr,
+            len,
+            read_pos: 0,
+            channel,
+            dma_periph,
+        })
+    }
+
+    #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+    /// Receive a Modbus RTU frame using DMA. This sets the receiver timeout to the t3.5 interval for
+    /// the current baud rate, and enables its interrupt. When that interrupt fires, the frame is
+    /// complete; call `modbus_read_complete()` with the same buffer to decode it. `buf` should be
+    /// at least `modbus::MAX_ADU_LEN` bytes long.
+    /// Note that the `channel` argument is unused on F3 and L4, since it is hard-coded,
+    /// and can't be configured using the DMAMUX peripheral. (`dma::mux()` fn).
+    /// It's only used there if this peripheral isn't connected to `dma_periph`.
+    pub unsafe fn modbus_read_dma(
+        &mut self,
+        buf: &mut [u8],
+        channel: DmaChannel,
+        channel_cfg: ChannelCfg,
+        dma_periph: dma::DmaPeriph,
+    ) {
+        self.enable_receiver_timeout(modbus::t35_bit_times(self.baud));
+        self.clear_interrupt(UsartInterrupt::ReceiverTimeout);
+        self.enable_interrupt(UsartInterrupt::ReceiverTimeout);
+
+        self.read_dma(buf, channel, channel_cfg, dma_periph);
+    }
+
+    #[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+    /// Finish receiving a Modbus RTU frame started with `modbus_read_dma()`; call this from the
+    /// `ReceiverTimeout` interrupt. This clears the interrupt, stops the DMA transfer, and decodes
+    /// the frame received into `buf`, checking its CRC.
+    pub fn modbus_read_complete<'a>(
+        &mut self,
+        buf: &'a [u8],
+        channel: DmaChannel,
+        dma_periph: dma::DmaPeriph,
+    ) -> Result<modbus::Adu<'a>, Error> {
+        #[cfg(any(feature = "f3", feature = "l4"))]
+        let channel = R::read_chan(dma_periph).unwrap_or(channel);
+
+        self.clear_interrupt(UsartInterrupt::ReceiverTimeout);
+
+        let remaining = dma::transfers_remaining(dma_periph, channel) as usize;
+        dma::stop(dma_periph, channel);
+
+        let len = buf.len().saturating_sub(remaining);
+
+        Ok(modbus::Adu::decode(&buf[..len])?)
+    }
+
+    /// Flush the transmit buffer: Block until the last frame has been shifted out. (TC flag set)
+    /// When using RS-485 Driver Enable, the DE signal is de-asserted by hardware once the
+    /// deassertion time elapses after this; it's then safe to turn the bus around.
+    ///
+    /// Breaking change: this previously returned `()`. It now returns `Error::Hardware` if the
+    /// flags don't set in time, or `Error::CtsTimeout` if CTS flow control holds off transmission
+    /// for longer than `UsartConfig::cts_timeout`.
+    pub fn flush(&self) -> Result<(), Error> {
+        // Wait for the transmit data register (or FIFO) to empty; otherwise TC may read
+        // as set before the final word is loaded into the shift register.
+        cfg_if! {
+            if #[cfg(feature = "f4")] {
+                let mut i = 0;
+                while self.regs.sr.read().txe().bit_is_clear() {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(Error::Hardware);
+                    }
+                }
+            } else {
+                wait_tx_flag!(self, txe);
+            }
+        }
+
+        cfg_if! {
+            if #[cfg(feature = "f4")] {
+                let mut i = 0;
+                while self.regs.sr.read().tc().bit_is_clear() {
+                    i += 1;
+                    if i >= MAX_ITERS {
+                        return Err(Error::Hardware);
+                    }
+                }
+            } else {
+                wait_tx_flag!(self, tc);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "f4"))]
+    /// Returns `true` if the CTS input is asserted (low); ie the receiver is ready for data.
+    /// This reflects the pin state when CTS flow control is enabled. (USART_ISR, CTS)
+    pub fn cts_asserted(&self) -> bool {
+        isr!(self.regs).read().cts().bit_is_set()
+    }
+
+    #[cfg(not(feature = "f4"))]
+    /// Returns `true` if CTS flow control is enabled, and the receiver is holding off transmission.
+    fn cts_blocked(&self) -> bool {
+        match self.config.flow_control {
+            FlowControl::Cts | FlowControl::RtsCts => !self.cts_asserted(),
+            _ => false,
+        }
     }
 
     #[cfg(not(feature = "f4"))]
@@ -778,6 +1575,14 @@ where
             UsartInterrupt::ReceiverTimeout => {
                 cr1!(self.regs).modify(|_, w| w.rtoie().set_bit());
             }
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::RxFifoThreshold => {
+                self.regs.cr3.modify(|_, w| w.rxftie().set_bit());
+            }
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::TxFifoThreshold => {
+                self.regs.cr3.modify(|_, w| w.txftie().set_bit());
+            }
             #[cfg(not(any(feature = "f3", feature = "l4")))]
             UsartInterrupt::Tcbgt => {
                 self.regs.cr3.modify(|_, w| w.tcbgtie().set_bit());
@@ -833,6 +1638,14 @@ where
             UsartInterrupt::ReceiverTimeout => {
                 cr1!(self.regs).modify(|_, w| w.rtoie().clear_bit());
             }
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::RxFifoThreshold => {
+                self.regs.cr3.modify(|_, w| w.rxftie().clear_bit());
+            }
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::TxFifoThreshold => {
+                self.regs.cr3.modify(|_, w| w.txftie().clear_bit());
+            }
             #[cfg(not(any(feature = "f3", feature = "l4")))]
             UsartInterrupt::Tcbgt => {
                 self.regs.cr3.modify(|_, w| w.tcbgtie().clear_bit());
@@ -873,6 +1686,9 @@ where
             UsartInterrupt::ParityError => self.regs.icr.write(|w| w.pecf().set_bit()),
             UsartInterrupt::ReadNotEmpty => self.regs.rqr.write(|w| w.rxfrq().set_bit()),
             UsartInterrupt::ReceiverTimeout => self.regs.icr.write(|w| w.rtocf().set_bit()),
+            // The FIFO threshold flags are cleared by hardware when the FIFO level changes.
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::RxFifoThreshold | UsartInterrupt::TxFifoThreshold => (),
             #[cfg(not(any(feature = "f3", feature = "l4", feature = "h7")))]
             UsartInterrupt::Tcbgt => self.regs.icr.write(|w| w.tcbgtcf().set_bit()),
             #[cfg(feature = "h7")]
@@ -890,7 +1706,7 @@ where
 
         match flag {
             UsartInterrupt::CharDetect(_) => status.cmf().bit_is_set(),
-            UsartInterrupt::Cts => status.cts().bit_is_set(),
+            UsartInterrupt::Cts => status.ctsif().bit_is_set(),
             UsartInterrupt::EndOfBlock => status.eobf().bit_is_set(),
             UsartInterrupt::Idle => status.idle().bit_is_set(),
             UsartInterrupt::FramingError => status.fe().bit_is_set(),
@@ -902,6 +1718,10 @@ where
             #[cfg(not(feature = "h5"))]
             UsartInterrupt::ReadNotEmpty => status.rxne().bit_is_set(),
             UsartInterrupt::ReceiverTimeout => status.rtof().bit_is_set(),
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::RxFifoThreshold => status.rxft().bit_is_set(),
+            #[cfg(feature = "usart_fifo")]
+            UsartInterrupt::TxFifoThreshold => status.txft().bit_is_set(),
             #[cfg(not(any(feature = "f3", feature = "l4")))]
             UsartInterrupt::Tcbgt => status.tcbgt().bit_is_set(),
             UsartInterrupt::TransmissionComplete => status.tc().bit_is_set(),
@@ -913,6 +1733,292 @@ where
     }
 }
 
+#[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+/// Reads data received by circular DMA, as set up by `Usart::read_dma_circular()`. The
+/// position the DMA is writing to is computed from the channel's remaining transfer count, so
+/// variable-length frames can be consumed as they arrive, eg from the USART Idle interrupt.
+pub struct UsartDmaReader {
+    buf: *const u8,
+    len: usize,
+    read_pos: usize,
+    channel: DmaChannel,
+    dma_periph: dma::DmaPeriph,
+}
+
+#[cfg(not(any(feature = "f4", feature = "l552", feature = "h5")))]
+impl UsartDmaReader {
+    /// The index in the buffer the DMA will write the next received byte to.
+    fn write_pos(&self) -> usize {
+        let remaining = dma::transfers_remaining(self.dma_periph, self.channel) as usize;
+        // The count is reloaded to `len` after reaching 0, so this wraps to 0.
+        (self.len - remaining) % self.len
+    }
+
+    /// The number of received bytes that haven't been read yet.
+    pub fn available(&self) -> usize {
+        (self.write_pos() + self.len - self.read_pos) % self.len
+    }
+
+    /// Copy received bytes into `buf`, up to its length. Returns the number of bytes copied.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let count = self.available().min(buf.len());
+
+        for byte in buf.iter_mut().take(count) {
+            // The DMA writes to this buffer concurrently, so use volatile reads.
+            *byte = unsafe { core::ptr::read_volatile(self.buf.add(self.read_pos)) };
+            self.read_pos = (self.read_pos + 1) % self.len;
+        }
+
+        count
+    }
+
+    /// Discard all received bytes that haven't been read yet.
+    pub fn clear(&mut self) {
+        self.read_pos = self.write_pos();
+    }
+
+    /// Stop the DMA transfer.
+    pub fn stop(&mut self) {
+        dma::stop(self.dma_periph, self.channel);
+    }
+}
+
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+#[derive(Clone, Copy, Default, Debug)]
+/// Counts of receive errors recorded by `BufferedUsart`.
+pub struct ErrorStats {
+    /// A character was received before the previous one was read. (ORE)
+    pub overrun: u32,
+    /// A stop bit wasn't detected when expected. (FE)
+    pub framing: u32,
+    /// Noise was detected on a received frame. (NF)
+    pub noise: u32,
+    /// A parity error was detected. (PE)
+    pub parity: u32,
+    /// A character was received while the RX buffer was full, and discarded.
+    pub rx_dropped: u32,
+}
+
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+/// A ring buffer of bytes, with one slot kept empty to distinguish full from empty.
+struct RingBuf {
+    buf: &'static mut [u8],
+    head: usize,
+    tail: usize,
+}
+
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+impl RingBuf {
+    fn push(&mut self, byte: u8) -> bool {
+        let next = (self.head + 1) % self.buf.len();
+        if next == self.tail {
+            return false;
+        }
+
+        self.buf[self.head] = byte;
+        self.head = next;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.head == self.tail {
+            return None;
+        }
+
+        let byte = self.buf[self.tail];
+        self.tail = (self.tail + 1) % self.buf.len();
+        Some(byte)
+    }
+
+    fn len(&self) -> usize {
+        (self.head + self.buf.len() - self.tail) % self.buf.len()
+    }
+}
+
+// ISR and ICR bit positions used by `BufferedUsart`, for flags whose names vary between PACs.
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+const ISR_NF: u32 = 1 << 2;
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+const ICR_PECF: u32 = 1 << 0;
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+const ICR_FECF: u32 = 1 << 1;
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+const ICR_NCF: u32 = 1 << 2;
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+const ICR_ORECF: u32 = 1 << 3;
+
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+/// An interrupt-driven USART, with RX and TX ring buffers. Received data is queued from the USART
+/// interrupt, and queued data is transmitted from it, so reads and writes don't block. Call
+/// `on_interrupt()` from the USART's interrupt handler. This is usually stored in a global
+/// `Mutex<RefCell<Option<>>>`, to share between the interrupt handler and application code.
+/// Each buffer holds one less byte than its length.
+pub struct BufferedUsart<R> {
+    pub usart: Usart<R>,
+    rx: RingBuf,
+    tx: RingBuf,
+    errors: ErrorStats,
+}
+
+#[cfg(not(any(feature = "f4", feature = "h5")))]
+impl<R> BufferedUsart<R>
+where
+    R: Deref<Target = pac::usart1::RegisterBlock> + RccPeriph + BaudPeriph,
+{
+    /// Create a buffered USART from a configured one, and enable its receive and error interrupts.
+    /// You must unmask the USART's interrupt line in the NVIC.
+    pub fn new(mut usart: Usart<R>, rx_buf: &'static mut [u8], tx_buf: &'static mut [u8]) -> Self {
+        assert!(rx_buf.len() >= 2 && tx_buf.len() >= 2);
+
+        usart.enable_interrupt(UsartInterrupt::ReadNotEmpty);
+        // Enables interrupts on overrun, framing, and noise errors.
+        usart.enable_interrupt(UsartInterrupt::Overrun);
+        usart.enable_interrupt(UsartInterrupt::ParityError);
+
+        Self {
+            usart,
+            rx: RingBuf {
+                buf: rx_buf,
+                head: 0,
+                tail: 0,
+            },
+            tx: RingBuf {
+                buf: tx_buf,
+                head: 0,
+                tail: 0,
+            },
+            errors: ErrorStats::default(),
+        }
+    }
+
+    /// Copy received bytes into `buf`, up to its length. Returns the number of bytes copied. This
+    /// doesn't block.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+
+        for byte in buf.iter_mut() {
+            match self.rx.pop() {
+                Some(b) => *byte = b,
+                None => break,
+            }
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Queue bytes for transmission, up to the space available in the TX buffer. Returns the number of
+    /// bytes queued. This doesn't block.
+    pub fn try_write(&mut self, data: &[u8]) -> usize {
+        let mut count = 0;
+
+        for byte in data {
+            if !self.tx.push(*byte) {
+                break;
+            }
+            count += 1;
+        }
+
+        if count > 0 {
+            // The TXE interrupt moves queued bytes to the transmit register.
+            self.usart
+                .disable_interrupt(UsartInterrupt::TransmissionComplete);
+            self.usart.enable_interrupt(UsartInterrupt::TransmitEmpty);
+        }
+
+        count
+    }
+
+    /// The number of received bytes available to read.
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Returns `true` if all queued data has been transmitted, including the last frame's stop bits.
+    pub fn tx_complete(&self) -> bool {
+        self.tx.len() == 0 && isr!(self.usart.regs).read().tc().bit_is_set()
+    }
+
+    /// Counts of receive errors since creation, or since `clear_errors()`.
+    pub fn errors(&self) -> ErrorStats {
+        self.errors
+    }
+
+    /// Reset the error counts.
+    pub fn clear_errors(&mut self) {
+        self.errors = ErrorStats::default();
+    }
+
+    /// Service the USART: Record and clear errors, queue received data, and transmit queued data.
+    /// Call this from the USART's interrupt handler.
+    pub fn on_interrupt(&mut self) {
+        let status = isr!(self.usart.regs).read();
+        let cr1 = cr1!(self.usart.regs).read();
+
+        // Error flags are set along with RXNE for the affected character; we still receive it.
+        if status.ore().bit_is_set() {
+            self.errors.overrun += 1;
+        }
+        if status.fe().bit_is_set() {
+            self.errors.framing += 1;
+        }
+        // NF is named NE on some PACs (eg WL and G0B1), so read it by position. "Bit 2 NF: START bit
+        // Noise detection flag"
+        if status.bits() & ISR_NF != 0 {
+            self.errors.noise += 1;
+        }
+        if status.pe().bit_is_set() {
+            self.errors.parity += 1;
+        }
+        // Clear the overrun, framing, noise, and parity error flags. (NCF is named NECF on some PACs)
+        self.usart
+            .regs
+            .icr
+            .write(|w| unsafe { w.bits(ICR_ORECF | ICR_NCF | ICR_FECF | ICR_PECF) });
+
+        // Reading RDR clears RXNE. With the FIFO enabled, this drains it.
+        while isr!(self.usart.regs).read().rxne().bit_is_set() {
+            let byte = self.usart.read_one();
+            if !self.rx.push(byte) {
+                self.errors.rx_dropped += 1;
+            }
+        }
+
+        if cr1.txeie().bit_is_set() {
+            // With the FIFO enabled, this fills it.
+            while isr!(self.usart.regs).read().txe().bit_is_set() {
+                match self.tx.pop() {
+                    Some(byte) => self.usart.write_one(byte),
+                    None => {
+                        // Nothing left to send; wait for the last frame to complete.
+                        self.usart.disable_interrupt(UsartInterrupt::TransmitEmpty);
+                        self.usart
+                            .enable_interrupt(UsartInterrupt::TransmissionComplete);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if cr1.tcie().bit_is_set() && status.tc().bit_is_set() {
+            self.usart
+                .disable_interrupt(UsartInterrupt::TransmissionComplete);
+        }
+    }
+
+    /// Stop the interrupts this enabled, and return the underlying USART.
+    pub fn free(mut self) -> Usart<R> {
+        self.usart.disable_interrupt(UsartInterrupt::ReadNotEmpty);
+        self.usart.disable_interrupt(UsartInterrupt::Overrun);
+        self.usart.disable_interrupt(UsartInterrupt::ParityError);
+        self.usart.disable_interrupt(UsartInterrupt::TransmitEmpty);
+        self.usart
+            .disable_interrupt(UsartInterrupt::TransmissionComplete);
+
+        self.usart
+    }
+}
+
 /// Serial error
 #[non_exhaustive]
 #[derive(Debug)]
@@ -926,10 +2032,76 @@ pub enum Error {
     /// Parity check error
     Parity,
     Hardware,
+    /// Transmission was held off by CTS flow control for longer than `UsartConfig::cts_timeout`.
+    CtsTimeout,
+    /// The baud rate requested can't be generated from the peripheral's clock.
+    BaudRate,
+    /// A buffer passed was empty, where at least one word is required.
+    EmptyBuffer,
+    /// An error decoding or encoding a LIN frame.
+    Lin(lin::Error),
+    /// An error decoding or encoding a Modbus RTU frame.
+    Modbus(modbus::Error),
+    /// An error parsing an ATR, or in the T=0 protocol.
+    Iso7816(iso7816::Error),
+}
+
+impl From<lin::Error> for Error {
+    fn from(e: lin::Error) -> Self {
+        Self::Lin(e)
+    }
+}
+
+impl From<modbus::Error> for Error {
+    fn from(e: modbus::Error) -> Self {
+        Self::Modbus(e)
+    }
+}
+
+impl From<iso7816::Error> for Error {
+    fn from(e: iso7816::Error) -> Self {
+        Self::Iso7816(e)
+    }
 }
 
 // todo: Use those errors above.
 
+#[cfg(not(feature = "f4"))]
+/// Convert a Driver Enable assertion or deassertion time, in bit-times, to the sample-time
+/// units used by the DEAT and DEDT fields. These fields are 5 bits wide.
+fn de_time_to_samples(bit_times: f32, samples_per_bit: f32) -> u8 {
+    let samples = bit_times * samples_per_bit + 0.5;
+
+    if samples <= 0. {
+        0
+    } else if samples >= 31. {
+        31
+    } else {
+        samples as u8
+    }
+}
+
+impl<R> iso7816::Transport for Usart<R>
+where
+    R: Deref<Target = pac::usart1::RegisterBlock> + RccPeriph + BaudPeriph,
+{
+    type Error = Error;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        // In Smartcard mode, the TX pin is used for both transmission and reception. Disable the
+        // receiver while transmitting, so sent characters aren't received.
+        cr1!(self.regs).modify(|_, w| w.re().clear_bit());
+        let result = Usart::write(self, data);
+        cr1!(self.regs).modify(|_, w| w.re().set_bit());
+
+        result
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        Usart::read(self, buf)
+    }
+}
+
 #[cfg(feature = "embedded_hal")]
 impl<R> Read<u8> for Usart<R>
 where
@@ -980,10 +2152,7 @@ where
     }
 
     fn flush(&mut self) -> nb::Result<(), Error> {
-        #[cfg(not(feature = "f4"))]
-        while !isr!(self.regs).read().tc().bit_is_set() {}
-        #[cfg(feature = "f4")]
-        while !self.regs.sr.read().tc().bit_is_set() {}
+        Usart::flush(self)?;
 
         Ok(())
     }
@@ -1003,8 +2172,6 @@ where
     }
 
     fn bflush(&mut self) -> Result<(), Error> {
-        Self::flush(self);
-
-        Ok(())
+        Usart::flush(self)
     }
 }
@@ -1,8 +1,5 @@
 //! This is an internal module that contains utility functionality used by other modules.
 
-#[cfg(feature = "l4")]
-use core::ops::Deref;
-
 use crate::{
     clocks::Clocks,
     pac::{self, rcc::RegisterBlock},
@@ -11,12 +8,9 @@ use crate::{
 #[cfg(any(feature = "f3", feature = "l4"))]
 use crate::pac::DMA1;
 
-#[cfg(feature = "l4")]
-use crate::pac::dma1;
-
 // todo: L5 has a PAC bug on CCR registers past 1.
 #[cfg(any(feature = "f3", feature = "l4"))]
-use crate::dma::{self, Dma, DmaChannel, DmaInput};
+use crate::dma::{self, Dma, DmaChannel, DmaInput, DmaPeriph};
 
 use cfg_if::cfg_if;
 
@@ -136,7 +130,7 @@ macro_rules! rcc_en_reset {
 
 pub(crate) use rcc_en_reset;
 
-// todo: This trait is currently a one-off for usart
+/// The peripheral clock used to generate the baud rate of a U[S]ART or LPUART.
 pub trait BaudPeriph {
     fn baud(clock_cfg: &Clocks) -> u32;
 }
@@ -223,6 +217,32 @@ cfg_if! {
     }
 }
 
+#[cfg(any(
+    feature = "l4",
+    feature = "l5",
+    feature = "g4",
+    all(feature = "h7", not(feature = "h7b3")),
+    feature = "wb",
+))]
+impl BaudPeriph for pac::LPUART1 {
+    fn baud(clock_cfg: &Clocks) -> u32 {
+        cfg_if! {
+            if #[cfg(feature = "h7")] {
+                clock_cfg.apb4()
+            } else {
+                clock_cfg.apb1()
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "g031", feature = "g041", feature = "g071", feature = "g081"))]
+impl BaudPeriph for pac::LPUART {
+    fn baud(clock_cfg: &Clocks) -> u32 {
+        clock_cfg.apb1()
+    }
+}
+
 // todo: This trait is currently a one-off for adc, and isn't currently used.
 pub trait VrefPeriph {
     fn vref(clock_cfg: &Clocks) -> u32;
@@ -273,14 +293,18 @@ impl VrefPeriph for pac::ADC5 {
 pub trait RccPeriph {
     fn en_reset(rcc: &RegisterBlock);
 
+    /// The hard-coded DMA channel for this peripheral's reads, on a given DMA peripheral. Returns
+    /// `None` if they aren't connected to it, or this isn't known.
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel;
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel>;
+    /// The hard-coded DMA channel for this peripheral's writes, on a given DMA peripheral. Returns
+    /// `None` if they aren't connected to it, or this isn't known.
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel;
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel>;
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D);
+    fn read_sel(periph: DmaPeriph);
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D);
+    fn write_sel(periph: DmaPeriph);
 }
 
 #[cfg(not(any(
@@ -300,24 +324,20 @@ impl RccPeriph for pac::TIM6 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 #[cfg(not(any(
@@ -339,24 +359,20 @@ impl RccPeriph for pac::TIM7 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 impl RccPeriph for pac::I2C1 {
@@ -365,23 +381,23 @@ impl RccPeriph for pac::I2C1 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::I2c1Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::I2c1Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::I2c1Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::I2c1Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::I2c1Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::I2c1Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::I2c1Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::I2c1Tx);
     }
 }
 
@@ -392,27 +408,31 @@ impl RccPeriph for pac::I2C2 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::I2c2Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::I2c2Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::I2c2Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::I2c2Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::I2c2Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::I2c2Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::I2c2Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::I2c2Tx);
     }
 }
 
-#[cfg(any(feature = "h7", feature = "wb"))]
+#[cfg(any(
+    feature = "h7",
+    feature = "wb",
+    all(feature = "f4", not(feature = "f410"))
+))]
 impl RccPeriph for pac::I2C3 {
     fn en_reset(rcc: &RegisterBlock) {
         rcc_en_reset!(apb1, i2c3, rcc);
@@ -426,23 +446,23 @@ impl RccPeriph for pac::SPI1 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Spi1Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi1Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Spi1Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi1Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi1Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi1Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi1Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi1Tx);
     }
 }
 
@@ -453,23 +473,23 @@ impl RccPeriph for pac::SPI2 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Spi2Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi2Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Spi2Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi2Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi2Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi2Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi2Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi2Tx);
     }
 }
 
@@ -495,23 +515,23 @@ impl RccPeriph for pac::SPI3 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Spi3Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi3Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Spi3Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Spi3Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi3Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi3Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Spi3Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Spi3Tx);
     }
 }
 
@@ -545,24 +565,20 @@ impl RccPeriph for pac::SAI1 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 #[cfg(all(feature = "h7", not(feature = "h735")))]
@@ -572,24 +588,20 @@ impl RccPeriph for pac::SAI2 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 #[cfg(all(feature = "h7", not(feature = "h735")))]
@@ -599,24 +611,20 @@ impl RccPeriph for pac::SAI3 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 #[cfg(feature = "h7")]
@@ -626,24 +634,20 @@ impl RccPeriph for pac::SAI4 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        unimplemented!()
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        unimplemented!()
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn read_sel(_periph: DmaPeriph) {}
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
-        unimplemented!()
-    }
+    fn write_sel(_periph: DmaPeriph) {}
 }
 
 // #[cfg(any(feature = "g0c1", feature = "g4", feature = "h7"))]
@@ -659,22 +663,22 @@ impl RccPeriph for pac::SAI4 {
 //     }
 //
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn read_chan() -> DmaChannel {
+//     fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
 //         unimplemented!()
 //     }
 //
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn write_chan() -> DmaChannel {
+//     fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
 //         unimplemented!()
 //     }
 //
 //     #[cfg(feature = "l4")]
-//     fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
+//     fn read_sel(_periph: DmaPeriph) {
 //         unimplemented!()
 //     }
 //
 //     #[cfg(feature = "l4")]
-//     fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {
+//     fn write_sel(_periph: DmaPeriph) {
 //         unimplemented!()
 //     }
 // }
@@ -685,23 +689,23 @@ impl RccPeriph for pac::USART1 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Usart1Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart1Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Usart1Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart1Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart1Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart1Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart1Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart1Tx);
     }
 }
 
@@ -721,23 +725,23 @@ impl RccPeriph for pac::USART2 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Usart2Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart2Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Usart2Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart2Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart2Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart2Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart2Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart2Tx);
     }
 }
 
@@ -766,23 +770,23 @@ impl RccPeriph for pac::USART3 {
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn read_chan() -> DmaChannel {
-        DmaInput::Usart3Rx.dma1_channel()
+    fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart3Rx.channel(periph)
     }
 
     #[cfg(any(feature = "f3", feature = "l4"))]
-    fn write_chan() -> DmaChannel {
-        DmaInput::Usart3Tx.dma1_channel()
+    fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+        DmaInput::Usart3Tx.channel(periph)
     }
 
     #[cfg(feature = "l4")]
-    fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart3Rx);
+    fn read_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart3Rx);
     }
 
     #[cfg(feature = "l4")]
-    fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-        dma::channel_select(regs, DmaInput::Usart3Tx);
+    fn write_sel(periph: DmaPeriph) {
+        dma::channel_select(periph, DmaInput::Usart3Tx);
     }
 }
 
@@ -794,23 +798,23 @@ cfg_if! {
             }
 
              #[cfg(feature = "l4")]
-            fn read_chan() -> DmaChannel {
-                DmaInput::Uart4Rx.dma1_channel()
+            fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+                DmaInput::Uart4Rx.channel(periph)
             }
 
             #[cfg(feature = "l4")]
-            fn write_chan() -> DmaChannel {
-                DmaInput::Uart4Tx.dma1_channel()
+            fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+                DmaInput::Uart4Tx.channel(periph)
             }
 
             #[cfg(feature = "l4")]
-            fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-                dma::channel_select(regs, DmaInput::Uart4Rx);
+            fn read_sel(periph: DmaPeriph) {
+                dma::channel_select(periph, DmaInput::Uart4Rx);
             }
 
             #[cfg(feature = "l4")]
-            fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-                dma::channel_select(regs, DmaInput::Uart4Tx);
+            fn write_sel(periph: DmaPeriph) {
+                dma::channel_select(periph, DmaInput::Uart4Tx);
             }
         }
 
@@ -820,23 +824,23 @@ cfg_if! {
             }
 
             #[cfg(feature = "l4")]
-            fn read_chan() -> DmaChannel {
-                DmaInput::Uart5Rx.dma1_channel()
+            fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+                DmaInput::Uart5Rx.channel(periph)
             }
 
             #[cfg(feature = "l4")]
-            fn write_chan() -> DmaChannel {
-                DmaInput::Uart5Tx.dma1_channel()
+            fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+                DmaInput::Uart5Tx.channel(periph)
             }
 
             #[cfg(feature = "l4")]
-            fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-                dma::channel_select(regs, DmaInput::Uart5Rx);
+            fn read_sel(periph: DmaPeriph) {
+                dma::channel_select(periph, DmaInput::Uart5Rx);
             }
 
             #[cfg(feature = "l4")]
-            fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(regs: &mut D) {
-                dma::channel_select(regs, DmaInput::Uart5Tx);
+            fn write_sel(periph: DmaPeriph) {
+                dma::channel_select(periph, DmaInput::Uart5Tx);
             }
         }
 
@@ -878,6 +882,52 @@ cfg_if! {
     }
 }
 
+#[cfg(any(
+    feature = "l4",
+    feature = "l5",
+    feature = "g4",
+    all(feature = "h7", not(feature = "h7b3")),
+    feature = "wb",
+))]
+impl RccPeriph for pac::LPUART1 {
+    fn en_reset(rcc: &RegisterBlock) {
+        cfg_if! {
+            if #[cfg(feature = "h7")] {
+                rcc.apb4enr.modify(|_, w| w.lpuart1en().set_bit());
+                rcc.apb4rstr.modify(|_, w| w.lpuart1rst().set_bit());
+                rcc.apb4rstr.modify(|_, w| w.lpuart1rst().clear_bit());
+            } else {
+                rcc.apb1enr2.modify(|_, w| w.lpuart1en().set_bit());
+                rcc.apb1rstr2.modify(|_, w| w.lpuart1rst().set_bit());
+                rcc.apb1rstr2.modify(|_, w| w.lpuart1rst().clear_bit());
+            }
+        }
+    }
+
+    #[cfg(feature = "l4")]
+    fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
+    }
+
+    #[cfg(feature = "l4")]
+    fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {
+        None
+    }
+
+    #[cfg(feature = "l4")]
+    fn read_sel(_periph: DmaPeriph) {}
+
+    #[cfg(feature = "l4")]
+    fn write_sel(_periph: DmaPeriph) {}
+}
+
+#[cfg(any(feature = "g031", feature = "g041", feature = "g071", feature = "g081"))]
+impl RccPeriph for pac::LPUART {
+    fn en_reset(rcc: &RegisterBlock) {
+        rcc_en_reset!(apb1, lpuart1, rcc);
+    }
+}
+
 #[cfg(not(any(
     feature = "f401",
     feature = "f411",
@@ -897,9 +947,9 @@ cfg_if! {
             fn en_reset(rcc: &RegisterBlock) {
                 rcc_en_reset!(apb1, dac1, rcc);
             }
-            fn read_chan() -> DmaChannel {unimplemented!()}
+            fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
 
-            fn write_chan() -> DmaChannel {unimplemented!()}
+            fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
         }
 
         #[cfg(any(feature = "f303", feature = "f373", feature = "f3x4"))]
@@ -907,9 +957,9 @@ cfg_if! {
             fn en_reset(rcc: &RegisterBlock) {
                 rcc_en_reset!(apb1, dac2, rcc);
             }
-            fn read_chan() -> DmaChannel {unimplemented!()}
+            fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
 
-            fn write_chan() -> DmaChannel {unimplemented!()}
+            fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
         }
     } else if #[cfg(feature = "g4")] {
         impl RccPeriph for DAC1 {
@@ -959,16 +1009,16 @@ cfg_if! {
             }
 
             #[cfg(feature = "l4")]
-            fn read_chan() -> DmaChannel {unimplemented!()}
+            fn read_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
 
             #[cfg(feature = "l4")]
-            fn write_chan() -> DmaChannel {unimplemented!()}
+            fn write_chan(_periph: DmaPeriph) -> Option<DmaChannel> {None}
 
             #[cfg(feature = "l4")]
-            fn read_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {unimplemented!()}
+            fn read_sel(_periph: DmaPeriph) {}
 
             #[cfg(feature = "l4")]
-            fn write_sel<D: Deref<Target = dma1::RegisterBlock>>(_regs: &mut D) {unimplemented!()}
+            fn write_sel(_periph: DmaPeriph) {}
         }
     }
 }
@@ -985,12 +1035,12 @@ cfg_if! {
 // #[cfg(any(feature = "f3", feature = "l4"))]
 // impl DmaPeriph for ADC1 {
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn read_chan() -> DmaChannel {
-//         DmaInput::Adc1.dma1_channel()
+//     fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+//         DmaInput::Adc1.channel(periph)
 //     }
 //
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn write_chan() -> DmaChannel {
+//     fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
 //         unimplemented!()
 //     }
 //
@@ -1014,12 +1064,12 @@ cfg_if! {
 // ))]
 // impl DmaPeriph for pac::ADC2 {
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn read_chan() -> DmaChannel {
-//         DmaInput::Adc2.dma1_channel()
+//     fn read_chan(periph: DmaPeriph) -> Option<DmaChannel> {
+//         DmaInput::Adc2.channel(periph)
 //     }
 //
 //     #[cfg(any(feature = "f3", feature = "l4"))]
-//     fn write_chan() -> DmaChannel {
+//     fn write_chan(periph: DmaPeriph) -> Option<DmaChannel> {
 //         unimplemented!()
 //     }
 //